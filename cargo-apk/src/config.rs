@@ -3,7 +3,6 @@ use cargo::ops;
 use cargo::util::CargoResult;
 use cargo::CliError;
 use failure::format_err;
-use itertools::Itertools;
 use serde::Deserialize;
 use std::collections::btree_map::BTreeMap;
 use std::env;
@@ -15,6 +14,13 @@ use std::path::Path;
 use std::path::PathBuf;
 use toml;
 
+/// Template of the names of the artifacts copied to `--out-dir` when `out_file_name` is not
+/// configured
+const DEFAULT_OUT_FILE_NAME: &str = "{target}-{abi}-{profile}";
+
+/// Template used instead of `DEFAULT_OUT_FILE_NAME` when a flavor is selected
+const DEFAULT_FLAVOR_OUT_FILE_NAME: &str = "{target}-{flavor}-{abi}-{profile}";
+
 #[derive(Clone)]
 pub struct AndroidConfig {
     /// Name of the cargo package
@@ -33,6 +39,24 @@ pub struct AndroidConfig {
     /// List of targets to build the app for. Eg. `armv7-linux-androideabi`.
     pub build_targets: Vec<AndroidBuildTarget>,
 
+    /// Build one APK per target architecture instead of a single APK containing all of them
+    pub split_per_abi: bool,
+    /// Build an APK containing all architectures in addition to the split APKs
+    pub universal_apk: bool,
+
+    /// Whether the shared libraries are extracted from the APK on installation. When false, they
+    /// are stored uncompressed and page-aligned so they can be loaded directly from the APK.
+    pub extract_native_libs: bool,
+    /// Page size to which uncompressed shared libraries are aligned. Either 4096 or 16384.
+    pub native_lib_page_size: u32,
+
+    /// Template of the names of the artifacts copied to the directory passed with `--out-dir`
+    pub out_file_name: String,
+
+    /// Modification time, in seconds since the unix epoch, given to all the package entries when
+    /// building reproducibly. `None` when entries keep the modification time of their files.
+    pub reproducible_timestamp: Option<u64>,
+
     /// Path to the android.jar for the selected android platform
     pub android_jar_path: PathBuf,
 
@@ -47,20 +71,90 @@ pub struct AndroidConfig {
     /// Should we build in release mode?
     pub release: bool,
 
+    /// Name of the selected flavor
+    pub flavor: Option<String>,
+
     /// Target configuration settings that are associated with a specific target
     default_target_config: TomlAndroidTarget,
 
     /// Target specific configuration settings
     target_configs: BTreeMap<(TargetKind, String), TomlAndroidTarget>,
+
+    /// Configuration settings of the `dev` and `release` profiles
+    profile_configs: TomlProfiles,
+
+    /// Configuration settings of the flavors, by name
+    flavor_configs: BTreeMap<String, TomlFlavor>,
+
+    /// Signing configuration and its per profile overrides
+    signing: TomlSigning,
 }
 
 impl AndroidConfig {
     /// Builds the android target config based on the default target config and the specific target configs defined in the manifest
     pub fn resolve(&self, target: (TargetKind, String)) -> CargoResult<AndroidTargetConfig> {
-        let primary_config = self.target_configs.get(&target);
+        // The configuration of the flavor overrides the target specific configuration, and the
+        // configuration of the current profile overrides both
+        let flavor_config = self.flavor_config();
+        let profile_config = if self.release {
+            self.profile_configs.release.as_ref()
+        } else {
+            self.profile_configs.dev.as_ref()
+        };
+        let is_default_target = target.1 == self.cargo_package_name;
+        // Like the ones of the default configuration, the label and package name of the flavor
+        // and of the profile only apply to the default target
+        let overriding_config = |config: &TomlAndroidTarget| {
+            let mut config = config.clone();
+            if !is_default_target {
+                config.label = None;
+                config.package_name = None;
+            }
+            config
+        };
+        let mut merged_config = self.target_configs.get(&target).cloned();
+        if let Some(flavor_config) = flavor_config {
+            merged_config =
+                Some(overriding_config(&flavor_config.config).or(merged_config.as_ref()));
+        }
+        if let Some(profile_config) = profile_config {
+            merged_config = Some(overriding_config(profile_config).or(merged_config.as_ref()));
+        }
+        let primary_config = merged_config.as_ref();
         let target_name = target.1;
-        let is_default_target = target_name == self.cargo_package_name;
         let example = target.0 == TargetKind::ExampleBin;
+        let (package_icon, icon_images) = resolve_icon(
+            self.manifest_path.parent().unwrap(),
+            primary_config
+                .and_then(|a| a.icon.clone())
+                .or_else(|| self.default_target_config.icon.clone()),
+        )?;
+        let application_meta_data = resolve_meta_data(
+            primary_config
+                .and_then(|a| a.application_metadata.clone())
+                .or_else(|| self.default_target_config.application_metadata.clone()),
+        )?;
+        let activity_meta_data = resolve_meta_data(
+            primary_config
+                .and_then(|a| a.activity_metadata.clone())
+                .or_else(|| self.default_target_config.activity_metadata.clone()),
+        )?;
+        let package_label = primary_config
+            .and_then(|a| a.label.clone())
+            .or_else(|| {
+                if is_default_target {
+                    self.default_target_config.label.clone()
+                } else {
+                    None
+                }
+            })
+            .unwrap_or_else(|| target_name.clone());
+        let (package_label, strings) = resolve_strings(
+            package_label,
+            primary_config
+                .and_then(|a| a.strings.clone())
+                .or_else(|| self.default_target_config.strings.clone()),
+        );
 
         Ok(AndroidTargetConfig {
             package_name: primary_config
@@ -78,17 +172,12 @@ impl AndroidConfig {
                     } else {
                         format!("rust.{}", target_name)
                     }
-                }),
-            package_label: primary_config
-                .and_then(|a| a.label.clone())
-                .or_else(|| {
-                    if is_default_target {
-                        self.default_target_config.label.clone()
-                    } else {
-                        None
-                    }
                 })
-                .unwrap_or_else(|| target_name.clone()),
+                + flavor_config
+                    .and_then(|a| a.package_name_suffix.as_deref())
+                    .unwrap_or(""),
+            package_label,
+            strings,
             version_code: primary_config
                 .and_then(|a| a.version_code)
                 .or_else(|| self.default_target_config.version_code)
@@ -97,17 +186,39 @@ impl AndroidConfig {
                 .and_then(|a| a.version_name.clone())
                 .or_else(|| self.default_target_config.version_name.clone())
                 .unwrap_or_else(|| self.cargo_package_version.clone()),
-            package_icon: primary_config
-                .and_then(|a| a.icon.clone())
-                .or_else(|| self.default_target_config.icon.clone()),
-            assets_path: primary_config
-                .and_then(|a| a.assets.as_ref())
-                .or_else(|| self.default_target_config.assets.as_ref())
-                .map(|p| self.manifest_path.parent().unwrap().join(p)),
-            res_path: primary_config
-                .and_then(|a| a.res.as_ref())
-                .or_else(|| self.default_target_config.res.as_ref())
-                .map(|p| self.manifest_path.parent().unwrap().join(p)),
+            package_icon,
+            icon_images,
+            asset_roots: primary_config
+                .and_then(|a| a.assets.clone())
+                .or_else(|| self.default_target_config.assets.clone())
+                .map(|assets| asset_roots(self.manifest_path.parent().unwrap(), assets))
+                .unwrap_or_default(),
+            assets_exclude: primary_config
+                .and_then(|a| a.assets_exclude.clone())
+                .or_else(|| self.default_target_config.assets_exclude.clone())
+                .unwrap_or_default(),
+            no_compress: primary_config
+                .and_then(|a| a.no_compress.clone())
+                .or_else(|| self.default_target_config.no_compress.clone())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|extension| extension.trim_start_matches('.').to_lowercase())
+                .collect(),
+            res_paths: primary_config
+                .and_then(|a| a.res.clone())
+                .or_else(|| self.default_target_config.res.clone())
+                .map(|res| match res {
+                    TomlRes::Path(path) => vec![path],
+                    TomlRes::Paths(paths) => paths,
+                })
+                .unwrap_or_default()
+                .into_iter()
+                .map(|path| self.manifest_path.parent().unwrap().join(path))
+                .collect(),
+            manifest_template: primary_config
+                .and_then(|a| a.manifest_template.clone())
+                .or_else(|| self.default_target_config.manifest_template.clone())
+                .map(|path| self.manifest_path.parent().unwrap().join(path)),
             fullscreen: primary_config
                 .and_then(|a| a.fullscreen)
                 .or_else(|| self.default_target_config.fullscreen)
@@ -115,11 +226,11 @@ impl AndroidConfig {
             application_attributes: primary_config
                 .and_then(|a| a.application_attributes.clone())
                 .or_else(|| self.default_target_config.application_attributes.clone())
-                .map(build_attribute_string),
+                .unwrap_or_default(),
             activity_attributes: primary_config
                 .and_then(|a| a.activity_attributes.clone())
                 .or_else(|| self.default_target_config.activity_attributes.clone())
-                .map(build_attribute_string),
+                .unwrap_or_default(),
             opengles_version_major: primary_config
                 .and_then(|a| a.opengles_version_major)
                 .or_else(|| self.default_target_config.opengles_version_major)
@@ -142,12 +253,154 @@ impl AndroidConfig {
                 .into_iter()
                 .map(AndroidPermission::from)
                 .collect(),
+            intent_filters: primary_config
+                .and_then(|a| a.intent_filter.clone())
+                .or_else(|| self.default_target_config.intent_filter.clone())
+                .unwrap_or_default()
+                .into_iter()
+                .map(AndroidIntentFilter::from)
+                .collect(),
+            application_meta_data,
+            activity_meta_data,
         })
     }
+
+    /// Selects the flavor whose configuration overrides the target configurations
+    pub fn set_flavor(&mut self, flavor: Option<&str>) -> CargoResult<()> {
+        if let Some(flavor) = flavor {
+            if !self.flavor_configs.contains_key(flavor) {
+                return Err(format_err!(
+                    "Unknown flavor `{}`. Flavors are defined in `[package.metadata.android.flavor.<name>]` sections.",
+                    flavor
+                ));
+            }
+            if self.out_file_name == DEFAULT_OUT_FILE_NAME {
+                self.out_file_name = DEFAULT_FLAVOR_OUT_FILE_NAME.to_owned();
+            }
+        }
+        self.flavor = flavor.map(str::to_owned);
+        Ok(())
+    }
+
+    /// Cargo features enabled by the selected flavor
+    pub fn cargo_features(&self) -> Vec<String> {
+        self.flavor_config()
+            .and_then(|a| a.cargo_features.clone())
+            .unwrap_or_default()
+    }
+
+    fn flavor_config(&self) -> Option<&TomlFlavor> {
+        self.flavor
+            .as_ref()
+            .and_then(|flavor| self.flavor_configs.get(flavor))
+    }
+
+    /// Resolves the signing configuration of the current profile. Returns `None` if no keystore
+    /// is configured, in which case the debug keystore is used.
+    pub fn signing_config(&self) -> CargoResult<Option<AndroidSigningConfig>> {
+        let profile_config = if self.release {
+            self.signing.release.as_ref()
+        } else {
+            self.signing.dev.as_ref()
+        };
+        let default_config = &self.signing.default_profile_config();
+        let manifest_dir = self.manifest_path.parent().unwrap();
+
+        // A keystore configured for the profile comes with its own alias and passwords. Settings
+        // of the profile without a keystore are layered over the default ones.
+        let (config, fallback) = match profile_config {
+            Some(profile_config) if profile_config.keystore.is_some() => (profile_config, None),
+            Some(profile_config) => (profile_config, Some(default_config)),
+            None => (default_config, None),
+        };
+
+        let keystore_path = match config
+            .keystore
+            .as_ref()
+            .or_else(|| fallback.and_then(|a| a.keystore.as_ref()))
+        {
+            Some(keystore) => manifest_dir.join(keystore),
+            None => return Ok(None),
+        };
+
+        let store_password = match config.store_password(manifest_dir)? {
+            Some(password) => Some(password),
+            None => fallback
+                .map(|a| a.store_password(manifest_dir))
+                .transpose()?
+                .flatten(),
+        };
+        // PEM and PK8 keys are not encrypted
+        let is_key_file = match keystore_path.extension().and_then(|e| e.to_str()) {
+            Some(extension) => extension == "pem" || extension == "pk8",
+            None => false,
+        };
+        let store_password = match store_password {
+            Some(password) => password,
+            None if is_key_file => String::new(),
+            None => {
+                return Err(format_err!(
+                    "No store password configured for keystore `{}`. Set `store_password_env` \
+                     or `store_password_file` in the signing configuration.",
+                    keystore_path.display()
+                ))
+            }
+        };
+        let key_password = match config.key_password(manifest_dir)? {
+            Some(password) => password,
+            None => fallback
+                .map(|a| a.key_password(manifest_dir))
+                .transpose()?
+                .flatten()
+                .unwrap_or_else(|| store_password.clone()),
+        };
+
+        Ok(Some(AndroidSigningConfig {
+            keystore_path,
+            key_alias: config
+                .key_alias
+                .clone()
+                .or_else(|| fallback.and_then(|a| a.key_alias.clone())),
+            store_password,
+            key_password,
+        }))
+    }
+}
+
+/// Reads a password from an environment variable or from the first line of a file. The
+/// environment variable takes precedence.
+fn read_password(
+    manifest_dir: &Path,
+    env_var: Option<&String>,
+    file: Option<&String>,
+) -> CargoResult<Option<String>> {
+    if let Some(env_var) = env_var {
+        let password = env::var(env_var).map_err(|_| {
+            format_err!(
+                "Environment variable `{}` containing a signing password is not set",
+                env_var
+            )
+        })?;
+        return Ok(Some(password));
+    }
+
+    if let Some(file) = file {
+        let path = manifest_dir.join(file);
+        let content = fs::read_to_string(&path).map_err(|e| {
+            format_err!(
+                "Unable to read signing password file `{}`. {}",
+                path.display(),
+                e
+            )
+        })?;
+        return Ok(Some(content.lines().next().unwrap_or("").to_owned()));
+    }
+
+    Ok(None)
 }
 
 /// Build targets supported by NDK
-#[derive(Debug, Copy, Clone, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
 pub enum AndroidBuildTarget {
     #[serde(rename(deserialize = "armv7-linux-androideabi"))]
     ArmV7a,
@@ -191,6 +444,111 @@ impl From<TomlPermission> for AndroidPermission {
     }
 }
 
+#[derive(Clone)]
+pub struct AndroidIntentFilter {
+    pub actions: Vec<String>,
+    pub categories: Vec<String>,
+    pub data: Vec<AndroidIntentFilterData>,
+    /// Whether Android verifies that the app is allowed to open the links of the filter
+    pub auto_verify: bool,
+}
+
+impl From<TomlIntentFilter> for AndroidIntentFilter {
+    fn from(f: TomlIntentFilter) -> Self {
+        AndroidIntentFilter {
+            actions: f.actions,
+            categories: f.categories.unwrap_or_default(),
+            data: f
+                .data
+                .unwrap_or_default()
+                .into_iter()
+                .map(|d| AndroidIntentFilterData {
+                    scheme: d.scheme,
+                    host: d.host,
+                    port: d.port,
+                    path: d.path,
+                    path_prefix: d.path_prefix,
+                    path_pattern: d.path_pattern,
+                    mime_type: d.mime_type,
+                })
+                .collect(),
+            auto_verify: f.auto_verify.unwrap_or(false),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AndroidMetaData {
+    pub name: String,
+    pub value: AndroidMetaDataValue,
+}
+
+#[derive(Clone)]
+pub enum AndroidMetaDataValue {
+    /// Value of a string, integer, float or boolean
+    Value(String),
+    /// Reference to a resource, such as `@xml/config`
+    Resource(String),
+}
+
+/// `<data>` element of an intent filter
+#[derive(Clone)]
+pub struct AndroidIntentFilterData {
+    pub scheme: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub path: Option<String>,
+    pub path_prefix: Option<String>,
+    pub path_pattern: Option<String>,
+    pub mime_type: Option<String>,
+}
+
+/// Directory whose files are shipped as assets, or single asset file
+pub struct AndroidAssetRoot {
+    pub path: PathBuf,
+
+    /// Directory of the `assets` directory of the package in which the files are placed. Empty
+    /// to place them at the root of the `assets` directory.
+    pub prefix: String,
+}
+
+/// Images from which the launcher icon is generated. At least one of `image` and `foreground`
+/// is set.
+#[derive(Clone)]
+pub struct AndroidIconImages {
+    /// Icon used before Android 8.0, and by all versions if there is no foreground. Defaults to
+    /// the foreground drawn over the background.
+    pub image: Option<PathBuf>,
+
+    /// Foreground layer of the adaptive icon used starting with Android 8.0
+    pub foreground: Option<PathBuf>,
+
+    /// Background layer of the adaptive icon
+    pub background: AndroidIconBackground,
+}
+
+#[derive(Clone)]
+pub enum AndroidIconBackground {
+    /// RGBA color
+    Color([u8; 4]),
+    Image(PathBuf),
+}
+
+/// Keystore and passwords used to sign the APKs
+pub struct AndroidSigningConfig {
+    /// Path to the JKS or PKCS#12 keystore, or to a PEM/PK8 key.
+    pub keystore_path: PathBuf,
+
+    /// Alias of the key in the keystore. Can be omitted if the keystore contains a single key.
+    pub key_alias: Option<String>,
+
+    /// Password of the keystore.
+    pub store_password: String,
+
+    /// Password of the key. Defaults to the password of the keystore.
+    pub key_password: String,
+}
+
 /// Android build settings for a specific target
 pub struct AndroidTargetConfig {
     /// Name that the package will have on the Android machine.
@@ -201,6 +559,10 @@ pub struct AndroidTargetConfig {
     /// Label for the package.
     pub package_label: String,
 
+    /// String resources by locale, the default strings having an empty locale. Includes the
+    /// `app_name` string referenced by the label when there are string resources.
+    pub strings: BTreeMap<String, BTreeMap<String, String>>,
+
     /// Internal version number for manifest.
     pub version_code: i32,
 
@@ -208,28 +570,46 @@ pub struct AndroidTargetConfig {
     pub version_name: String,
 
     /// Name of the launcher icon.
-    /// Versions of this icon with different resolutions have to reside in the res folder
+    /// Versions of this icon with different resolutions have to reside in the res folder, unless
+    /// they are generated from `icon_images`.
     pub package_icon: Option<String>,
 
-    /// If `Some`, a path that contains the list of assets to ship as part of the package.
+    /// Images from which the launcher icon is generated
+    pub icon_images: Option<AndroidIconImages>,
+
+    /// Directories containing the assets to ship as part of the package.
     ///
     /// The assets can later be loaded with the runtime library.
-    pub assets_path: Option<PathBuf>,
+    pub asset_roots: Vec<AndroidAssetRoot>,
+
+    /// Glob patterns of the asset files which are not shipped. Patterns without a `/` are matched
+    /// against the file names, others against the paths in the `assets` directory.
+    pub assets_exclude: Vec<String>,
+
+    /// Lowercase extensions of the asset files which are stored uncompressed, so that they can be
+    /// memory mapped
+    pub no_compress: Vec<String>,
 
-    /// If `Some`, a path that contains the list of resources to ship as part of the package.
+    /// Paths that contain the resources to ship as part of the package. Resources of a path
+    /// override the resources of the previous paths with the same type, qualifiers and name.
     ///
     /// The resources can later be loaded with the runtime library.
-    /// This folder contains for example the launcher icon, the styles and resolution dependent images.
-    pub res_path: Option<PathBuf>,
+    /// These folders contain for example the launcher icon, the styles and resolution dependent images.
+    pub res_paths: Vec<PathBuf>,
+
+    /// AndroidManifest.xml into which the generated manifest is merged
+    pub manifest_template: Option<PathBuf>,
 
     /// Should this app be in fullscreen mode (hides the title bar)?
     pub fullscreen: bool,
 
-    /// Appends this string to the application attributes in the AndroidManifest.xml
-    pub application_attributes: Option<String>,
+    /// Attributes of the application element in the AndroidManifest.xml, by name such as
+    /// `android:debuggable`. They replace the attributes generated by cargo-apk.
+    pub application_attributes: BTreeMap<String, String>,
 
-    /// Appends this string to the activity attributes in the AndroidManifest.xml
-    pub activity_attributes: Option<String>,
+    /// Attributes of the activity element in the AndroidManifest.xml, by name such as
+    /// `android:screenOrientation`. They replace the attributes generated by cargo-apk.
+    pub activity_attributes: BTreeMap<String, String>,
 
     /// The OpenGL ES major version in the AndroidManifest.xml
     pub opengles_version_major: u8,
@@ -242,6 +622,15 @@ pub struct AndroidTargetConfig {
 
     /// uses-permission in AndroidManifest.xml
     pub permissions: Vec<AndroidPermission>,
+
+    /// Intent filters of the activity in AndroidManifest.xml, in addition to the launcher one
+    pub intent_filters: Vec<AndroidIntentFilter>,
+
+    /// meta-data of the application in AndroidManifest.xml
+    pub application_meta_data: Vec<AndroidMetaData>,
+
+    /// meta-data of the activity in AndroidManifest.xml, in addition to `android.app.lib_name`
+    pub activity_meta_data: Vec<AndroidMetaData>,
 }
 
 pub fn load(
@@ -371,28 +760,43 @@ pub fn load(
         .and_then(|a| a.min_sdk_version)
         .unwrap_or(18);
 
+    let native_lib_page_size = manifest_content
+        .as_ref()
+        .and_then(|a| a.native_lib_page_size)
+        .unwrap_or(4096);
+    if native_lib_page_size != 4096 && native_lib_page_size != 16384 {
+        return Err(format_err!(
+            "Unsupported native_lib_page_size {}. Supported values are 4096 and 16384.",
+            native_lib_page_size
+        )
+        .into());
+    }
+
+    // Builds are reproducible when requested or when SOURCE_DATE_EPOCH is set, unless disabled
+    let source_date_epoch = match env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) => Some(value.trim().parse::<u64>().map_err(|_| {
+            format_err!(
+                "SOURCE_DATE_EPOCH must be a number of seconds since the unix epoch. Got `{}`.",
+                value
+            )
+        })?),
+        Err(_) => None,
+    };
+    let reproducible_timestamp = match manifest_content.as_ref().and_then(|a| a.reproducible) {
+        Some(false) => None,
+        Some(true) => Some(source_date_epoch.unwrap_or(0)),
+        None => source_date_epoch,
+    };
+
     let default_target_config = manifest_content
         .as_ref()
         .map(|a| a.default_target_config.clone())
         .unwrap_or_else(Default::default);
 
-    let mut target_configs = BTreeMap::new();
-    manifest_content
+    let target_configs = manifest_content
         .as_ref()
-        .and_then(|a| a.bin.as_ref())
-        .unwrap_or(&Vec::new())
-        .iter()
-        .for_each(|t| {
-            target_configs.insert((TargetKind::Bin, t.name.clone()), t.config.clone());
-        });
-    manifest_content
-        .as_ref()
-        .and_then(|a| a.example.as_ref())
-        .unwrap_or(&Vec::new())
-        .iter()
-        .for_each(|t| {
-            target_configs.insert((TargetKind::ExampleBin, t.name.clone()), t.config.clone());
-        });
+        .map(target_configs)
+        .unwrap_or_default();
 
     // For the moment some fields of the config are dummies.
     Ok(AndroidConfig {
@@ -406,6 +810,7 @@ pub fn load(
         min_sdk_version,
         build_tools_version,
         release: false,
+        flavor: None,
         build_targets: manifest_content
             .as_ref()
             .and_then(|a| a.build_targets.clone())
@@ -416,16 +821,239 @@ pub fn load(
                     AndroidBuildTarget::X86,
                 ]
             }),
+        split_per_abi: manifest_content
+            .as_ref()
+            .and_then(|a| a.split_per_abi)
+            .unwrap_or(false),
+        universal_apk: manifest_content
+            .as_ref()
+            .and_then(|a| a.universal_apk)
+            .unwrap_or(false),
+        extract_native_libs: manifest_content
+            .as_ref()
+            .and_then(|a| a.extract_native_libs)
+            .unwrap_or(true),
+        native_lib_page_size,
+        out_file_name: manifest_content
+            .as_ref()
+            .and_then(|a| a.out_file_name.clone())
+            .unwrap_or_else(|| DEFAULT_OUT_FILE_NAME.to_owned()),
+        reproducible_timestamp,
         default_target_config,
         target_configs,
+        profile_configs: manifest_content
+            .as_ref()
+            .and_then(|a| a.profile.clone())
+            .unwrap_or_default(),
+        flavor_configs: manifest_content
+            .as_ref()
+            .and_then(|a| a.flavor.clone())
+            .unwrap_or_default(),
+        signing: manifest_content
+            .as_ref()
+            .and_then(|a| a.signing.clone())
+            .unwrap_or_default(),
     })
 }
 
-fn build_attribute_string(input_map: BTreeMap<String, String>) -> String {
-    input_map
+/// Returns the configurations of the `[[bin]]` and `[[example]]` tables
+fn target_configs(android: &TomlAndroid) -> BTreeMap<(TargetKind, String), TomlAndroidTarget> {
+    let mut target_configs = BTreeMap::new();
+    android
+        .bin
+        .as_ref()
+        .unwrap_or(&Vec::new())
         .iter()
-        .map(|(key, val)| format!("\n{}=\"{}\"", key, val))
-        .join("")
+        .for_each(|t| {
+            target_configs.insert((TargetKind::Bin, t.name.clone()), t.config.clone());
+        });
+    android
+        .example
+        .as_ref()
+        .unwrap_or(&Vec::new())
+        .iter()
+        .for_each(|t| {
+            target_configs.insert((TargetKind::ExampleBin, t.name.clone()), t.config.clone());
+        });
+    target_configs
+}
+
+/// Name of the file in which a build script lists the assets it generated in its `OUT_DIR`
+pub const OUT_DIR_ASSETS_FILE_NAME: &str = "cargo-apk-assets.toml";
+
+/// Loads the assets listed by a build script in its `OUT_DIR`. Relative paths are relative to
+/// `OUT_DIR`. Returns no assets if the build script didn't list any.
+pub fn load_out_dir_asset_roots(out_dir: &Path) -> CargoResult<Vec<AndroidAssetRoot>> {
+    let path = out_dir.join(OUT_DIR_ASSETS_FILE_NAME);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format_err!("Unable to read `{}`. {}", path.display(), e))?;
+    let toml: TomlOutDirAssets = toml::from_str(&content)
+        .map_err(|e| format_err!("Unable to parse `{}`. {}", path.display(), e))?;
+    Ok(asset_roots(out_dir, toml.assets))
+}
+
+/// Returns the launcher icon resource and the images it is generated from. Icons which are not
+/// resource references are paths to a PNG image.
+fn resolve_icon(
+    base_directory: &Path,
+    icon: Option<TomlIcon>,
+) -> CargoResult<(Option<String>, Option<AndroidIconImages>)> {
+    let images = match icon {
+        None => return Ok((None, None)),
+        Some(TomlIcon::Resource(icon)) if icon.starts_with('@') => return Ok((Some(icon), None)),
+        Some(TomlIcon::Resource(image)) => TomlIconImages {
+            image: Some(image),
+            foreground: None,
+            background: None,
+        },
+        Some(TomlIcon::Images(images)) => images,
+    };
+    if images.image.is_none() && images.foreground.is_none() {
+        return Err(format_err!(
+            "The icon requires an `image` or a `foreground`"
+        ));
+    }
+    let background = match images.background {
+        Some(background) => {
+            if images.foreground.is_none() {
+                return Err(format_err!("The icon `background` requires a `foreground`"));
+            }
+            if background.starts_with('#') {
+                AndroidIconBackground::Color(parse_color(&background)?)
+            } else {
+                AndroidIconBackground::Image(base_directory.join(background))
+            }
+        }
+        None => AndroidIconBackground::Color([255, 255, 255, 255]),
+    };
+    Ok((
+        Some("@mipmap/ic_launcher".to_owned()),
+        Some(AndroidIconImages {
+            image: images.image.map(|image| base_directory.join(image)),
+            foreground: images
+                .foreground
+                .map(|foreground| base_directory.join(foreground)),
+            background,
+        }),
+    ))
+}
+
+/// Resolves meta-data entries, which have either a value or a resource
+fn resolve_meta_data(
+    toml_meta_data: Option<Vec<TomlMetaData>>,
+) -> CargoResult<Vec<AndroidMetaData>> {
+    toml_meta_data
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| {
+            let value = match (entry.value, entry.resource) {
+                (Some(toml::Value::String(value)), None) => AndroidMetaDataValue::Value(value),
+                (Some(toml::Value::Integer(value)), None) => {
+                    AndroidMetaDataValue::Value(value.to_string())
+                }
+                (Some(toml::Value::Float(value)), None) => {
+                    AndroidMetaDataValue::Value(value.to_string())
+                }
+                (Some(toml::Value::Boolean(value)), None) => {
+                    AndroidMetaDataValue::Value(value.to_string())
+                }
+                (Some(_), None) => {
+                    return Err(format_err!(
+                        "The value of meta-data `{}` must be a string, a number or a boolean",
+                        entry.name
+                    ))
+                }
+                (None, Some(resource)) => AndroidMetaDataValue::Resource(resource),
+                _ => {
+                    return Err(format_err!(
+                        "Meta-data `{}` must have either a value or a resource",
+                        entry.name
+                    ))
+                }
+            };
+            Ok(AndroidMetaData {
+                name: entry.name,
+                value,
+            })
+        })
+        .collect()
+}
+
+/// Returns the label and the string resources by locale. When there are string resources, the
+/// label references the `app_name` string, which defaults to the label, so that it can be
+/// translated.
+fn resolve_strings(
+    label: String,
+    toml_strings: Option<BTreeMap<String, TomlStrings>>,
+) -> (String, BTreeMap<String, BTreeMap<String, String>>) {
+    let mut strings = BTreeMap::<_, BTreeMap<_, _>>::new();
+    for (key, entry) in toml_strings.unwrap_or_default() {
+        match entry {
+            TomlStrings::String(value) => {
+                strings.entry(String::new()).or_default().insert(key, value);
+            }
+            TomlStrings::Locale(locale_strings) => {
+                strings.entry(key).or_default().extend(locale_strings);
+            }
+        }
+    }
+    if strings.is_empty() || label.starts_with('@') {
+        return (label, strings);
+    }
+    strings
+        .entry(String::new())
+        .or_default()
+        .entry("app_name".to_owned())
+        .or_insert(label);
+    ("@string/app_name".to_owned(), strings)
+}
+
+/// Parses a `#RGB`, `#ARGB`, `#RRGGBB` or `#AARRGGBB` color to RGBA
+fn parse_color(color: &str) -> CargoResult<[u8; 4]> {
+    let invalid_color = || format_err!("Invalid color `{}`", color);
+    let digits = color[1..]
+        .chars()
+        .map(|digit| digit.to_digit(16).map(|digit| digit as u8))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(invalid_color)?;
+    let components = match digits.len() {
+        3 | 4 => digits.iter().map(|digit| digit * 17).collect::<Vec<_>>(),
+        6 | 8 => digits
+            .chunks(2)
+            .map(|digits| digits[0] * 16 + digits[1])
+            .collect(),
+        _ => return Err(invalid_color()),
+    };
+    Ok(match components[..] {
+        [red, green, blue] => [red, green, blue, 255],
+        [alpha, red, green, blue] => [red, green, blue, alpha],
+        _ => unreachable!(),
+    })
+}
+
+fn asset_roots(base_directory: &Path, assets: TomlAssets) -> Vec<AndroidAssetRoot> {
+    let roots = match assets {
+        TomlAssets::Path(path) => vec![TomlAssetRoot::Path(path)],
+        TomlAssets::Roots(roots) => roots,
+    };
+    roots
+        .into_iter()
+        .map(|root| {
+            let (path, prefix) = match root {
+                TomlAssetRoot::Path(path) => (path, None),
+                TomlAssetRoot::Directory(directory) => (directory.path, directory.prefix),
+            };
+            AndroidAssetRoot {
+                path: base_directory.join(path),
+                prefix: prefix
+                    .map(|prefix| prefix.trim_matches('/').to_owned())
+                    .unwrap_or_default(),
+            }
+        })
+        .collect()
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -451,12 +1079,23 @@ struct TomlAndroid {
     target_sdk_version: Option<u32>,
     min_sdk_version: Option<u32>,
     build_targets: Option<Vec<AndroidBuildTarget>>,
+    split_per_abi: Option<bool>,
+    universal_apk: Option<bool>,
+    extract_native_libs: Option<bool>,
+    native_lib_page_size: Option<u32>,
+    out_file_name: Option<String>,
+    reproducible: Option<bool>,
 
     #[serde(flatten)]
     default_target_config: TomlAndroidTarget,
 
     bin: Option<Vec<TomlAndroidSpecificTarget>>,
     example: Option<Vec<TomlAndroidSpecificTarget>>,
+
+    profile: Option<TomlProfiles>,
+    flavor: Option<BTreeMap<String, TomlFlavor>>,
+
+    signing: Option<TomlSigning>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -474,6 +1113,96 @@ struct TomlPermission {
     max_sdk_version: Option<u32>,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlMetaData {
+    name: String,
+    value: Option<toml::Value>,
+    resource: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlIntentFilter {
+    actions: Vec<String>,
+    categories: Option<Vec<String>>,
+    data: Option<Vec<TomlIntentFilterData>>,
+    auto_verify: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlIntentFilterData {
+    scheme: Option<String>,
+    host: Option<String>,
+    port: Option<u16>,
+    path: Option<String>,
+    path_prefix: Option<String>,
+    path_pattern: Option<String>,
+    mime_type: Option<String>,
+}
+
+/// Either a single assets directory or a list of them
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum TomlAssets {
+    Path(String),
+    Roots(Vec<TomlAssetRoot>),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum TomlAssetRoot {
+    Path(String),
+    Directory(TomlAssetDirectory),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlAssetDirectory {
+    path: String,
+    prefix: Option<String>,
+}
+
+/// Either a reference to the launcher icon resource or the images to generate it from
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum TomlIcon {
+    Resource(String),
+    Images(TomlIconImages),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlIconImages {
+    image: Option<String>,
+    foreground: Option<String>,
+    background: Option<String>,
+}
+
+/// Entry of the `strings` table: either a default string or the strings of a locale
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum TomlStrings {
+    String(String),
+    Locale(BTreeMap<String, String>),
+}
+
+/// Either a single resources directory or a list of them, later ones overriding earlier ones
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum TomlRes {
+    Path(String),
+    Paths(Vec<String>),
+}
+
+/// Assets listed by a build script in `OUT_DIR`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlOutDirAssets {
+    assets: TomlAssets,
+}
+
 /// Configuration specific to a single cargo target
 #[derive(Debug, Clone, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -485,14 +1214,19 @@ struct TomlAndroidSpecificTarget {
 }
 
 #[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 struct TomlAndroidTarget {
     package_name: Option<String>,
     label: Option<String>,
     version_code: Option<i32>,
     version_name: Option<String>,
-    icon: Option<String>,
-    assets: Option<String>,
-    res: Option<String>,
+    icon: Option<TomlIcon>,
+    strings: Option<BTreeMap<String, TomlStrings>>,
+    assets: Option<TomlAssets>,
+    assets_exclude: Option<Vec<String>>,
+    no_compress: Option<Vec<String>>,
+    res: Option<TomlRes>,
+    manifest_template: Option<String>,
     fullscreen: Option<bool>,
     application_attributes: Option<BTreeMap<String, String>>,
     activity_attributes: Option<BTreeMap<String, String>>,
@@ -500,4 +1234,418 @@ struct TomlAndroidTarget {
     opengles_version_minor: Option<u8>,
     feature: Option<Vec<TomlFeature>>,
     permission: Option<Vec<TomlPermission>>,
+    intent_filter: Option<Vec<TomlIntentFilter>>,
+    application_metadata: Option<Vec<TomlMetaData>>,
+    activity_metadata: Option<Vec<TomlMetaData>>,
+}
+
+impl TomlAndroidTarget {
+    /// Returns this configuration with the fields it doesn't set taken from a fallback
+    fn or(self, fallback: Option<&TomlAndroidTarget>) -> TomlAndroidTarget {
+        let fallback = match fallback {
+            Some(fallback) => fallback,
+            None => return self,
+        };
+        TomlAndroidTarget {
+            package_name: self.package_name.or_else(|| fallback.package_name.clone()),
+            label: self.label.or_else(|| fallback.label.clone()),
+            version_code: self.version_code.or(fallback.version_code),
+            version_name: self.version_name.or_else(|| fallback.version_name.clone()),
+            icon: self.icon.or_else(|| fallback.icon.clone()),
+            strings: self.strings.or_else(|| fallback.strings.clone()),
+            assets: self.assets.or_else(|| fallback.assets.clone()),
+            assets_exclude: self
+                .assets_exclude
+                .or_else(|| fallback.assets_exclude.clone()),
+            no_compress: self.no_compress.or_else(|| fallback.no_compress.clone()),
+            res: self.res.or_else(|| fallback.res.clone()),
+            manifest_template: self
+                .manifest_template
+                .or_else(|| fallback.manifest_template.clone()),
+            fullscreen: self.fullscreen.or(fallback.fullscreen),
+            application_attributes: self
+                .application_attributes
+                .or_else(|| fallback.application_attributes.clone()),
+            activity_attributes: self
+                .activity_attributes
+                .or_else(|| fallback.activity_attributes.clone()),
+            opengles_version_major: self
+                .opengles_version_major
+                .or(fallback.opengles_version_major),
+            opengles_version_minor: self
+                .opengles_version_minor
+                .or(fallback.opengles_version_minor),
+            feature: self.feature.or_else(|| fallback.feature.clone()),
+            permission: self.permission.or_else(|| fallback.permission.clone()),
+            intent_filter: self
+                .intent_filter
+                .or_else(|| fallback.intent_filter.clone()),
+            application_metadata: self
+                .application_metadata
+                .or_else(|| fallback.application_metadata.clone()),
+            activity_metadata: self
+                .activity_metadata
+                .or_else(|| fallback.activity_metadata.clone()),
+        }
+    }
+}
+
+/// Configuration of the `dev` and `release` profiles, overriding the other configuration
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlProfiles {
+    dev: Option<TomlAndroidTarget>,
+    release: Option<TomlAndroidTarget>,
+}
+
+/// Configuration of a flavor, overriding the target configurations
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlFlavor {
+    #[serde(flatten)]
+    config: TomlAndroidTarget,
+
+    /// Appended to the package names of the targets
+    package_name_suffix: Option<String>,
+    /// Cargo features enabled when compiling the targets
+    cargo_features: Option<Vec<String>>,
+}
+
+/// Signing configuration. The `dev` and `release` tables override the values for a profile.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlSigning {
+    keystore: Option<String>,
+    key_alias: Option<String>,
+    store_password_env: Option<String>,
+    store_password_file: Option<String>,
+    key_password_env: Option<String>,
+    key_password_file: Option<String>,
+
+    dev: Option<TomlSigningProfile>,
+    release: Option<TomlSigningProfile>,
+}
+
+impl TomlSigning {
+    /// Returns the configuration used by the profiles which don't override it
+    fn default_profile_config(&self) -> TomlSigningProfile {
+        TomlSigningProfile {
+            keystore: self.keystore.clone(),
+            key_alias: self.key_alias.clone(),
+            store_password_env: self.store_password_env.clone(),
+            store_password_file: self.store_password_file.clone(),
+            key_password_env: self.key_password_env.clone(),
+            key_password_file: self.key_password_file.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlSigningProfile {
+    keystore: Option<String>,
+    key_alias: Option<String>,
+    store_password_env: Option<String>,
+    store_password_file: Option<String>,
+    key_password_env: Option<String>,
+    key_password_file: Option<String>,
+}
+
+impl TomlSigningProfile {
+    fn store_password(&self, manifest_dir: &Path) -> CargoResult<Option<String>> {
+        read_password(
+            manifest_dir,
+            self.store_password_env.as_ref(),
+            self.store_password_file.as_ref(),
+        )
+    }
+
+    fn key_password(&self, manifest_dir: &Path) -> CargoResult<Option<String>> {
+        read_password(
+            manifest_dir,
+            self.key_password_env.as_ref(),
+            self.key_password_file.as_ref(),
+        )
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::ops::TempDir;
+
+    /// Returns the configuration of a package named `app` whose `[package.metadata.android]`
+    /// table is `android`
+    pub(crate) fn config(manifest_dir: &Path, android: &str) -> AndroidConfig {
+        let android: TomlAndroid = toml::from_str(android).unwrap();
+        let target_configs = target_configs(&android);
+        AndroidConfig {
+            cargo_package_name: "app".to_owned(),
+            cargo_package_version: "1.0.0".to_owned(),
+            manifest_path: manifest_dir.join("Cargo.toml"),
+            sdk_path: PathBuf::new(),
+            ndk_path: PathBuf::new(),
+            build_targets: Vec::new(),
+            split_per_abi: false,
+            universal_apk: false,
+            extract_native_libs: true,
+            native_lib_page_size: 4096,
+            out_file_name: DEFAULT_OUT_FILE_NAME.to_owned(),
+            reproducible_timestamp: None,
+            android_jar_path: PathBuf::new(),
+            target_sdk_version: 29,
+            min_sdk_version: 18,
+            build_tools_version: String::new(),
+            release: false,
+            flavor: None,
+            default_target_config: android.default_target_config,
+            target_configs,
+            profile_configs: android.profile.unwrap_or_default(),
+            flavor_configs: android.flavor.unwrap_or_default(),
+            signing: android.signing.unwrap_or_default(),
+        }
+    }
+
+    fn resolve(config: &AndroidConfig, kind: TargetKind, name: &str) -> AndroidTargetConfig {
+        config.resolve((kind, name.to_owned())).unwrap()
+    }
+
+    const LAYERED_CONFIG: &str = r#"
+        label = "App"
+        package_name = "com.example.app"
+        version_code = 1
+        fullscreen = true
+
+        [[bin]]
+        name = "app"
+        version_code = 2
+        opengles_version_major = 3
+
+        [[example]]
+        name = "demo"
+
+        [profile.release]
+        label = "App Release"
+        package_name = "com.example.release"
+        version_code = 3
+    "#;
+
+    #[test]
+    fn resolve_targets() {
+        let dir = TempDir::new("config-resolve-targets").unwrap();
+        let config = config(&dir.path, LAYERED_CONFIG);
+
+        // The target configuration overrides the default one
+        let app = resolve(&config, TargetKind::Bin, "app");
+        assert_eq!(app.package_label, "App");
+        assert_eq!(app.package_name, "com.example.app");
+        assert_eq!(app.version_code, 2);
+        assert_eq!(app.opengles_version_major, 3);
+        assert!(app.fullscreen);
+
+        // The default label and package name only apply to the default target
+        let demo = resolve(&config, TargetKind::ExampleBin, "demo");
+        assert_eq!(demo.package_label, "demo");
+        assert_eq!(demo.package_name, "rust.app.example.demo");
+        assert_eq!(demo.version_code, 1);
+        assert_eq!(demo.opengles_version_major, 2);
+        assert!(demo.fullscreen);
+    }
+
+    #[test]
+    fn resolve_profile() {
+        let dir = TempDir::new("config-resolve-profile").unwrap();
+        let mut config = config(&dir.path, LAYERED_CONFIG);
+        config.release = true;
+
+        let app = resolve(&config, TargetKind::Bin, "app");
+        assert_eq!(app.package_label, "App Release");
+        assert_eq!(app.package_name, "com.example.release");
+        assert_eq!(app.version_code, 3);
+        assert_eq!(app.opengles_version_major, 3);
+
+        // Like the default ones, the label and package name of the profile only apply to the
+        // default target
+        let demo = resolve(&config, TargetKind::ExampleBin, "demo");
+        assert_eq!(demo.package_label, "demo");
+        assert_eq!(demo.package_name, "rust.app.example.demo");
+        assert_eq!(demo.version_code, 3);
+    }
+
+    const FLAVORED_CONFIG: &str = r#"
+        label = "App"
+        package_name = "com.example.app"
+        version_code = 1
+
+        [[bin]]
+        name = "app"
+        version_code = 2
+
+        [[example]]
+        name = "demo"
+
+        [flavor.pro]
+        label = "App Pro"
+        package_name = "com.example.pro"
+        package_name_suffix = ".pro"
+        cargo_features = ["pro"]
+        version_code = 10
+        fullscreen = true
+
+        [flavor.free]
+
+        [profile.release]
+        fullscreen = false
+    "#;
+
+    #[test]
+    fn resolve_flavor() {
+        let dir = TempDir::new("config-resolve-flavor").unwrap();
+        let mut config = config(&dir.path, FLAVORED_CONFIG);
+        config.set_flavor(Some("pro")).unwrap();
+        assert_eq!(config.cargo_features(), ["pro"]);
+
+        // The flavor overrides the target configuration
+        let app = resolve(&config, TargetKind::Bin, "app");
+        assert_eq!(app.package_label, "App Pro");
+        assert_eq!(app.package_name, "com.example.pro.pro");
+        assert_eq!(app.version_code, 10);
+        assert!(app.fullscreen);
+
+        // Only the suffix is applied to the package names of the other targets
+        let demo = resolve(&config, TargetKind::ExampleBin, "demo");
+        assert_eq!(demo.package_label, "demo");
+        assert_eq!(demo.package_name, "rust.app.example.demo.pro");
+        assert_eq!(demo.version_code, 10);
+
+        // The profile overrides the flavor
+        config.release = true;
+        assert!(!resolve(&config, TargetKind::Bin, "app").fullscreen);
+
+        config.set_flavor(Some("free")).unwrap();
+        assert!(config.cargo_features().is_empty());
+        let app = resolve(&config, TargetKind::Bin, "app");
+        assert_eq!(app.package_label, "App");
+        assert_eq!(app.package_name, "com.example.app");
+        assert_eq!(app.version_code, 2);
+    }
+
+    #[test]
+    fn set_flavor() {
+        let dir = TempDir::new("config-set-flavor").unwrap();
+        let mut config = config(&dir.path, FLAVORED_CONFIG);
+        assert!(config.set_flavor(Some("unknown")).is_err());
+        assert_eq!(config.out_file_name, DEFAULT_OUT_FILE_NAME);
+
+        config.set_flavor(Some("pro")).unwrap();
+        assert_eq!(config.flavor.as_deref(), Some("pro"));
+        assert_eq!(config.out_file_name, DEFAULT_FLAVOR_OUT_FILE_NAME);
+
+        config.set_flavor(None).unwrap();
+        assert_eq!(config.flavor, None);
+        assert!(config.cargo_features().is_empty());
+    }
+
+    #[test]
+    fn flavor_unknown_fields() {
+        assert!(toml::from_str::<TomlAndroid>("[flavor.pro]\nlable = \"App\"").is_err());
+    }
+
+    #[test]
+    fn profile_unknown_fields() {
+        for profile in &[
+            "[profile.debug]\nlabel = \"App\"",
+            "[profile.release]\nlable = \"App\"",
+        ] {
+            assert!(toml::from_str::<TomlAndroid>(profile).is_err());
+        }
+    }
+
+    fn signing_config(config: &mut AndroidConfig, release: bool) -> AndroidSigningConfig {
+        config.release = release;
+        config.signing_config().unwrap().unwrap()
+    }
+
+    #[test]
+    fn signing_profiles() {
+        let dir = TempDir::new("config-signing").unwrap();
+        fs::write(dir.path.join("store_password.txt"), "store\nignored").unwrap();
+        fs::write(dir.path.join("key_password.txt"), "key").unwrap();
+        fs::write(dir.path.join("release_password.txt"), "release").unwrap();
+
+        let config = self::config(&dir.path, "");
+        assert!(config.signing_config().unwrap().is_none());
+
+        let mut config = self::config(
+            &dir.path,
+            r#"
+            [signing]
+            keystore = "debug.jks"
+            key_alias = "debug"
+            store_password_file = "store_password.txt"
+
+            [signing.dev]
+            key_password_file = "key_password.txt"
+
+            [signing.release]
+            keystore = "release.jks"
+            store_password_file = "release_password.txt"
+            "#,
+        );
+
+        // The dev profile is layered over the default configuration
+        let dev = signing_config(&mut config, false);
+        assert_eq!(dev.keystore_path, dir.path.join("debug.jks"));
+        assert_eq!(dev.key_alias.as_deref(), Some("debug"));
+        assert_eq!(dev.store_password, "store");
+        assert_eq!(dev.key_password, "key");
+
+        // The release keystore doesn't inherit the alias and passwords of the default one
+        let release = signing_config(&mut config, true);
+        assert_eq!(release.keystore_path, dir.path.join("release.jks"));
+        assert_eq!(release.key_alias, None);
+        assert_eq!(release.store_password, "release");
+        assert_eq!(release.key_password, "release");
+    }
+
+    #[test]
+    fn signing_store_password() {
+        let dir = TempDir::new("config-store-password").unwrap();
+        fs::write(dir.path.join("password.txt"), "password").unwrap();
+        let mut config = self::config(
+            &dir.path,
+            r#"
+            [signing]
+            keystore = "debug.jks"
+            store_password_file = "password.txt"
+
+            [signing.release]
+            keystore = "release.jks"
+            "#,
+        );
+        config.release = true;
+        let error = config.signing_config().err().unwrap().to_string();
+        assert!(error.starts_with("No store password configured for keystore"));
+
+        // PEM and PK8 keys are not encrypted
+        for key in &["release.pem", "release.pk8"] {
+            let mut config = self::config(
+                &dir.path,
+                &format!("[signing.release]\nkeystore = \"{}\"", key),
+            );
+            let release = signing_config(&mut config, true);
+            assert_eq!(release.store_password, "");
+            assert_eq!(release.key_password, "");
+        }
+    }
+
+    #[test]
+    fn signing_unknown_fields() {
+        for signing in &[
+            "[signing]\nkeystore_path = \"release.jks\"",
+            "[signing.release]\nalias = \"release\"",
+        ] {
+            assert!(toml::from_str::<TomlAndroid>(signing).is_err());
+        }
+    }
 }
@@ -58,9 +58,10 @@ fn main() {
         "install" => execute_install(&subcommand_args, &cargo_config),
         "run" => execute_run(&subcommand_args, &cargo_config),
         "logcat" => execute_logcat(&subcommand_args, &cargo_config),
+        "inspect" => execute_inspect(subcommand_args, &cargo_config),
         _ => cargo::exit_with_error(
             format_err!(
-                "Expected `build`, `install`, `run`, or `logcat`. Got {}",
+                "Expected `build`, `install`, `run`, `logcat` or `inspect`. Got {}",
                 command
             )
             .into(),
@@ -115,6 +116,7 @@ fn cli() -> App<'static, 'static> {
             cli_install(),
             cli_run(),
             cli_logcat(),
+            cli_inspect(),
         ])
 }
 
@@ -126,7 +128,13 @@ fn cli_apk() -> App<'static, 'static> {
             AppSettings::DontCollapseArgsInUsage,
         ])
         .about("dummy subcommand to allow for calling cargo apk instead of cargo-apk")
-        .subcommands(vec![cli_build(), cli_install(), cli_run(), cli_logcat()])
+        .subcommands(vec![
+            cli_build(),
+            cli_install(),
+            cli_run(),
+            cli_logcat(),
+            cli_inspect(),
+        ])
 }
 
 fn cli_build() -> App<'static, 'static> {
@@ -157,10 +165,24 @@ fn cli_build() -> App<'static, 'static> {
             "Build all targets",
         )
         .arg_release("Build artifacts in release mode, with optimizations")
+        .arg(opt("flavor", "Build the given flavor of the app").value_name("NAME"))
         .arg_features()
         .arg_target_triple("Build for the target triple")
         .arg_target_dir()
         .arg(opt("out-dir", "Copy final artifacts to this directory").value_name("PATH"))
+        .arg(
+            opt("format", "Package format to produce: apk (default) or aab")
+                .value_name("FORMAT")
+                .possible_values(&["apk", "aab"]),
+        )
+        .arg(
+            opt(
+                "size-report",
+                "Print the size of the APKs by entry, crate and symbol as a table or as JSON",
+            )
+            .value_name("FORMAT")
+            .possible_values(&["table", "json"]),
+        )
         .arg_manifest_path()
         .arg_message_format()
         .arg_build_plan()
@@ -204,6 +226,7 @@ fn cli_install() -> App<'static, 'static> {
         .arg(opt("force", "Force overwriting existing crates or binaries").short("f"))
         .arg_features()
         .arg(opt("debug", "Build in debug mode instead of release mode"))
+        .arg(opt("flavor", "Build the given flavor of the app").value_name("NAME"))
         .arg_targets_bins_examples(
             "Install only the specified binary",
             "Install all binaries",
@@ -270,6 +293,7 @@ fn cli_run() -> App<'static, 'static> {
         .arg_package("Package with the target to run")
         .arg_jobs()
         .arg_release("Build artifacts in release mode, with optimizations")
+        .arg(opt("flavor", "Build the given flavor of the app").value_name("NAME"))
         .arg_features()
         .arg_target_triple("Build for the target triple")
         .arg_target_dir()
@@ -301,6 +325,21 @@ fn cli_logcat() -> App<'static, 'static> {
         .arg_message_format()
 }
 
+fn cli_inspect() -> App<'static, 'static> {
+    SubCommand::with_name("inspect")
+        .settings(&[
+            AppSettings::UnifiedHelpMessage,
+            AppSettings::DeriveDisplayOrder,
+            AppSettings::DontCollapseArgsInUsage,
+        ])
+        .about("Print the manifest, entries, ABIs and signers of an APK")
+        .arg(
+            Arg::with_name("APK")
+                .help("Path to the APK to inspect")
+                .required(true),
+        )
+}
+
 pub fn execute_build(options: &ArgMatches, cargo_config: &CargoConfig) -> cargo::CliResult {
     let root_manifest = options.root_manifest(&cargo_config)?;
 
@@ -311,6 +350,7 @@ pub fn execute_build(options: &ArgMatches, cargo_config: &CargoConfig) -> cargo:
         &options.value_of("package").map(|s| s.to_owned()),
     )?;
     android_config.release = options.is_present("release");
+    android_config.set_flavor(options.value_of("flavor"))?;
 
     ops::build(&workspace, &android_config, &options)?;
     Ok(())
@@ -326,6 +366,7 @@ pub fn execute_install(options: &ArgMatches, cargo_config: &CargoConfig) -> carg
         &options.value_of("package").map(|s| s.to_owned()),
     )?;
     android_config.release = !options.is_present("debug");
+    android_config.set_flavor(options.value_of("flavor"))?;
 
     ops::install(&workspace, &android_config, &options)?;
     Ok(())
@@ -341,6 +382,7 @@ pub fn execute_run(options: &ArgMatches, cargo_config: &CargoConfig) -> cargo::C
         &options.value_of("package").map(|s| s.to_owned()),
     )?;
     android_config.release = options.is_present("release");
+    android_config.set_flavor(options.value_of("flavor"))?;
 
     ops::run(&workspace, &android_config, &options)?;
     Ok(())
@@ -365,3 +407,9 @@ pub fn execute_logcat(options: &ArgMatches, cargo_config: &CargoConfig) -> cargo
 
     Ok(())
 }
+
+pub fn execute_inspect(options: &ArgMatches, cargo_config: &CargoConfig) -> cargo::CliResult {
+    let apk_path = cargo_config.cwd().join(options.value_of("APK").unwrap());
+    ops::inspect(&apk_path)?;
+    Ok(())
+}
@@ -1,26 +1,66 @@
+pub mod apk;
+mod assets;
+pub mod axml;
+mod bundle;
 mod compile;
+mod debug_symbols;
+mod elf;
+mod fingerprint;
+mod icon;
+mod manifest;
+mod out_dir;
+mod png;
+mod resources;
+pub mod sign;
+mod size_report;
+mod strings;
 mod targets;
 pub mod tempfile;
 mod util;
 
-use self::compile::SharedLibraries;
-use crate::config::{AndroidConfig, AndroidTargetConfig};
+use self::apk::{ApkEntry, ApkReader, ApkWriter, Compression, DosDateTime};
+use self::compile::{SharedLibraries, SharedLibrary};
+use self::fingerprint::{FingerprintHasher, Fingerprints};
+use self::sign::SigningKey;
+use self::size_report::SizeReportFormat;
+use crate::config::{
+    AndroidBuildTarget, AndroidConfig, AndroidMetaData, AndroidMetaDataValue, AndroidTargetConfig,
+};
 use cargo::core::{Target, TargetKind, Workspace};
-use cargo::util::process_builder::process;
+use cargo::util::command_prelude::ArgMatchesExt;
 use cargo::util::CargoResult;
 use clap::ArgMatches;
 use failure::format_err;
-use std::collections::BTreeMap;
-use std::fs::File;
-use std::io::Write;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
-use std::{env, fs};
 
 #[derive(Debug)]
 pub struct BuildResult {
     /// Mapping from target kind and target name to the built APK
     pub target_to_apk_map: BTreeMap<(TargetKind, String), PathBuf>,
+    /// Mapping from target kind and target name to the APKs built for each ABI when
+    /// `split_per_abi` is enabled
+    pub target_to_split_apks_map:
+        BTreeMap<(TargetKind, String), Vec<(AndroidBuildTarget, PathBuf)>>,
+    /// Mapping from target kind and target name to the built bundle when building with
+    /// `--format aab`
+    pub target_to_bundle_map: BTreeMap<(TargetKind, String), PathBuf>,
+    /// Mapping from target kind and target name to the zip of the unstripped shared libraries of
+    /// release builds
+    pub target_to_debug_symbols_map: BTreeMap<(TargetKind, String), PathBuf>,
+}
+
+/// Multiplier of the version code offsets of split APKs. The version code of the package must be
+/// lower so that the version codes of different ABIs don't overlap.
+const ABI_VERSION_CODE_MULTIPLIER: i32 = 1000;
+
+/// Format of the packages produced for each target
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum PackageFormat {
+    Apk,
+    Aab,
 }
 
 pub fn build(
@@ -28,23 +68,80 @@ pub fn build(
     config: &AndroidConfig,
     options: &ArgMatches,
 ) -> CargoResult<BuildResult> {
+    // Load the signing key before compiling so that configuration errors are reported early.
+    // Without a configured keystore, the debug keystore used by the Android SDK is used.
+    let signing_key = match config.signing_config()? {
+        Some(signing_config) => SigningKey::load(
+            &signing_config.keystore_path,
+            &signing_config.store_password,
+            signing_config.key_alias.as_deref(),
+            &signing_config.key_password,
+        )?,
+        None => {
+            if config.release {
+                workspace.config().shell().warn(
+                    "No keystore configured in `[package.metadata.android.signing]`. \
+                     The release APKs are signed with the debug keystore.",
+                )?;
+            }
+            sign::debug_signing_key()?
+        }
+    };
+
     let root_build_dir = util::get_root_build_directory(workspace, config);
     let shared_libraries =
         compile::build_shared_libraries(workspace, config, options, &root_build_dir)?;
-    build_apks(config, &root_build_dir, shared_libraries)
+    let format = match options.value_of("format") {
+        None | Some("apk") => PackageFormat::Apk,
+        Some("aab") => PackageFormat::Aab,
+        Some(format) => return Err(format_err!("Unsupported package format: {}", format)),
+    };
+    let size_report_format = match options.value_of("size-report") {
+        None => None,
+        Some(_) if format == PackageFormat::Aab => {
+            return Err(format_err!("Size reports are only supported for APKs"))
+        }
+        Some("table") => Some(SizeReportFormat::Table),
+        Some("json") => Some(SizeReportFormat::Json),
+        Some(format) => return Err(format_err!("Unsupported size report format: {}", format)),
+    };
+
+    let build_result = build_apks(
+        config,
+        &root_build_dir,
+        &shared_libraries,
+        &signing_key,
+        format,
+    )?;
+
+    if let Some(size_report_format) = size_report_format {
+        size_report::print(&build_result, &shared_libraries, size_report_format)?;
+    }
+
+    // Copy the artifacts to the directory passed with --out-dir
+    if let Some(out_dir) = options.value_of_path("out-dir", workspace.config()) {
+        out_dir::copy_artifacts(config, &build_result, &out_dir)?;
+    }
+
+    Ok(build_result)
 }
 
 fn build_apks(
     config: &AndroidConfig,
     root_build_dir: &PathBuf,
-    shared_libraries: SharedLibraries,
+    shared_libraries: &SharedLibraries,
+    signing_key: &SigningKey,
+    format: PackageFormat,
 ) -> CargoResult<BuildResult> {
-    // Create directory to hold final APKs which are signed using the debug key
+    // Create directory to hold final signed APKs and bundles
     let final_apk_dir = root_build_dir.join("apk");
     fs::create_dir_all(&final_apk_dir)?;
 
     // Paths of created APKs
     let mut target_to_apk_map = BTreeMap::new();
+    let mut target_to_split_apks_map = BTreeMap::new();
+    let mut target_to_bundle_map = BTreeMap::new();
+    let mut target_to_debug_symbols_map = BTreeMap::new();
 
     // Build an APK for each cargo target
     for (target, shared_libraries) in shared_libraries.shared_libraries.iter_all() {
@@ -52,74 +149,23 @@ fn build_apks(
         fs::create_dir_all(&target_directory)?;
 
         // Determine Target Configuration
-        let target_config = config.resolve((target.kind().to_owned(), target.name().to_owned()))?;
-
-        //
-        // Run commands to produce APK
-        //
-        build_manifest(&target_directory, &config, &target_config, &target)?;
-
-        let build_tools_path = config
-            .sdk_path
-            .join("build-tools")
-            .join(&config.build_tools_version);
-        let aapt_path = build_tools_path.join("aapt");
-        let zipalign_path = build_tools_path.join("zipalign");
-
-        // Create unaligned APK which includes resources and assets
-        let unaligned_apk_name = format!("{}_unaligned.apk", target.name());
-        let unaligned_apk_path = target_directory.join(&unaligned_apk_name);
-        if unaligned_apk_path.exists() {
-            std::fs::remove_file(unaligned_apk_path)
-                .map_err(|e| format_err!("Unable to delete APK file. {}", e))?;
+        let mut target_config =
+            config.resolve((target.kind().to_owned(), target.name().to_owned()))?;
+
+        // Generate the launcher icon and the string resources in resources directories which
+        // override the other ones
+        if let Some(icon_images) = &target_config.icon_images {
+            let icon_res_path = target_directory.join(format!("{}_icon_res", target.name()));
+            icon::generate(icon_images, &icon_res_path)?;
+            target_config.res_paths.push(icon_res_path);
         }
-
-        let mut aapt_package_cmd = process(&aapt_path);
-        aapt_package_cmd
-            .arg("package")
-            .arg("-F")
-            .arg(&unaligned_apk_name)
-            .arg("-M")
-            .arg("AndroidManifest.xml")
-            .arg("-I")
-            .arg(&config.android_jar_path);
-
-        if let Some(res_path) = target_config.res_path {
-            aapt_package_cmd.arg("-S").arg(res_path);
+        if !target_config.strings.is_empty() {
+            let strings_res_path = target_directory.join(format!("{}_strings_res", target.name()));
+            strings::generate(&target_config.strings, &strings_res_path)?;
+            target_config.res_paths.push(strings_res_path);
         }
 
-        // Link assets
-        if let Some(assets_path) = &target_config.assets_path {
-            aapt_package_cmd.arg("-A").arg(assets_path);
-        }
-
-        aapt_package_cmd.cwd(&target_directory).exec()?;
-
-        // Add shared libraries to the APK
-        for shared_library in shared_libraries {
-            // Copy the shared library to the appropriate location in the target directory and with the appropriate name
-            // Note: that the type of slash used matters. This path is passed to aapt and the shared library
-            // will not load if backslashes are used.
-            let so_path = format!(
-                "lib/{}/{}",
-                &shared_library.abi.android_abi(),
-                shared_library.filename
-            );
-
-            let target_shared_object_path = target_directory.join(&so_path);
-            fs::create_dir_all(target_shared_object_path.parent().unwrap())?;
-            fs::copy(&shared_library.path, target_shared_object_path)?;
-
-            // Add to the APK
-            process(&aapt_path)
-                .arg("add")
-                .arg(&unaligned_apk_name)
-                .arg(so_path)
-                .cwd(&target_directory)
-                .exec()?;
-        }
-
-        // Determine the directory in which to place the aligned and signed APK
+        // Determine the directory in which to place the aligned and signed APK or bundle
         let target_apk_directory = match target.kind() {
             TargetKind::Bin => final_apk_dir.clone(),
             TargetKind::ExampleBin => final_apk_dir.join("examples"),
@@ -127,227 +173,460 @@ fn build_apks(
         };
         fs::create_dir_all(&target_apk_directory)?;
 
-        // Align apk
-        let final_apk_path = target_apk_directory.join(format!("{}.apk", target.name()));
-        process(&zipalign_path)
-            .arg("-f")
-            .arg("-v")
-            .arg("4")
-            .arg(&unaligned_apk_name)
-            .arg(&final_apk_path)
-            .cwd(&target_directory)
-            .exec()?;
-
-        // Find or generate a debug keystore for signing the APK
-        // We use the same debug keystore as used by the Android SDK. If it does not exist,
-        // then we create it using keytool which is part of the JRE/JDK
-        let android_directory = dirs::home_dir()
-            .ok_or_else(|| format_err!("Unable to determine home directory"))?
-            .join(".android");
-        fs::create_dir_all(&android_directory)?;
-        let keystore_path = android_directory.join("debug.keystore");
-        if !keystore_path.exists() {
-            // Generate key
-            let keytool_filename = if cfg!(target_os = "windows") {
-                "keytool.exe"
-            } else {
-                "keytool"
-            };
-
-            let keytool_path = find_java_executable(keytool_filename)?;
-            process(keytool_path)
-                .arg("-genkey")
-                .arg("-v")
-                .arg("-keystore")
-                .arg(&keystore_path)
-                .arg("-storepass")
-                .arg("android")
-                .arg("-alias")
-                .arg("androidebugkey")
-                .arg("-keypass")
-                .arg("android")
-                .arg("-dname")
-                .arg("CN=Android Debug,O=Android,C=US")
-                .arg("-keyalg")
-                .arg("RSA")
-                .arg("-keysize")
-                .arg("2048")
-                .arg("-validity")
-                .arg("10000")
-                .cwd(root_build_dir)
-                .exec()?;
+        // Archive the unstripped shared libraries for symbolication of crash reports
+        let debug_symbols_path =
+            target_apk_directory.join(format!("{}-native-debug-symbols.zip", target.name()));
+        if debug_symbols::write_zip(
+            shared_libraries,
+            config.reproducible_timestamp,
+            &debug_symbols_path,
+        )? {
+            target_to_debug_symbols_map.insert(
+                (target.kind().to_owned(), target.name().to_owned()),
+                debug_symbols_path,
+            );
+        }
+
+        if format == PackageFormat::Aab {
+            build_manifest(
+                &target_directory,
+                &config,
+                &target_config,
+                target_config.version_code,
+                &target,
+            )?;
+            let bundle_path = target_apk_directory.join(format!("{}.aab", target.name()));
+            bundle::build_bundle(
+                config,
+                &target_config,
+                target,
+                &target_directory,
+                shared_libraries,
+                signing_key,
+                &bundle_path,
+            )?;
+            target_to_bundle_map.insert(
+                (target.kind().to_owned(), target.name().to_owned()),
+                bundle_path,
+            );
+            continue;
+        }
+
+        if !config.split_per_abi {
+            let final_apk_path = target_apk_directory.join(format!("{}.apk", target.name()));
+            build_apk(
+                config,
+                &target_config,
+                target,
+                &target_directory,
+                &shared_libraries.iter().collect::<Vec<_>>(),
+                target_config.version_code,
+                signing_key,
+                &final_apk_path,
+            )?;
+            target_to_apk_map.insert(
+                (target.kind().to_owned(), target.name().to_owned()),
+                final_apk_path,
+            );
+            continue;
         }
 
-        // Sign the APK with the development certificate
-        util::script_process(
-            build_tools_path.join(format!("apksigner{}", util::EXECUTABLE_SUFFIX_BAT)),
-        )
-        .arg("sign")
-        .arg("--ks")
-        .arg(keystore_path)
-        .arg("--ks-pass")
-        .arg("pass:android")
-        .arg(&final_apk_path)
-        .cwd(&target_directory)
-        .exec()?;
-
-        target_to_apk_map.insert(
+        // Build an APK per ABI. Each APK is built in its own directory since the manifests differ
+        // by version code.
+        if target_config.version_code >= ABI_VERSION_CODE_MULTIPLIER {
+            return Err(format_err!(
+                "version_code must be lower than {} when split_per_abi is enabled",
+                ABI_VERSION_CODE_MULTIPLIER
+            ));
+        }
+        let mut split_apks = Vec::new();
+        for build_target in &config.build_targets {
+            let abi = build_target.android_abi();
+            let final_apk_path =
+                target_apk_directory.join(format!("{}-{}.apk", target.name(), abi));
+            build_apk(
+                config,
+                &target_config,
+                target,
+                &target_directory.join(abi),
+                &shared_libraries
+                    .iter()
+                    .filter(|shared_library| shared_library.abi == *build_target)
+                    .collect::<Vec<_>>(),
+                target_config.version_code + build_target.version_code_offset(),
+                signing_key,
+                &final_apk_path,
+            )?;
+            split_apks.push((*build_target, final_apk_path));
+        }
+        target_to_split_apks_map.insert(
             (target.kind().to_owned(), target.name().to_owned()),
-            final_apk_path,
+            split_apks,
         );
+
+        if config.universal_apk {
+            let final_apk_path =
+                target_apk_directory.join(format!("{}-universal.apk", target.name()));
+            build_apk(
+                config,
+                &target_config,
+                target,
+                &target_directory.join("universal"),
+                &shared_libraries.iter().collect::<Vec<_>>(),
+                target_config.version_code,
+                signing_key,
+                &final_apk_path,
+            )?;
+            target_to_apk_map.insert(
+                (target.kind().to_owned(), target.name().to_owned()),
+                final_apk_path,
+            );
+        }
     }
 
-    Ok(BuildResult { target_to_apk_map })
+    Ok(BuildResult {
+        target_to_apk_map,
+        target_to_split_apks_map,
+        target_to_bundle_map,
+        target_to_debug_symbols_map,
+    })
 }
 
-/// Find an executable that is part of the Java SDK
-fn find_java_executable(name: &str) -> CargoResult<PathBuf> {
-    // Look in PATH
-    env::var_os("PATH")
-        .and_then(|paths| {
-            env::split_paths(&paths)
-                .filter_map(|path| {
-                    let filepath = path.join(name);
-                    if fs::metadata(&filepath).is_ok() {
-                        Some(filepath)
-                    } else {
-                        None
-                    }
-                })
-                .next()
-        })
-        .or_else(||
-            // Look in JAVA_HOME
-            env::var_os("JAVA_HOME").and_then(|java_home| {
-                let filepath = PathBuf::from(java_home).join("bin").join(name);
-                if filepath.exists() {
-                    Some(filepath)
-                } else {
-                    None
-                }
-            }))
-        .ok_or_else(|| {
-            format_err!(
-                "Unable to find executable: '{}'. Configure PATH or JAVA_HOME with the path to the JRE or JDK.",
-                name
-            )
-        })
+/// Builds a signed APK containing the given shared libraries. Intermediate files are placed in
+/// the package directory.
+#[allow(clippy::too_many_arguments)]
+fn build_apk(
+    config: &AndroidConfig,
+    target_config: &AndroidTargetConfig,
+    target: &Target,
+    package_directory: &Path,
+    shared_libraries: &[&SharedLibrary],
+    version_code: i32,
+    signing_key: &SigningKey,
+    final_apk_path: &Path,
+) -> CargoResult<()> {
+    fs::create_dir_all(package_directory)?;
+    build_manifest(
+        package_directory,
+        config,
+        target_config,
+        version_code,
+        target,
+    )?;
+
+    let resources_apk_path = package_directory.join(format!("{}_resources.apk", target.name()));
+    let unsigned_apk_path = package_directory.join(format!("{}_unsigned.apk", target.name()));
+
+    // Fingerprint the inputs of each step so that steps whose inputs didn't change since the
+    // previous build are skipped
+    let mut fingerprints =
+        Fingerprints::load(package_directory.join(format!("{}.fingerprints", target.name())));
+
+    let mut hasher = FingerprintHasher::new();
+    hasher
+        .file_content(&package_directory.join("AndroidManifest.xml"))?
+        .bytes(config.build_tools_version.as_bytes())
+        .path(&config.android_jar_path);
+    for res_path in &target_config.res_paths {
+        hasher.path(res_path).tree(res_path)?;
+    }
+    let resources_changed = fingerprints.update("resources", hasher.finish());
+
+    let out_dirs = shared_libraries
+        .iter()
+        .filter_map(|shared_library| shared_library.out_dir.as_deref())
+        .collect::<Vec<_>>();
+    let assets = assets::collect(target_config, &out_dirs)?;
+    let mut hasher = FingerprintHasher::new();
+    for asset in &assets {
+        hasher
+            .bytes(asset.apk_path.as_bytes())
+            .file_metadata(&asset.path)?
+            .bytes(&[(asset.compression == Compression::Stored) as u8]);
+    }
+    let assets_changed = fingerprints.update("assets", hasher.finish());
+
+    // Shared libraries are stored uncompressed and page-aligned when they are not extracted on
+    // installation
+    let native_lib_compression = if config.extract_native_libs {
+        Compression::Deflated
+    } else {
+        Compression::Stored
+    };
+
+    let mut changed_libraries = HashSet::new();
+    for shared_library in shared_libraries {
+        // Libraries are relinked and stripped by every build, so their content is fingerprinted
+        let mut hasher = FingerprintHasher::new();
+        hasher
+            .file_content(&shared_library.path)?
+            .bytes(&[config.extract_native_libs as u8])
+            .bytes(&config.native_lib_page_size.to_le_bytes());
+        let so_path = shared_library_apk_path(shared_library);
+        if fingerprints.update(&so_path, hasher.finish()) {
+            changed_libraries.insert(so_path);
+        }
+    }
+
+    let mut hasher = FingerprintHasher::new();
+    hasher
+        .bytes(&signing_key.certificate.der)
+        .bytes(&config.min_sdk_version.to_le_bytes());
+    fingerprints.update("signing", hasher.finish());
+
+    let mut hasher = FingerprintHasher::new();
+    hasher
+        .bytes(&[config.reproducible_timestamp.is_some() as u8])
+        .bytes(&config.reproducible_timestamp.unwrap_or(0).to_le_bytes());
+    fingerprints.update("timestamp", hasher.finish());
+
+    if !fingerprints.is_changed() && unsigned_apk_path.exists() && final_apk_path.exists() {
+        return Ok(());
+    }
+    fingerprints.invalidate()?;
+
+    // Create APK which includes the compiled manifest and resources
+    if resources_changed || !resources_apk_path.exists() {
+        resources::link(
+            config,
+            target_config,
+            target,
+            package_directory,
+            &resources_apk_path,
+        )?;
+    }
+
+    // Write the aligned APK. Entries produced by aapt are copied as is, and the assets and shared
+    // libraries are added. Assets and shared libraries which didn't change are copied from the
+    // previous APK so that they don't need to be compressed again. Entries are sorted by name so
+    // that their order doesn't depend on aapt or on the build order.
+    let previous_apk = if unsigned_apk_path.exists() {
+        ApkReader::open(&unsigned_apk_path).ok()
+    } else {
+        None
+    };
+    let resources_apk = ApkReader::open(&resources_apk_path)?;
+    let mut apk = ApkWriter::create(&unsigned_apk_path)?;
+    apk.set_page_alignment(u64::from(config.native_lib_page_size));
+    if let Some(timestamp) = config.reproducible_timestamp {
+        apk.set_timestamp(DosDateTime::from_unix_timestamp(timestamp));
+    }
+    apk.copy_entries_sorted(&resources_apk)?;
+
+    for asset in &assets {
+        let previous_apk = previous_apk.as_ref().filter(|_| !assets_changed);
+        match previous_entry(previous_apk, &asset.apk_path) {
+            Some((previous_apk, entry)) => apk.copy_entry(previous_apk, entry)?,
+            None => apk.add_file(&asset.apk_path, &asset.path, asset.compression)?,
+        }
+    }
+
+    let mut shared_libraries = shared_libraries.to_vec();
+    shared_libraries.sort_by_key(|shared_library| shared_library_apk_path(shared_library));
+    for shared_library in shared_libraries {
+        let so_path = shared_library_apk_path(shared_library);
+        let previous_apk = previous_apk
+            .as_ref()
+            .filter(|_| !changed_libraries.contains(&so_path));
+        match previous_entry(previous_apk, &so_path) {
+            Some((previous_apk, entry)) => apk.copy_entry(previous_apk, entry)?,
+            None => apk.add_file(&so_path, &shared_library.path, native_lib_compression)?,
+        }
+    }
+    apk.finish()?;
+
+    // Sign the APK
+    sign::sign_apk(
+        &unsigned_apk_path,
+        final_apk_path,
+        signing_key,
+        config.min_sdk_version,
+    )?;
+
+    fingerprints.save()
+}
+
+/// Returns the entry of the previous APK with the given name
+fn previous_entry<'a>(
+    previous_apk: Option<&'a ApkReader>,
+    name: &str,
+) -> Option<(&'a ApkReader, &'a ApkEntry)> {
+    previous_apk.and_then(|previous_apk| {
+        previous_apk
+            .entries()
+            .iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| (previous_apk, entry))
+    })
+}
+
+/// Returns the path of a shared library within the APK
+fn shared_library_apk_path(shared_library: &SharedLibrary) -> String {
+    // Note: that the type of slash used matters. The shared library will not load if
+    // backslashes are used.
+    format!(
+        "lib/{}/{}",
+        &shared_library.abi.android_abi(),
+        shared_library.filename
+    )
 }
 
 fn build_manifest(
     path: &Path,
     config: &AndroidConfig,
     target_config: &AndroidTargetConfig,
+    version_code: i32,
     target: &Target,
 ) -> CargoResult<()> {
-    let file = path.join("AndroidManifest.xml");
-    let mut file = File::create(&file)?;
-
-    // Building application attributes
-    let application_attrs = format!(
-        r#"
-            android:hasCode="false" android:label="{0}"{1}{2}{3}"#,
-        target_config.package_label,
-        target_config
-            .package_icon
-            .as_ref()
-            .map_or(String::new(), |a| format!(
-                r#"
-            android:icon="{}""#,
-                a
-            )),
-        if target_config.fullscreen {
-            r#"
-            android:theme="@android:style/Theme.DeviceDefault.NoActionBar.Fullscreen""#
-        } else {
-            ""
-        },
-        target_config
-            .application_attributes
-            .as_ref()
-            .map_or(String::new(), |a| a.replace("\n", "\n            "))
-    );
-
-    // Build activity attributes
-    let activity_attrs = format!(
-        r#"
-                android:name="android.app.NativeActivity"
-                android:label="{0}"
-                android:configChanges="orientation|keyboardHidden|screenSize" {1}"#,
-        target_config.package_label,
+    let uses_features = std::iter::once(manifest::UsesFeature {
+        name: None,
+        gl_es_version: Some(
+            u32::from(target_config.opengles_version_major) << 16
+                | u32::from(target_config.opengles_version_minor),
+        ),
+        required: true,
+        version: None,
+    })
+    .chain(
         target_config
-            .activity_attributes
-            .as_ref()
-            .map_or(String::new(), |a| a.replace("\n", "\n                "))
-    );
-
-    let uses_features = target_config
-        .features
-        .iter()
-        .map(|f| {
-            format!(
-                "\n\t<uses-feature android:name=\"{}\" android:required=\"{}\" {}/>",
-                f.name,
-                f.required,
-                f.version
-                    .as_ref()
-                    .map_or(String::new(), |v| format!(r#"android:version="{}""#, v))
-            )
-        })
-        .collect::<Vec<String>>()
-        .join(", ");
+            .features
+            .iter()
+            .map(|feature| manifest::UsesFeature {
+                name: Some(feature.name.clone()),
+                gl_es_version: None,
+                required: feature.required,
+                version: feature.version.clone(),
+            }),
+    )
+    .collect();
 
     let uses_permissions = target_config
         .permissions
         .iter()
-        .map(|f| {
-            format!(
-                "\n\t<uses-permission android:name=\"{}\" {max_sdk_version}/>",
-                f.name,
-                max_sdk_version = f.max_sdk_version.map_or(String::new(), |v| format!(
-                    r#"android:maxSdkVersion="{}""#,
-                    v
-                ))
-            )
+        .map(|permission| manifest::UsesPermission {
+            name: permission.name.clone(),
+            max_sdk_version: permission.max_sdk_version,
         })
-        .collect::<Vec<String>>()
-        .join(", ");
-
-    // Write final AndroidManifest
-    writeln!(
-        file,
-        r#"<?xml version="1.0" encoding="utf-8"?>
-<manifest xmlns:android="http://schemas.android.com/apk/res/android"
-        package="{package}"
-        android:versionCode="{version_code}"
-        android:versionName="{version_name}">
-    <uses-sdk android:targetSdkVersion="{targetSdkVersion}" android:minSdkVersion="{minSdkVersion}" />
-    <uses-feature android:glEsVersion="{glEsVersion}" android:required="true"></uses-feature>{uses_features}{uses_permissions}
-    <application {application_attrs} >
-        <activity {activity_attrs} >
-            <meta-data android:name="android.app.lib_name" android:value="{target_name}" />
-            <intent-filter>
-                <action android:name="android.intent.action.MAIN" />
-                <category android:name="android.intent.category.LAUNCHER" />
-            </intent-filter>
-        </activity>
-    </application>
-</manifest>"#,
-        package = target_config.package_name.replace("-", "_"),
-        version_code = target_config.version_code,
-        version_name = target_config.version_name,
-        targetSdkVersion = config.target_sdk_version,
-        minSdkVersion = config.min_sdk_version,
-        glEsVersion = format!(
-            "0x{:04}{:04}",
-            target_config.opengles_version_major, target_config.opengles_version_minor
-        ),
-        uses_features = uses_features,
-        uses_permissions = uses_permissions,
-        application_attrs = application_attrs,
-        activity_attrs = activity_attrs,
-        target_name = target.name(),
-    )?;
+        .collect();
+
+    let activity = manifest::Activity {
+        name: "android.app.NativeActivity".to_owned(),
+        label: target_config.package_label.clone(),
+        config_changes: Some("orientation|keyboardHidden|screenSize".to_owned()),
+        extra_attributes: manifest::parse_attributes(&target_config.activity_attributes)?,
+        meta_data: std::iter::once(manifest::MetaData {
+            name: "android.app.lib_name".to_owned(),
+            value: manifest::MetaDataValue::Value(target.name().to_owned()),
+        })
+        .chain(target_config.activity_meta_data.iter().map(meta_data))
+        .collect(),
+        intent_filters: std::iter::once(manifest::IntentFilter {
+            actions: vec!["android.intent.action.MAIN".to_owned()],
+            categories: vec!["android.intent.category.LAUNCHER".to_owned()],
+            data: Vec::new(),
+            auto_verify: false,
+        })
+        .chain(target_config.intent_filters.iter().map(|filter| {
+            manifest::IntentFilter {
+                actions: filter.actions.clone(),
+                categories: filter.categories.clone(),
+                data: filter
+                    .data
+                    .iter()
+                    .map(|data| manifest::IntentFilterData {
+                        scheme: data.scheme.clone(),
+                        host: data.host.clone(),
+                        port: data.port.map(|port| port.to_string()),
+                        path: data.path.clone(),
+                        path_prefix: data.path_prefix.clone(),
+                        path_pattern: data.path_pattern.clone(),
+                        mime_type: data.mime_type.clone(),
+                    })
+                    .collect(),
+                auto_verify: filter.auto_verify,
+            }
+        }))
+        .collect(),
+    };
+
+    let application = manifest::Application {
+        label: target_config.package_label.clone(),
+        icon: target_config.package_icon.clone(),
+        theme: if target_config.fullscreen {
+            Some("@android:style/Theme.DeviceDefault.NoActionBar.Fullscreen".to_owned())
+        } else {
+            None
+        },
+        has_code: false,
+        // Unless `android:debuggable` is set in `application_attributes`
+        debuggable: !config.release,
+        extract_native_libs: if config.extract_native_libs {
+            None
+        } else {
+            Some(false)
+        },
+        extra_attributes: manifest::parse_attributes(&target_config.application_attributes)?,
+        meta_data: target_config
+            .application_meta_data
+            .iter()
+            .map(meta_data)
+            .collect(),
+        activities: vec![activity],
+    };
+
+    let manifest = manifest::Manifest {
+        package: target_config.package_name.replace("-", "_"),
+        version_code,
+        version_name: target_config.version_name.clone(),
+        uses_sdk: manifest::UsesSdk {
+            min_sdk_version: config.min_sdk_version,
+            target_sdk_version: config.target_sdk_version,
+        },
+        uses_features,
+        uses_permissions,
+        application,
+    };
+
+    let mut manifest = manifest.to_element();
+    if let Some(template_path) = &target_config.manifest_template {
+        let template = fs::read_to_string(template_path).map_err(|e| {
+            format_err!(
+                "Unable to read manifest template `{}`. {}",
+                template_path.display(),
+                e
+            )
+        })?;
+        let mut template = axml::parse(&template).map_err(|e| {
+            format_err!(
+                "Unable to parse manifest template `{}`. {}",
+                template_path.display(),
+                e
+            )
+        })?;
+        manifest::merge(&mut template, manifest).map_err(|e| {
+            format_err!(
+                "Unable to merge manifest template `{}`. {}",
+                template_path.display(),
+                e
+            )
+        })?;
+        manifest = template;
+    }
+
+    util::write_if_changed(
+        &path.join("AndroidManifest.xml"),
+        manifest::to_xml(&manifest).as_bytes(),
+    )
+}
 
-    Ok(())
+fn meta_data(meta_data: &AndroidMetaData) -> manifest::MetaData {
+    manifest::MetaData {
+        name: meta_data.name.clone(),
+        value: match &meta_data.value {
+            AndroidMetaDataValue::Value(value) => manifest::MetaDataValue::Value(value.clone()),
+            AndroidMetaDataValue::Resource(resource) => {
+                manifest::MetaDataValue::Resource(resource.clone())
+            }
+        },
+    }
 }
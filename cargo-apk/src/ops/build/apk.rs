@@ -0,0 +1,663 @@
+use super::util;
+use cargo::util::CargoResult;
+use failure::format_err;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Alignment used for ordinary uncompressed entries. Matches `zipalign 4`.
+pub const DEFAULT_ALIGNMENT: u64 = 4;
+
+/// Default alignment used for uncompressed shared libraries so they can be mapped directly from
+/// the APK.
+pub const PAGE_ALIGNMENT: u64 = 4096;
+
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+const LOCAL_FILE_HEADER_LEN: u64 = 30;
+const END_OF_CENTRAL_DIRECTORY_LEN: usize = 22;
+
+/// Extra field used by the Android build tools to record the alignment of an entry
+const ALIGNMENT_EXTRA_FIELD_ID: u16 = 0xd935;
+const ALIGNMENT_EXTRA_FIELD_MIN_LEN: u64 = 6;
+
+/// Compression method of an APK entry
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Compression {
+    Stored,
+    Deflated,
+}
+
+impl Compression {
+    fn from_method(method: u16) -> CargoResult<Compression> {
+        match method {
+            0 => Ok(Compression::Stored),
+            8 => Ok(Compression::Deflated),
+            _ => Err(format_err!(
+                "Unsupported zip compression method: {}",
+                method
+            )),
+        }
+    }
+
+    fn method(self) -> u16 {
+        match self {
+            Compression::Stored => 0,
+            Compression::Deflated => 8,
+        }
+    }
+}
+
+/// Timestamp of an APK entry in MS-DOS format
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DosDateTime {
+    pub time: u16,
+    pub date: u16,
+}
+
+impl DosDateTime {
+    /// Converts a system time to a DOS timestamp. Times before 1980 are clamped to 1980-01-01.
+    pub fn from_system_time(time: SystemTime) -> DosDateTime {
+        let secs = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        DosDateTime::from_unix_timestamp(secs)
+    }
+
+    /// Converts seconds since the unix epoch to a DOS timestamp.
+    pub fn from_unix_timestamp(secs: u64) -> DosDateTime {
+        let (year, month, day, hour, minute, second) = util::civil_from_unix_timestamp(secs);
+        if year < 1980 {
+            return DosDateTime {
+                time: 0,
+                date: (1 << 5) | 1,
+            };
+        }
+
+        DosDateTime {
+            time: ((hour << 11) | (minute << 5) | (second / 2)) as u16,
+            date: ((((year - 1980).min(127)) << 9) | (month << 5) | day) as u16,
+        }
+    }
+}
+
+/// Entry of an existing APK
+#[derive(Debug, Clone)]
+pub struct ApkEntry {
+    pub name: String,
+    pub compression: Compression,
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub modified: DosDateTime,
+    /// Offset of the entry data
+    pub data_offset: u64,
+    /// Alignment recorded in the alignment extra field of the local header, if any
+    pub alignment: Option<u64>,
+}
+
+/// Reads the entries of an existing APK.
+pub struct ApkReader {
+    data: Vec<u8>,
+    entries: Vec<ApkEntry>,
+}
+
+impl ApkReader {
+    pub fn open(path: &Path) -> CargoResult<ApkReader> {
+        let data = fs::read(path)
+            .map_err(|e| format_err!("Unable to read APK `{}`. {}", path.display(), e))?;
+        ApkReader::from_bytes(data)
+            .map_err(|e| format_err!("Unable to read APK `{}`. {}", path.display(), e))
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> CargoResult<ApkReader> {
+        let eocd_offset = find_end_of_central_directory(&data)?;
+        let entry_count = read_u16(&data, eocd_offset + 10)? as usize;
+        let cd_offset = read_u32(&data, eocd_offset + 16)? as usize;
+
+        let mut entries = Vec::with_capacity(entry_count);
+        let mut offset = cd_offset;
+        for _ in 0..entry_count {
+            if read_u32(&data, offset)? != CENTRAL_DIRECTORY_SIGNATURE {
+                return Err(format_err!("Invalid central directory entry"));
+            }
+            let method = read_u16(&data, offset + 10)?;
+            let time = read_u16(&data, offset + 12)?;
+            let date = read_u16(&data, offset + 14)?;
+            let crc32 = read_u32(&data, offset + 16)?;
+            let compressed_size = u64::from(read_u32(&data, offset + 20)?);
+            let uncompressed_size = u64::from(read_u32(&data, offset + 24)?);
+            let name_len = read_u16(&data, offset + 28)? as usize;
+            let extra_len = read_u16(&data, offset + 30)? as usize;
+            let comment_len = read_u16(&data, offset + 32)? as usize;
+            let header_offset = u64::from(read_u32(&data, offset + 42)?);
+            let name = data
+                .get(offset + 46..offset + 46 + name_len)
+                .ok_or_else(|| format_err!("Truncated central directory"))?;
+            let name = String::from_utf8_lossy(name).into_owned();
+
+            // The local header may have a different extra field than the central directory
+            let header = header_offset as usize;
+            if read_u32(&data, header)? != LOCAL_FILE_HEADER_SIGNATURE {
+                return Err(format_err!("Invalid local file header for `{}`", name));
+            }
+            let local_name_len = u64::from(read_u16(&data, header + 26)?);
+            let local_extra_len = u64::from(read_u16(&data, header + 28)?);
+            let extra_offset = header_offset + LOCAL_FILE_HEADER_LEN + local_name_len;
+            let data_offset = extra_offset + local_extra_len;
+            if data_offset + compressed_size > data.len() as u64 {
+                return Err(format_err!("Truncated data for `{}`", name));
+            }
+
+            entries.push(ApkEntry {
+                name,
+                compression: Compression::from_method(method)?,
+                crc32,
+                compressed_size,
+                uncompressed_size,
+                modified: DosDateTime { time, date },
+                data_offset,
+                alignment: read_alignment(&data, extra_offset as usize, local_extra_len as usize)?,
+            });
+
+            offset += 46 + name_len + extra_len + comment_len;
+        }
+
+        Ok(ApkReader { data, entries })
+    }
+
+    pub fn entries(&self) -> &[ApkEntry] {
+        &self.entries
+    }
+
+    /// Returns the whole APK
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the data of the entry as it is stored in the APK
+    pub fn raw_data(&self, entry: &ApkEntry) -> &[u8] {
+        &self.data[entry.data_offset as usize..(entry.data_offset + entry.compressed_size) as usize]
+    }
+
+    /// Returns the uncompressed data of the entry
+    pub fn read(&self, entry: &ApkEntry) -> CargoResult<Vec<u8>> {
+        let raw = self.raw_data(entry);
+        match entry.compression {
+            Compression::Stored => Ok(raw.to_vec()),
+            Compression::Deflated => {
+                // The size is read from the APK, so don't trust it beyond a plausible ratio
+                let capacity = (entry.uncompressed_size as usize).min(raw.len().saturating_mul(4));
+                let mut data = Vec::with_capacity(capacity);
+                DeflateDecoder::new(raw)
+                    .read_to_end(&mut data)
+                    .map_err(|e| format_err!("Unable to decompress `{}`. {}", entry.name, e))?;
+                Ok(data)
+            }
+        }
+    }
+}
+
+struct CentralDirectoryEntry {
+    name: String,
+    compression: Compression,
+    crc32: u32,
+    compressed_size: u32,
+    uncompressed_size: u32,
+    modified: DosDateTime,
+    header_offset: u32,
+}
+
+/// Writes an APK in a single pass. Uncompressed entries are aligned as they are written so the
+/// result doesn't need to be processed by `zipalign`.
+pub struct ApkWriter {
+    file: BufWriter<File>,
+    offset: u64,
+    entries: Vec<CentralDirectoryEntry>,
+    page_alignment: u64,
+    timestamp: Option<DosDateTime>,
+}
+
+impl ApkWriter {
+    pub fn create(path: &Path) -> CargoResult<ApkWriter> {
+        let file = File::create(path)
+            .map_err(|e| format_err!("Unable to create APK `{}`. {}", path.display(), e))?;
+        Ok(ApkWriter {
+            file: BufWriter::new(file),
+            offset: 0,
+            entries: Vec::new(),
+            page_alignment: PAGE_ALIGNMENT,
+            timestamp: None,
+        })
+    }
+
+    /// Sets the alignment of uncompressed shared libraries. Must be a multiple of 4096.
+    pub fn set_page_alignment(&mut self, page_alignment: u64) {
+        self.page_alignment = page_alignment;
+    }
+
+    /// Sets the modification time of all the entries, including copied entries, instead of the
+    /// time of each entry
+    pub fn set_timestamp(&mut self, timestamp: DosDateTime) {
+        self.timestamp = Some(timestamp);
+    }
+
+    /// Adds an entry to the APK using the specified compression method.
+    pub fn add_entry(
+        &mut self,
+        name: &str,
+        data: &[u8],
+        compression: Compression,
+        modified: DosDateTime,
+    ) -> CargoResult<()> {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(data);
+        let crc32 = hasher.finalize();
+        match compression {
+            Compression::Stored => self.write_entry(
+                name,
+                data,
+                compression,
+                crc32,
+                data.len() as u64,
+                modified,
+                self.entry_alignment(name),
+            ),
+            Compression::Deflated => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+                encoder.write_all(data)?;
+                let compressed = encoder.finish()?;
+                self.write_entry(
+                    name,
+                    &compressed,
+                    compression,
+                    crc32,
+                    data.len() as u64,
+                    modified,
+                    self.entry_alignment(name),
+                )
+            }
+        }
+    }
+
+    /// Adds the contents of a file to the APK
+    pub fn add_file(
+        &mut self,
+        name: &str,
+        path: &Path,
+        compression: Compression,
+    ) -> CargoResult<()> {
+        let data = fs::read(path)
+            .map_err(|e| format_err!("Unable to read `{}`. {}", path.display(), e))?;
+        let modified = DosDateTime::from_system_time(fs::metadata(path)?.modified()?);
+        self.add_entry(name, &data, compression, modified)
+    }
+
+    /// Copies an entry from another APK without recompressing it
+    pub fn copy_entry(&mut self, reader: &ApkReader, entry: &ApkEntry) -> CargoResult<()> {
+        self.copy_entry_as(reader, entry, &entry.name)
+    }
+
+    /// Copies an entry from another APK to a different path without recompressing it. The
+    /// alignment recorded in the entry is preserved.
+    pub fn copy_entry_as(
+        &mut self,
+        reader: &ApkReader,
+        entry: &ApkEntry,
+        name: &str,
+    ) -> CargoResult<()> {
+        self.write_entry(
+            name,
+            reader.raw_data(entry),
+            entry.compression,
+            entry.crc32,
+            entry.uncompressed_size,
+            entry.modified,
+            entry
+                .alignment
+                .unwrap_or_else(|| self.entry_alignment(name)),
+        )
+    }
+
+    /// Copies all entries from another APK sorted by name, so that their order doesn't depend on
+    /// the tool which produced it
+    pub fn copy_entries_sorted(&mut self, reader: &ApkReader) -> CargoResult<()> {
+        let mut entries = reader.entries().iter().collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        for entry in entries {
+            self.copy_entry(reader, entry)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the alignment required for an uncompressed entry
+    fn entry_alignment(&self, name: &str) -> u64 {
+        if name.starts_with("lib/") && name.ends_with(".so") {
+            self.page_alignment
+        } else {
+            DEFAULT_ALIGNMENT
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write_entry(
+        &mut self,
+        name: &str,
+        data: &[u8],
+        compression: Compression,
+        crc32: u32,
+        uncompressed_size: u64,
+        modified: DosDateTime,
+        alignment: u64,
+    ) -> CargoResult<()> {
+        if self.entries.iter().any(|e| e.name == name) {
+            return Err(format_err!("Duplicate APK entry `{}`", name));
+        }
+        let modified = self.timestamp.unwrap_or(modified);
+
+        // Pad the extra field of uncompressed entries so that their data is aligned
+        let header_offset = self.offset;
+        let data_start = header_offset + LOCAL_FILE_HEADER_LEN + name.len() as u64;
+        let extra = if compression == Compression::Stored {
+            let padding =
+                (alignment - (data_start + ALIGNMENT_EXTRA_FIELD_MIN_LEN) % alignment) % alignment;
+            let mut extra = Vec::new();
+            extra.extend_from_slice(&ALIGNMENT_EXTRA_FIELD_ID.to_le_bytes());
+            extra.extend_from_slice(&to_u16(2 + padding, name, "alignment padding")?.to_le_bytes());
+            extra.extend_from_slice(&to_u16(alignment, name, "alignment")?.to_le_bytes());
+            extra.resize(extra.len() + padding as usize, 0);
+            extra
+        } else {
+            Vec::new()
+        };
+
+        // Zip64 isn't written, so every size and offset has to fit the 32 bit fields
+        let compressed_size = to_u32(data.len() as u64, name, "compressed size")?;
+        let uncompressed_size = to_u32(uncompressed_size, name, "uncompressed size")?;
+        let header_offset = to_u32(header_offset, name, "offset")?;
+        let name_len = to_u16(name.len() as u64, name, "name length")?;
+        let extra_len = to_u16(extra.len() as u64, name, "extra field length")?;
+
+        let mut header = Vec::with_capacity(LOCAL_FILE_HEADER_LEN as usize + name.len());
+        header.extend_from_slice(&LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes());
+        header.extend_from_slice(&20u16.to_le_bytes()); // Version needed to extract
+        header.extend_from_slice(&0u16.to_le_bytes()); // Flags
+        header.extend_from_slice(&compression.method().to_le_bytes());
+        header.extend_from_slice(&modified.time.to_le_bytes());
+        header.extend_from_slice(&modified.date.to_le_bytes());
+        header.extend_from_slice(&crc32.to_le_bytes());
+        header.extend_from_slice(&compressed_size.to_le_bytes());
+        header.extend_from_slice(&uncompressed_size.to_le_bytes());
+        header.extend_from_slice(&name_len.to_le_bytes());
+        header.extend_from_slice(&extra_len.to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        header.extend_from_slice(&extra);
+
+        self.file.write_all(&header)?;
+        self.file.write_all(data)?;
+        self.offset += header.len() as u64 + data.len() as u64;
+
+        self.entries.push(CentralDirectoryEntry {
+            name: name.to_owned(),
+            compression,
+            crc32,
+            compressed_size,
+            uncompressed_size,
+            modified,
+            header_offset,
+        });
+
+        Ok(())
+    }
+
+    /// Writes the central directory and flushes the APK to disk
+    pub fn finish(mut self) -> CargoResult<()> {
+        let cd_offset = self.offset;
+        let mut cd = Vec::new();
+        for entry in &self.entries {
+            cd.extend_from_slice(&CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+            cd.extend_from_slice(&20u16.to_le_bytes()); // Version made by
+            cd.extend_from_slice(&20u16.to_le_bytes()); // Version needed to extract
+            cd.extend_from_slice(&0u16.to_le_bytes()); // Flags
+            cd.extend_from_slice(&entry.compression.method().to_le_bytes());
+            cd.extend_from_slice(&entry.modified.time.to_le_bytes());
+            cd.extend_from_slice(&entry.modified.date.to_le_bytes());
+            cd.extend_from_slice(&entry.crc32.to_le_bytes());
+            cd.extend_from_slice(&entry.compressed_size.to_le_bytes());
+            cd.extend_from_slice(&entry.uncompressed_size.to_le_bytes());
+            cd.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            cd.extend_from_slice(&0u16.to_le_bytes()); // Extra field length
+            cd.extend_from_slice(&0u16.to_le_bytes()); // Comment length
+            cd.extend_from_slice(&0u16.to_le_bytes()); // Disk number
+            cd.extend_from_slice(&0u16.to_le_bytes()); // Internal attributes
+            cd.extend_from_slice(&0u32.to_le_bytes()); // External attributes, without permissions
+            cd.extend_from_slice(&entry.header_offset.to_le_bytes());
+            cd.extend_from_slice(entry.name.as_bytes());
+        }
+
+        let entry_count = u16::try_from(self.entries.len())
+            .map_err(|_| format_err!("APK contains more than {} entries", u16::MAX))?;
+        let cd_len = to_u32(cd.len() as u64, "central directory", "size")?;
+        let cd_offset = to_u32(cd_offset, "central directory", "offset")?;
+
+        let mut eocd = Vec::with_capacity(END_OF_CENTRAL_DIRECTORY_LEN);
+        eocd.extend_from_slice(&END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        eocd.extend_from_slice(&0u16.to_le_bytes()); // Disk number
+        eocd.extend_from_slice(&0u16.to_le_bytes()); // Disk with central directory
+        eocd.extend_from_slice(&entry_count.to_le_bytes());
+        eocd.extend_from_slice(&entry_count.to_le_bytes());
+        eocd.extend_from_slice(&cd_len.to_le_bytes());
+        eocd.extend_from_slice(&cd_offset.to_le_bytes());
+        eocd.extend_from_slice(&0u16.to_le_bytes()); // Comment length
+
+        self.file.write_all(&cd)?;
+        self.file.write_all(&eocd)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Returns the alignment recorded in the alignment extra field of a local header
+fn read_alignment(data: &[u8], extra_offset: usize, extra_len: usize) -> CargoResult<Option<u64>> {
+    let mut offset = extra_offset;
+    while offset + 4 <= extra_offset + extra_len {
+        let id = read_u16(data, offset)?;
+        let len = read_u16(data, offset + 2)? as usize;
+        if id == ALIGNMENT_EXTRA_FIELD_ID && len >= 2 {
+            let alignment = u64::from(read_u16(data, offset + 4)?);
+            return Ok(Some(alignment).filter(|&alignment| alignment > 0));
+        }
+        offset += 4 + len;
+    }
+    Ok(None)
+}
+
+/// Returns the offsets of the central directory and of the end of central directory record
+pub fn central_directory_offsets(data: &[u8]) -> CargoResult<(usize, usize)> {
+    let eocd_offset = find_end_of_central_directory(data)?;
+    let cd_offset = read_u32(data, eocd_offset + 16)? as usize;
+    if cd_offset > eocd_offset {
+        return Err(format_err!("Invalid central directory offset"));
+    }
+    Ok((cd_offset, eocd_offset))
+}
+
+fn find_end_of_central_directory(data: &[u8]) -> CargoResult<usize> {
+    if data.len() < END_OF_CENTRAL_DIRECTORY_LEN {
+        return Err(format_err!("File is too small to be a zip archive"));
+    }
+
+    // The end of central directory record is followed by a comment of up to 65535 bytes
+    let last = data.len() - END_OF_CENTRAL_DIRECTORY_LEN;
+    let first = last.saturating_sub(0xffff);
+    (first..=last)
+        .rev()
+        .find(|&offset| read_u32(data, offset).ok() == Some(END_OF_CENTRAL_DIRECTORY_SIGNATURE))
+        .ok_or_else(|| format_err!("Unable to find the end of central directory record"))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> CargoResult<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| format_err!("Unexpected end of zip archive"))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> CargoResult<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| format_err!("Unexpected end of zip archive"))
+}
+
+fn to_u16(value: u64, name: &str, field: &str) -> CargoResult<u16> {
+    u16::try_from(value)
+        .map_err(|_| format_err!("The {} of `{}` doesn't fit in a zip archive", field, name))
+}
+
+fn to_u32(value: u64, name: &str, field: &str) -> CargoResult<u32> {
+    u32::try_from(value).map_err(|_| {
+        format_err!(
+            "The {} of `{}` exceeds 4 GiB, which needs zip64 and isn't supported",
+            field,
+            name
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::build::tempfile::TempDir;
+
+    fn write_apk(name: &str, page_alignment: u64) -> ApkReader {
+        let dir = TempDir::new(name).unwrap();
+        let path = dir.path.join("test.apk");
+        let modified = DosDateTime::from_unix_timestamp(1_574_519_518);
+        let mut apk = ApkWriter::create(&path).unwrap();
+        apk.set_page_alignment(page_alignment);
+        apk.add_entry(
+            "AndroidManifest.xml",
+            b"manifest",
+            Compression::Deflated,
+            modified,
+        )
+        .unwrap();
+        apk.add_entry("a", b"odd", Compression::Stored, modified)
+            .unwrap();
+        apk.add_entry(
+            "lib/arm64-v8a/libtest.so",
+            &[1; 100],
+            Compression::Stored,
+            modified,
+        )
+        .unwrap();
+        apk.add_entry("classes.dex", &[2; 1000], Compression::Deflated, modified)
+            .unwrap();
+        apk.finish().unwrap();
+        ApkReader::from_bytes(fs::read(&path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn dos_date_time() {
+        // 2019-11-23 14:31:58 UTC
+        let modified = DosDateTime::from_unix_timestamp(1_574_519_518);
+        assert_eq!(modified.time, (14 << 11) | (31 << 5) | (58 / 2));
+        assert_eq!(modified.date, ((2019 - 1980) << 9) | (11 << 5) | 23);
+        assert_eq!(
+            DosDateTime::from_unix_timestamp(0),
+            DosDateTime {
+                time: 0,
+                date: (1 << 5) | 1
+            }
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let apk = write_apk("apk-round-trip", PAGE_ALIGNMENT);
+        let names: Vec<_> = apk.entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "AndroidManifest.xml",
+                "a",
+                "lib/arm64-v8a/libtest.so",
+                "classes.dex"
+            ]
+        );
+
+        let expected: [&[u8]; 4] = [b"manifest", b"odd", &[1; 100], &[2; 1000]];
+        for (entry, expected) in apk.entries().iter().zip(expected.iter()) {
+            assert_eq!(&apk.read(entry).unwrap()[..], *expected);
+            let mut hasher = crc32fast::Hasher::new();
+            hasher.update(expected);
+            assert_eq!(entry.crc32, hasher.finalize());
+            assert_eq!(entry.uncompressed_size, expected.len() as u64);
+            assert_eq!(
+                entry.modified,
+                DosDateTime::from_unix_timestamp(1_574_519_518)
+            );
+        }
+        let classes = &apk.entries()[3];
+        assert_eq!(classes.compression, Compression::Deflated);
+        assert!(classes.compressed_size < classes.uncompressed_size);
+    }
+
+    #[test]
+    fn reproducible() {
+        let dir = TempDir::new("apk-reproducible").unwrap();
+        let asset = dir.path.join("asset.txt");
+        fs::write(&asset, b"asset").unwrap();
+
+        // Writes the entries in the given order with the given modification time, then copies
+        // them sorted with a fixed timestamp like a reproducible build
+        let write = |names: &[&str], modified: u64| {
+            let path = dir.path.join("source.apk");
+            let mut apk = ApkWriter::create(&path).unwrap();
+            for name in names {
+                let modified = DosDateTime::from_unix_timestamp(modified);
+                apk.add_entry(name, name.as_bytes(), Compression::Deflated, modified)
+                    .unwrap();
+            }
+            apk.finish().unwrap();
+            let source = ApkReader::open(&path).unwrap();
+
+            let path = dir.path.join("reproducible.apk");
+            let mut apk = ApkWriter::create(&path).unwrap();
+            apk.set_timestamp(DosDateTime::from_unix_timestamp(0));
+            apk.copy_entries_sorted(&source).unwrap();
+            apk.add_file("assets/asset.txt", &asset, Compression::Stored)
+                .unwrap();
+            apk.finish().unwrap();
+            fs::read(&path).unwrap()
+        };
+
+        let first = write(
+            &["res/b.xml", "AndroidManifest.xml", "res/a.xml"],
+            1_500_000_000,
+        );
+        let second = write(
+            &["res/a.xml", "res/b.xml", "AndroidManifest.xml"],
+            1_600_000_000,
+        );
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn alignment() {
+        for &page_alignment in &[PAGE_ALIGNMENT, 16384] {
+            let apk = write_apk(&format!("apk-alignment-{}", page_alignment), page_alignment);
+            let entries = apk.entries();
+            assert_eq!(entries[0].alignment, None);
+            assert_eq!(entries[1].alignment, Some(DEFAULT_ALIGNMENT));
+            assert_eq!(entries[1].data_offset % DEFAULT_ALIGNMENT, 0);
+            assert_eq!(entries[2].alignment, Some(page_alignment));
+            assert_eq!(entries[2].data_offset % page_alignment, 0);
+        }
+    }
+}
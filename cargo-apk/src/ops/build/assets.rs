@@ -0,0 +1,196 @@
+//! Collection of the files shipped in the `assets` directory of the packages
+
+use super::apk::Compression;
+use crate::config::{self, AndroidTargetConfig};
+use cargo::util::CargoResult;
+use failure::format_err;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File shipped in the `assets` directory
+#[derive(Debug)]
+pub struct Asset {
+    /// Path of the asset in the package, starting with `assets/`
+    pub apk_path: String,
+    pub path: PathBuf,
+    pub compression: Compression,
+}
+
+/// Returns the assets of the asset roots of a target and the assets listed by its build script in
+/// the given `OUT_DIR`s, sorted by path in the package. Hidden files and the files matching the
+/// exclude patterns are skipped.
+pub fn collect(target_config: &AndroidTargetConfig, out_dirs: &[&Path]) -> CargoResult<Vec<Asset>> {
+    let excludes = Excludes::new(&target_config.assets_exclude)?;
+
+    let out_dir_roots = out_dirs
+        .iter()
+        .map(|out_dir| Ok((*out_dir, config::load_out_dir_asset_roots(out_dir)?)))
+        .collect::<CargoResult<Vec<_>>>()?;
+    let roots = target_config
+        .asset_roots
+        .iter()
+        .map(|root| (root, None))
+        .chain(
+            out_dir_roots
+                .iter()
+                .flat_map(|(out_dir, roots)| roots.iter().map(move |root| (root, Some(*out_dir)))),
+        );
+
+    let mut assets = Vec::new();
+    // Source and OUT_DIR of each path in the package, to detect assets provided by several roots
+    let mut sources = HashMap::<_, (PathBuf, Option<&Path>)>::new();
+    for (root, out_dir) in roots {
+        let mut files = Vec::new();
+        if root.path.is_dir() {
+            list_files(&root.path, &root.prefix, &mut files)?;
+        } else if root.path.is_file() {
+            let file_name = root.path.file_name().unwrap().to_string_lossy();
+            files.push((join(&root.prefix, &file_name), root.path.clone()));
+        } else {
+            return Err(format_err!(
+                "Assets path `{}` does not exist",
+                root.path.display()
+            ));
+        }
+        for (asset_path, path) in files {
+            if excludes.is_match(&asset_path) {
+                continue;
+            }
+            let apk_path = format!("assets/{}", asset_path);
+            if let Some((previous_path, previous_out_dir)) = sources.get(&apk_path) {
+                // Build scripts run for each ABI, so an APK containing several ABIs gets the same
+                // generated assets from several OUT_DIRs
+                let generated_for_other_abi =
+                    out_dir.is_some() && previous_out_dir.is_some() && out_dir != *previous_out_dir;
+                if generated_for_other_abi {
+                    if fs::read(previous_path)? == fs::read(&path)? {
+                        continue;
+                    }
+                    return Err(format_err!(
+                        "The build script generated `{}` with different contents for each ABI. \
+                         `{}` and `{}` can't both be packaged in an APK containing several ABIs.",
+                        apk_path,
+                        previous_path.display(),
+                        path.display()
+                    ));
+                }
+                return Err(format_err!(
+                    "`{}` and `{}` are both packaged as `{}`",
+                    previous_path.display(),
+                    path.display(),
+                    apk_path
+                ));
+            }
+            sources.insert(apk_path.clone(), (path.clone(), out_dir));
+            assets.push(Asset {
+                apk_path,
+                compression: compression(target_config, &asset_path),
+                path,
+            });
+        }
+    }
+    assets.sort_by(|a, b| a.apk_path.cmp(&b.apk_path));
+    Ok(assets)
+}
+
+/// Adds the non-hidden files of a directory tree, with their path under the given prefix
+fn list_files(
+    directory: &Path,
+    prefix: &str,
+    files: &mut Vec<(String, PathBuf)>,
+) -> CargoResult<()> {
+    let entries = fs::read_dir(directory)
+        .map_err(|e| format_err!("Unable to read directory `{}`. {}", directory.display(), e))?;
+    for entry in entries {
+        let path = entry?.path();
+        let file_name = path.file_name().unwrap().to_string_lossy().into_owned();
+        if file_name.starts_with('.') {
+            continue;
+        }
+        let asset_path = join(prefix, &file_name);
+        if path.is_dir() {
+            list_files(&path, &asset_path, files)?;
+        } else {
+            files.push((asset_path, path));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the path of a file in the `assets` directory under the given prefix
+fn join(prefix: &str, file_name: &str) -> String {
+    if prefix.is_empty() {
+        file_name.to_owned()
+    } else {
+        format!("{}/{}", prefix, file_name)
+    }
+}
+
+/// Assets whose extension is in the `no_compress` list are stored so they can be memory mapped
+fn compression(target_config: &AndroidTargetConfig, asset_path: &str) -> Compression {
+    let extension = Path::new(asset_path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension {
+        Some(extension) if target_config.no_compress.contains(&extension) => Compression::Stored,
+        _ => Compression::Deflated,
+    }
+}
+
+/// Exclude patterns. Patterns without a `/` match file names in any directory, others match
+/// paths in the `assets` directory.
+struct Excludes {
+    file_names: GlobSet,
+    paths: GlobSet,
+}
+
+impl Excludes {
+    fn new(patterns: &[String]) -> CargoResult<Excludes> {
+        let mut file_names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = GlobBuilder::new(pattern.trim_start_matches('/'))
+                .literal_separator(true)
+                .build()
+                .map_err(|e| format_err!("Invalid assets_exclude pattern `{}`. {}", pattern, e))?;
+            if pattern.contains('/') {
+                paths.add(glob);
+            } else {
+                file_names.add(glob);
+            }
+        }
+        Ok(Excludes {
+            file_names: file_names.build()?,
+            paths: paths.build()?,
+        })
+    }
+
+    fn is_match(&self, asset_path: &str) -> bool {
+        let file_name = asset_path.rsplit('/').next().unwrap_or(asset_path);
+        self.file_names.is_match(file_name) || self.paths.is_match(asset_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excludes() {
+        let excludes = Excludes::new(&[
+            "**/*.psd".to_owned(),
+            ".DS_Store".to_owned(),
+            "raw/*.wav".to_owned(),
+        ])
+        .unwrap();
+        assert!(excludes.is_match("textures/stone.psd"));
+        assert!(excludes.is_match("stone.psd"));
+        assert!(excludes.is_match("textures/.DS_Store"));
+        assert!(excludes.is_match("raw/hit.wav"));
+        assert!(!excludes.is_match("raw/sub/hit.wav"));
+        assert!(!excludes.is_match("sounds/raw/hit.wav"));
+        assert!(!excludes.is_match("textures/stone.png"));
+    }
+}
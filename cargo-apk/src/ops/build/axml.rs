@@ -0,0 +1,1048 @@
+//! Encoding of the manifest to the binary XML format used by Android, so that APKs without
+//! resources can be packaged without aapt, and decoding of binary XML to inspect APKs.
+//!
+//! Only the attributes of the `android` namespace listed in `ANDROID_ATTRIBUTES` are supported
+//! since their resource IDs and value formats have to be known in advance. References to
+//! resources are not supported either since there is no resource table to resolve them.
+
+use cargo::util::CargoResult;
+use failure::format_err;
+use std::collections::HashMap;
+use std::fmt;
+
+pub const ANDROID_NAMESPACE_URI: &str = "http://schemas.android.com/apk/res/android";
+const ANDROID_NAMESPACE_PREFIX: &str = "android";
+
+const RES_STRING_POOL_TYPE: u16 = 0x0001;
+const RES_XML_TYPE: u16 = 0x0003;
+const RES_XML_START_NAMESPACE_TYPE: u16 = 0x0100;
+const RES_XML_END_NAMESPACE_TYPE: u16 = 0x0101;
+const RES_XML_START_ELEMENT_TYPE: u16 = 0x0102;
+const RES_XML_END_ELEMENT_TYPE: u16 = 0x0103;
+const RES_XML_RESOURCE_MAP_TYPE: u16 = 0x0180;
+
+const TYPE_REFERENCE: u8 = 0x01;
+const TYPE_STRING: u8 = 0x03;
+const TYPE_FLOAT: u8 = 0x04;
+const TYPE_INT_DEC: u8 = 0x10;
+const TYPE_INT_HEX: u8 = 0x11;
+const TYPE_INT_BOOLEAN: u8 = 0x12;
+
+const NO_INDEX: u32 = 0xffff_ffff;
+const ATTRIBUTE_LEN: u16 = 20;
+
+/// Format of the value of an attribute
+#[derive(Debug, Copy, Clone)]
+enum Format {
+    String,
+    Boolean,
+    IntDec,
+    IntHex,
+    /// Boolean, integer, float or string depending on the value
+    Any,
+    Enum(&'static [(&'static str, u32)]),
+    Flags(&'static [(&'static str, u32)]),
+    /// Reference to a resource
+    Reference,
+}
+
+const LAUNCH_MODES: &[(&str, u32)] = &[
+    ("standard", 0),
+    ("singleTop", 1),
+    ("singleTask", 2),
+    ("singleInstance", 3),
+];
+
+const SCREEN_ORIENTATIONS: &[(&str, u32)] = &[
+    ("unspecified", 0xffff_ffff),
+    ("landscape", 0),
+    ("portrait", 1),
+    ("user", 2),
+    ("behind", 3),
+    ("sensor", 4),
+    ("nosensor", 5),
+    ("sensorLandscape", 6),
+    ("sensorPortrait", 7),
+    ("reverseLandscape", 8),
+    ("reversePortrait", 9),
+    ("fullSensor", 10),
+    ("userLandscape", 11),
+    ("userPortrait", 12),
+    ("fullUser", 13),
+    ("locked", 14),
+];
+
+const CONFIG_CHANGES: &[(&str, u32)] = &[
+    ("mcc", 0x0001),
+    ("mnc", 0x0002),
+    ("locale", 0x0004),
+    ("touchscreen", 0x0008),
+    ("keyboard", 0x0010),
+    ("keyboardHidden", 0x0020),
+    ("navigation", 0x0040),
+    ("orientation", 0x0080),
+    ("screenLayout", 0x0100),
+    ("uiMode", 0x0200),
+    ("screenSize", 0x0400),
+    ("smallestScreenSize", 0x0800),
+    ("density", 0x1000),
+    ("layoutDirection", 0x2000),
+    ("colorMode", 0x4000),
+    ("fontScale", 0x4000_0000),
+];
+
+const UI_OPTIONS: &[(&str, u32)] = &[("none", 0), ("splitActionBarWhenNarrow", 1)];
+
+/// Resource IDs and formats of the supported attributes of the `android` namespace, sorted by
+/// resource ID
+const ANDROID_ATTRIBUTES: &[(&str, u32, Format)] = &[
+    ("theme", 0x0101_0000, Format::Reference),
+    ("label", 0x0101_0001, Format::String),
+    ("icon", 0x0101_0002, Format::Reference),
+    ("name", 0x0101_0003, Format::String),
+    ("permission", 0x0101_0006, Format::String),
+    ("hasCode", 0x0101_000c, Format::Boolean),
+    ("persistent", 0x0101_000d, Format::Boolean),
+    ("enabled", 0x0101_000e, Format::Boolean),
+    ("debuggable", 0x0101_000f, Format::Boolean),
+    ("exported", 0x0101_0010, Format::Boolean),
+    ("process", 0x0101_0011, Format::String),
+    ("taskAffinity", 0x0101_0012, Format::String),
+    ("excludeFromRecents", 0x0101_0017, Format::Boolean),
+    ("launchMode", 0x0101_001d, Format::Enum(LAUNCH_MODES)),
+    (
+        "screenOrientation",
+        0x0101_001e,
+        Format::Enum(SCREEN_ORIENTATIONS),
+    ),
+    ("configChanges", 0x0101_001f, Format::Flags(CONFIG_CHANGES)),
+    ("description", 0x0101_0020, Format::String),
+    ("value", 0x0101_0024, Format::Any),
+    ("resource", 0x0101_0025, Format::Reference),
+    ("mimeType", 0x0101_0026, Format::String),
+    ("scheme", 0x0101_0027, Format::String),
+    ("host", 0x0101_0028, Format::String),
+    ("port", 0x0101_0029, Format::String),
+    ("path", 0x0101_002a, Format::String),
+    ("pathPrefix", 0x0101_002b, Format::String),
+    ("pathPattern", 0x0101_002c, Format::String),
+    ("minSdkVersion", 0x0101_020c, Format::Any),
+    ("versionCode", 0x0101_021b, Format::IntDec),
+    ("versionName", 0x0101_021c, Format::String),
+    ("targetSdkVersion", 0x0101_0270, Format::Any),
+    ("maxSdkVersion", 0x0101_0271, Format::IntDec),
+    ("allowBackup", 0x0101_0280, Format::Boolean),
+    ("glEsVersion", 0x0101_0281, Format::IntHex),
+    ("required", 0x0101_028e, Format::Boolean),
+    ("hardwareAccelerated", 0x0101_02d3, Format::Boolean),
+    ("uiOptions", 0x0101_0398, Format::Enum(UI_OPTIONS)),
+    ("extractNativeLibs", 0x0101_04ea, Format::Boolean),
+    ("autoVerify", 0x0101_04ee, Format::Boolean),
+];
+
+/// Element of an XML document. Text content is not represented since the manifest doesn't use
+/// it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<Attribute>,
+    pub children: Vec<Element>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    /// URI of the namespace of the attribute
+    pub namespace: Option<String>,
+    pub name: String,
+    pub value: String,
+}
+
+/// Typed value of an attribute in binary XML
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Boolean(bool),
+    IntDec(i32),
+    IntHex(u32),
+    Float(f32),
+    /// Resource ID of a referenced resource
+    Reference(u32),
+    /// Value of a type which is neither produced by the encoder nor interpreted by the decoder
+    Other(u8, u32),
+}
+
+impl Value {
+    fn parse(name: &str, format: Format, value: &str) -> CargoResult<Value> {
+        let invalid = || format_err!("Invalid value `{}` for attribute `{}`", value, name);
+        if value.starts_with('@') || value.starts_with('?') {
+            return Err(format_err!(
+                "Attribute `{}` references a resource (`{}`) which requires aapt",
+                name,
+                value
+            ));
+        }
+        Ok(match format {
+            Format::String => Value::String(value.to_owned()),
+            Format::Boolean => Value::Boolean(parse_bool(value).ok_or_else(invalid)?),
+            Format::IntDec => Value::IntDec(parse_int(value).ok_or_else(invalid)? as i32),
+            Format::IntHex => Value::IntHex(parse_int(value).ok_or_else(invalid)?),
+            Format::Any => {
+                if let Some(value) = parse_bool(value) {
+                    Value::Boolean(value)
+                } else if value.starts_with("0x") {
+                    Value::IntHex(parse_int(value).ok_or_else(invalid)?)
+                } else if let Ok(value) = value.parse::<i32>() {
+                    Value::IntDec(value)
+                } else if let (true, Ok(value)) = (value.contains('.'), value.parse::<f32>()) {
+                    Value::Float(value)
+                } else {
+                    Value::String(value.to_owned())
+                }
+            }
+            Format::Enum(values) => {
+                let value = values
+                    .iter()
+                    .find(|(name, _)| *name == value)
+                    .map(|(_, value)| *value)
+                    .or_else(|| parse_int(value))
+                    .ok_or_else(invalid)?;
+                Value::IntDec(value as i32)
+            }
+            Format::Flags(values) => {
+                let mut flags = 0;
+                for flag in value.split('|').map(str::trim) {
+                    flags |= values
+                        .iter()
+                        .find(|(name, _)| *name == flag)
+                        .map(|(_, value)| *value)
+                        .ok_or_else(invalid)?;
+                }
+                Value::IntHex(flags)
+            }
+            Format::Reference => return Err(invalid()),
+        })
+    }
+
+    fn data_type(&self) -> u8 {
+        match self {
+            Value::String(_) => TYPE_STRING,
+            Value::Boolean(_) => TYPE_INT_BOOLEAN,
+            Value::IntDec(_) => TYPE_INT_DEC,
+            Value::IntHex(_) => TYPE_INT_HEX,
+            Value::Float(_) => TYPE_FLOAT,
+            Value::Reference(_) => TYPE_REFERENCE,
+            Value::Other(data_type, _) => *data_type,
+        }
+    }
+
+    fn from_data(strings: &[String], data_type: u8, data: u32) -> CargoResult<Value> {
+        Ok(match data_type {
+            TYPE_STRING => Value::String(
+                strings
+                    .get(data as usize)
+                    .ok_or_else(|| format_err!("Invalid string index {}", data))?
+                    .clone(),
+            ),
+            TYPE_INT_BOOLEAN => Value::Boolean(data != 0),
+            TYPE_INT_DEC => Value::IntDec(data as i32),
+            TYPE_INT_HEX => Value::IntHex(data),
+            TYPE_FLOAT => Value::Float(f32::from_bits(data)),
+            TYPE_REFERENCE => Value::Reference(data),
+            _ => Value::Other(data_type, data),
+        })
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::String(value) => write!(f, "{}", value),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::IntDec(value) => write!(f, "{}", value),
+            Value::IntHex(value) => write!(f, "{:#x}", value),
+            Value::Float(value) => write!(f, "{}", value),
+            Value::Reference(id) => write!(f, "@{:#010x}", id),
+            Value::Other(data_type, data) => write!(f, "(type {:#04x}) {:#x}", data_type, data),
+        }
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_int(value: &str) -> Option<u32> {
+    if value.starts_with("0x") || value.starts_with("0X") {
+        u32::from_str_radix(&value[2..], 16).ok()
+    } else {
+        value.parse::<i32>().ok().map(|value| value as u32)
+    }
+}
+
+/// Returns the resource ID and typed value of an attribute
+fn typed_attribute(attribute: &Attribute) -> CargoResult<(Option<u32>, Value)> {
+    match attribute.namespace.as_deref() {
+        None => Ok((None, Value::String(attribute.value.clone()))),
+        Some(ANDROID_NAMESPACE_URI) => {
+            let (_, id, format) = ANDROID_ATTRIBUTES
+                .iter()
+                .find(|(name, _, _)| *name == attribute.name)
+                .ok_or_else(|| {
+                    format_err!(
+                        "Attribute `android:{}` is not supported without aapt",
+                        attribute.name
+                    )
+                })?;
+            let name = format!("android:{}", attribute.name);
+            Ok((Some(*id), Value::parse(&name, *format, &attribute.value)?))
+        }
+        Some(namespace) => Err(format_err!(
+            "Namespace `{}` of attribute `{}` is not supported without aapt",
+            namespace,
+            attribute.name
+        )),
+    }
+}
+
+/// Parses an XML document. Only the subset of XML used by manifests is supported: elements,
+/// attributes, comments and the XML declaration. Text content is ignored.
+pub fn parse(text: &str) -> CargoResult<Element> {
+    let mut parser = Parser { text, position: 0 };
+    parser.skip_misc()?;
+    let root = parser.element()?;
+    parser.skip_misc()?;
+    if parser.position != text.len() {
+        return Err(parser.error("Unexpected content after the root element"));
+    }
+    resolve_namespaces(root, &HashMap::new())
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+/// Element whose attribute names still contain their namespace prefix
+struct RawElement {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<RawElement>,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn error(&self, message: &str) -> failure::Error {
+        let line = self.text[..self.position].matches('\n').count() + 1;
+        format_err!("Invalid manifest XML at line {}. {}", line, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Skips everything up to and including the terminator
+    fn skip_past(&mut self, terminator: &str) -> CargoResult<&'a str> {
+        let rest = self.rest();
+        let end = rest
+            .find(terminator)
+            .ok_or_else(|| self.error(&format!("Missing `{}`", terminator)))?;
+        self.position += end + terminator.len();
+        Ok(&rest[..end])
+    }
+
+    /// Skips whitespace, comments and processing instructions
+    fn skip_misc(&mut self) -> CargoResult<()> {
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if self.rest().starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> CargoResult<&'a str> {
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        if end == 0 {
+            return Err(self.error("Expected a name"));
+        }
+        self.position += end;
+        Ok(&rest[..end])
+    }
+
+    fn expect(&mut self, expected: &str) -> CargoResult<()> {
+        if self.rest().starts_with(expected) {
+            self.position += expected.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected `{}`", expected)))
+        }
+    }
+
+    fn element(&mut self) -> CargoResult<RawElement> {
+        self.expect("<")?;
+        let name = self.name()?.to_owned();
+        let mut attributes = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.position += 2;
+                return Ok(RawElement {
+                    name,
+                    attributes,
+                    children: Vec::new(),
+                });
+            } else if self.rest().starts_with('>') {
+                self.position += 1;
+                break;
+            }
+
+            let attribute_name = self.name()?.to_owned();
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let quote = if self.rest().starts_with('"') {
+                "\""
+            } else {
+                "'"
+            };
+            self.expect(quote)?;
+            let value = unescape(self.skip_past(quote)?).map_err(|e| self.error(&e.to_string()))?;
+            attributes.push((attribute_name, value));
+        }
+
+        let mut children = Vec::new();
+        loop {
+            self.skip_past("<")?;
+            self.position -= 1;
+            if self.rest().starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.rest().starts_with("</") {
+                self.position += 2;
+                let end_name = self.name()?;
+                if end_name != name {
+                    return Err(self.error(&format!(
+                        "Expected `</{}>` but found `</{}>`",
+                        name, end_name
+                    )));
+                }
+                self.skip_whitespace();
+                self.expect(">")?;
+                return Ok(RawElement {
+                    name,
+                    attributes,
+                    children,
+                });
+            } else {
+                children.push(self.element()?);
+            }
+        }
+    }
+}
+
+fn unescape(value: &str) -> CargoResult<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find(';')
+            .ok_or_else(|| format_err!("Unterminated entity in `{}`", value))?;
+        let entity = &rest[start + 1..start + end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16)
+                .ok()
+                .and_then(std::char::from_u32),
+            _ if entity.starts_with('#') => entity[1..]
+                .parse::<u32>()
+                .ok()
+                .and_then(std::char::from_u32),
+            _ => None,
+        };
+        result.push(c.ok_or_else(|| format_err!("Unknown entity `&{};`", entity))?);
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Replaces the namespace prefixes of attributes by the namespace URIs declared using `xmlns`
+fn resolve_namespaces(
+    element: RawElement,
+    parent_namespaces: &HashMap<String, String>,
+) -> CargoResult<Element> {
+    let mut namespaces = parent_namespaces.clone();
+    for (name, value) in &element.attributes {
+        if let Some(prefix) = name.strip_prefix("xmlns:") {
+            namespaces.insert(prefix.to_owned(), value.clone());
+        }
+    }
+
+    let mut attributes = Vec::new();
+    for (name, value) in element.attributes {
+        if name.starts_with("xmlns:") {
+            continue;
+        }
+        let mut parts = name.splitn(2, ':');
+        let (namespace, name) = match (parts.next(), parts.next()) {
+            (Some(prefix), Some(name)) => {
+                let namespace = namespaces
+                    .get(prefix)
+                    .ok_or_else(|| format_err!("Undeclared namespace prefix `{}`", prefix))?;
+                (Some(namespace.clone()), name.to_owned())
+            }
+            _ => (None, name),
+        };
+        attributes.push(Attribute {
+            namespace,
+            name,
+            value,
+        });
+    }
+
+    let children = element
+        .children
+        .into_iter()
+        .map(|child| resolve_namespaces(child, &namespaces))
+        .collect::<CargoResult<_>>()?;
+
+    Ok(Element {
+        name: element.name,
+        attributes,
+        children,
+    })
+}
+
+/// Strings of the string pool. The names of attributes with a resource ID come first, in the
+/// same order as the resource map.
+struct StringPool {
+    strings: Vec<String>,
+    indices: HashMap<String, u32>,
+}
+
+impl StringPool {
+    fn index(&mut self, string: &str) -> u32 {
+        if let Some(index) = self.indices.get(string) {
+            return *index;
+        }
+        let index = self.strings.len() as u32;
+        self.strings.push(string.to_owned());
+        self.indices.insert(string.to_owned(), index);
+        index
+    }
+
+    /// Encodes the pool using UTF-16 strings
+    fn encode(&self) -> Vec<u8> {
+        let mut offsets = Vec::new();
+        let mut data = Vec::new();
+        for string in &self.strings {
+            offsets.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            let units = string.encode_utf16().collect::<Vec<_>>();
+            if units.len() > 0x7fff {
+                data.extend_from_slice(&((units.len() >> 16) as u16 | 0x8000).to_le_bytes());
+            }
+            data.extend_from_slice(&(units.len() as u16).to_le_bytes());
+            for unit in units {
+                data.extend_from_slice(&unit.to_le_bytes());
+            }
+            data.extend_from_slice(&0u16.to_le_bytes());
+        }
+        data.resize(data.len() + (4 - data.len() % 4) % 4, 0);
+
+        let header_len = 28;
+        let strings_start = header_len + offsets.len();
+        let mut chunk = chunk_header(
+            RES_STRING_POOL_TYPE,
+            header_len as u16,
+            strings_start + data.len(),
+        );
+        chunk.extend_from_slice(&(self.strings.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(&0u32.to_le_bytes()); // Style count
+        chunk.extend_from_slice(&0u32.to_le_bytes()); // Flags
+        chunk.extend_from_slice(&(strings_start as u32).to_le_bytes());
+        chunk.extend_from_slice(&0u32.to_le_bytes()); // Styles start
+        chunk.extend_from_slice(&offsets);
+        chunk.extend_from_slice(&data);
+        chunk
+    }
+}
+
+fn chunk_header(chunk_type: u16, header_len: u16, len: usize) -> Vec<u8> {
+    let mut header = Vec::with_capacity(len);
+    header.extend_from_slice(&chunk_type.to_le_bytes());
+    header.extend_from_slice(&header_len.to_le_bytes());
+    header.extend_from_slice(&(len as u32).to_le_bytes());
+    header
+}
+
+/// Header of an XML node: chunk header, line number and comment
+fn node_header(chunk_type: u16, len: usize) -> Vec<u8> {
+    let mut header = chunk_header(chunk_type, 16, len);
+    header.extend_from_slice(&1u32.to_le_bytes());
+    header.extend_from_slice(&NO_INDEX.to_le_bytes());
+    header
+}
+
+/// Encodes an XML document to Android binary XML. Attribute values are typed according to the
+/// format of the attribute.
+pub fn encode(root: &Element) -> CargoResult<Vec<u8>> {
+    // Attribute names with a resource ID go first in the string pool, sorted by resource ID
+    let mut resource_ids = Vec::new();
+    collect_resource_ids(root, &mut resource_ids)?;
+    resource_ids.sort();
+    resource_ids.dedup();
+    let mut pool = StringPool {
+        strings: Vec::new(),
+        indices: HashMap::new(),
+    };
+    for (_, name) in &resource_ids {
+        pool.index(name);
+    }
+
+    let android_prefix = pool.index(ANDROID_NAMESPACE_PREFIX);
+    let android_uri = pool.index(ANDROID_NAMESPACE_URI);
+    let mut nodes = node_header(RES_XML_START_NAMESPACE_TYPE, 24);
+    nodes.extend_from_slice(&android_prefix.to_le_bytes());
+    nodes.extend_from_slice(&android_uri.to_le_bytes());
+    encode_element(root, &mut pool, &mut nodes)?;
+    nodes.extend_from_slice(&node_header(RES_XML_END_NAMESPACE_TYPE, 24));
+    nodes.extend_from_slice(&android_prefix.to_le_bytes());
+    nodes.extend_from_slice(&android_uri.to_le_bytes());
+
+    let string_pool = pool.encode();
+    let mut resource_map = chunk_header(RES_XML_RESOURCE_MAP_TYPE, 8, 8 + 4 * resource_ids.len());
+    for (id, _) in &resource_ids {
+        resource_map.extend_from_slice(&id.to_le_bytes());
+    }
+
+    let mut document = chunk_header(
+        RES_XML_TYPE,
+        8,
+        8 + string_pool.len() + resource_map.len() + nodes.len(),
+    );
+    document.extend_from_slice(&string_pool);
+    document.extend_from_slice(&resource_map);
+    document.extend_from_slice(&nodes);
+    Ok(document)
+}
+
+fn collect_resource_ids(element: &Element, ids: &mut Vec<(u32, String)>) -> CargoResult<()> {
+    for attribute in &element.attributes {
+        if let (Some(id), _) = typed_attribute(attribute)? {
+            ids.push((id, attribute.name.clone()));
+        }
+    }
+    for child in &element.children {
+        collect_resource_ids(child, ids)?;
+    }
+    Ok(())
+}
+
+fn encode_element(
+    element: &Element,
+    pool: &mut StringPool,
+    nodes: &mut Vec<u8>,
+) -> CargoResult<()> {
+    // Attributes with a resource ID must be sorted by resource ID since Android looks them up
+    // in order
+    let mut attributes = element
+        .attributes
+        .iter()
+        .map(|attribute| typed_attribute(attribute).map(|(id, value)| (id, attribute, value)))
+        .collect::<CargoResult<Vec<_>>>()?;
+    attributes.sort_by(|(a_id, a, _), (b_id, b, _)| {
+        (a_id.is_none(), a_id, &a.name).cmp(&(b_id.is_none(), b_id, &b.name))
+    });
+
+    let name = pool.index(&element.name);
+    let len = 16 + 20 + attributes.len() * ATTRIBUTE_LEN as usize;
+    nodes.extend_from_slice(&node_header(RES_XML_START_ELEMENT_TYPE, len));
+    nodes.extend_from_slice(&NO_INDEX.to_le_bytes()); // Namespace
+    nodes.extend_from_slice(&name.to_le_bytes());
+    nodes.extend_from_slice(&20u16.to_le_bytes()); // Attribute start
+    nodes.extend_from_slice(&ATTRIBUTE_LEN.to_le_bytes());
+    nodes.extend_from_slice(&(attributes.len() as u16).to_le_bytes());
+    nodes.extend_from_slice(&0u16.to_le_bytes()); // ID index
+    nodes.extend_from_slice(&0u16.to_le_bytes()); // Class index
+    nodes.extend_from_slice(&0u16.to_le_bytes()); // Style index
+
+    for (_, attribute, value) in attributes {
+        let namespace = match &attribute.namespace {
+            Some(namespace) => pool.index(namespace),
+            None => NO_INDEX,
+        };
+        let (raw_value, data) = match &value {
+            Value::String(string) => {
+                let index = pool.index(string);
+                (index, index)
+            }
+            Value::Boolean(value) => (NO_INDEX, if *value { 0xffff_ffff } else { 0 }),
+            Value::IntDec(value) => (NO_INDEX, *value as u32),
+            Value::IntHex(value) => (NO_INDEX, *value),
+            Value::Float(value) => (NO_INDEX, value.to_bits()),
+            Value::Reference(id) => (NO_INDEX, *id),
+            Value::Other(_, data) => (NO_INDEX, *data),
+        };
+        nodes.extend_from_slice(&namespace.to_le_bytes());
+        nodes.extend_from_slice(&pool.index(&attribute.name).to_le_bytes());
+        nodes.extend_from_slice(&raw_value.to_le_bytes());
+        nodes.extend_from_slice(&8u16.to_le_bytes()); // Value size
+        nodes.push(0);
+        nodes.push(value.data_type());
+        nodes.extend_from_slice(&data.to_le_bytes());
+    }
+
+    for child in &element.children {
+        encode_element(child, pool, nodes)?;
+    }
+
+    nodes.extend_from_slice(&node_header(RES_XML_END_ELEMENT_TYPE, 24));
+    nodes.extend_from_slice(&NO_INDEX.to_le_bytes());
+    nodes.extend_from_slice(&name.to_le_bytes());
+    Ok(())
+}
+
+/// Element decoded from binary XML
+#[derive(Debug)]
+pub struct DecodedElement {
+    pub name: String,
+    pub attributes: Vec<DecodedAttribute>,
+    pub children: Vec<DecodedElement>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DecodedAttribute {
+    /// URI of the namespace of the attribute
+    pub namespace: Option<String>,
+    pub name: String,
+    pub resource_id: Option<u32>,
+    pub value: Value,
+}
+
+impl DecodedElement {
+    /// Returns the value of the attribute of the `android` namespace with the given name
+    pub fn android_attribute(&self, name: &str) -> Option<&Value> {
+        self.attributes
+            .iter()
+            .find(|attribute| {
+                attribute.namespace.as_deref() == Some(ANDROID_NAMESPACE_URI)
+                    && attribute.name == name
+            })
+            .map(|attribute| &attribute.value)
+    }
+
+    /// Returns the value of the attribute without namespace with the given name
+    pub fn attribute(&self, name: &str) -> Option<&Value> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.namespace.is_none() && attribute.name == name)
+            .map(|attribute| &attribute.value)
+    }
+
+    /// Returns the children with the given name
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a DecodedElement> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> CargoResult<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| format_err!("Truncated binary XML"))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> CargoResult<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| format_err!("Truncated binary XML"))
+}
+
+/// Decodes the strings of a UTF-8 or UTF-16 string pool chunk
+fn decode_string_pool(chunk: &[u8]) -> CargoResult<Vec<String>> {
+    const UTF8_FLAG: u32 = 0x100;
+    let count = read_u32(chunk, 8)? as usize;
+    let utf8 = read_u32(chunk, 16)? & UTF8_FLAG != 0;
+    let strings_start = read_u32(chunk, 20)? as usize;
+    let header_len = read_u16(chunk, 2)? as usize;
+
+    // Each string has a 4 byte offset, so the count read from the chunk can't exceed this
+    let mut strings = Vec::with_capacity(count.min(chunk.len() / 4));
+    for i in 0..count {
+        let mut offset = strings_start + read_u32(chunk, header_len + 4 * i)? as usize;
+        let string = if utf8 {
+            // UTF-16 length followed by the UTF-8 length, each encoded in one or two bytes
+            let mut len = 0;
+            for _ in 0..2 {
+                let byte = *chunk
+                    .get(offset)
+                    .ok_or_else(|| format_err!("Truncated string pool"))?;
+                len = usize::from(byte);
+                offset += 1;
+                if byte & 0x80 != 0 {
+                    let low = *chunk
+                        .get(offset)
+                        .ok_or_else(|| format_err!("Truncated string pool"))?;
+                    len = (usize::from(byte & 0x7f) << 8) | usize::from(low);
+                    offset += 1;
+                }
+            }
+            let bytes = chunk
+                .get(offset..offset + len)
+                .ok_or_else(|| format_err!("Truncated string pool"))?;
+            String::from_utf8_lossy(bytes).into_owned()
+        } else {
+            let mut len = read_u16(chunk, offset)? as usize;
+            offset += 2;
+            if len & 0x8000 != 0 {
+                len = ((len & 0x7fff) << 16) | read_u16(chunk, offset)? as usize;
+                offset += 2;
+            }
+            let units = (0..len)
+                .map(|j| read_u16(chunk, offset + 2 * j))
+                .collect::<CargoResult<Vec<_>>>()?;
+            String::from_utf16_lossy(&units)
+        };
+        strings.push(string);
+    }
+    Ok(strings)
+}
+
+/// Decodes an Android binary XML document
+pub fn decode(data: &[u8]) -> CargoResult<DecodedElement> {
+    if read_u16(data, 0)? != RES_XML_TYPE {
+        return Err(format_err!("Not an Android binary XML document"));
+    }
+
+    let mut strings = Vec::new();
+    let mut resource_ids = Vec::new();
+    let mut stack: Vec<DecodedElement> = Vec::new();
+    let mut root = None;
+    let string = |strings: &[String], index: u32| -> CargoResult<Option<String>> {
+        if index == NO_INDEX {
+            Ok(None)
+        } else {
+            strings
+                .get(index as usize)
+                .cloned()
+                .map(Some)
+                .ok_or_else(|| format_err!("Invalid string index {}", index))
+        }
+    };
+
+    let end = (read_u32(data, 4)? as usize).min(data.len());
+    let mut offset = read_u16(data, 2)? as usize;
+    while offset + 8 <= end {
+        let chunk_type = read_u16(data, offset)?;
+        let len = read_u32(data, offset + 4)? as usize;
+        let chunk = data
+            .get(offset..offset + len)
+            .filter(|_| len >= 8)
+            .ok_or_else(|| format_err!("Invalid binary XML chunk"))?;
+        match chunk_type {
+            RES_STRING_POOL_TYPE => strings = decode_string_pool(chunk)?,
+            RES_XML_RESOURCE_MAP_TYPE => {
+                resource_ids = (8..len)
+                    .step_by(4)
+                    .map(|o| read_u32(chunk, o))
+                    .collect::<CargoResult<_>>()?;
+            }
+            RES_XML_START_ELEMENT_TYPE => {
+                let ext = read_u16(chunk, 2)? as usize;
+                let attribute_start = ext + read_u16(chunk, ext + 8)? as usize;
+                let attribute_len = read_u16(chunk, ext + 10)? as usize;
+                let count = read_u16(chunk, ext + 12)? as usize;
+                let mut attributes = Vec::with_capacity(count);
+                for i in 0..count {
+                    let a = attribute_start + i * attribute_len;
+                    let name_index = read_u32(chunk, a + 4)?;
+                    let data_type = *chunk
+                        .get(a + 15)
+                        .ok_or_else(|| format_err!("Truncated binary XML"))?;
+                    attributes.push(DecodedAttribute {
+                        namespace: string(&strings, read_u32(chunk, a)?)?,
+                        name: string(&strings, name_index)?.unwrap_or_default(),
+                        resource_id: resource_ids.get(name_index as usize).cloned(),
+                        value: Value::from_data(&strings, data_type, read_u32(chunk, a + 16)?)?,
+                    });
+                }
+                stack.push(DecodedElement {
+                    name: string(&strings, read_u32(chunk, ext + 4)?)?.unwrap_or_default(),
+                    attributes,
+                    children: Vec::new(),
+                });
+            }
+            RES_XML_END_ELEMENT_TYPE => {
+                let element = stack
+                    .pop()
+                    .ok_or_else(|| format_err!("Unbalanced binary XML elements"))?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => root = Some(element),
+                }
+            }
+            _ => {}
+        }
+        offset += len;
+    }
+
+    root.ok_or_else(|| format_err!("Binary XML document has no root element"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that the decoded element matches the parsed element
+    fn assert_round_trip(element: &Element, decoded: &DecodedElement) {
+        assert_eq!(element.name, decoded.name);
+        assert_eq!(element.attributes.len(), decoded.attributes.len());
+        for attribute in &element.attributes {
+            let decoded_attribute = decoded
+                .attributes
+                .iter()
+                .find(|decoded_attribute| {
+                    decoded_attribute.namespace == attribute.namespace
+                        && decoded_attribute.name == attribute.name
+                })
+                .unwrap();
+            let (resource_id, value) = typed_attribute(attribute).unwrap();
+            assert_eq!(decoded_attribute.resource_id, resource_id);
+            assert_eq!(decoded_attribute.value, value);
+        }
+
+        // Attributes with resource IDs come first, sorted by resource ID
+        let ids = decoded
+            .attributes
+            .iter()
+            .map(|attribute| attribute.resource_id.unwrap_or(u32::MAX))
+            .collect::<Vec<_>>();
+        let mut sorted_ids = ids.clone();
+        sorted_ids.sort();
+        assert_eq!(ids, sorted_ids);
+
+        assert_eq!(element.children.len(), decoded.children.len());
+        for (child, decoded_child) in element.children.iter().zip(&decoded.children) {
+            assert_round_trip(child, decoded_child);
+        }
+    }
+
+    const MANIFEST: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<!-- Generated -->
+<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+        package="rust.example"
+        android:versionCode="3"
+        android:versionName="1.0 &quot;beta&quot;">
+    <uses-sdk android:targetSdkVersion="29" android:minSdkVersion="18" />
+    <uses-feature android:glEsVersion="0x00020000" android:required="true"></uses-feature>
+    <uses-permission android:name="android.permission.INTERNET" android:maxSdkVersion="28"/>
+    <application android:hasCode="false" android:label="Example &amp; co"
+            android:extractNativeLibs="false" android:debuggable="true">
+        <activity android:name="android.app.NativeActivity"
+                android:configChanges="orientation|keyboardHidden|screenSize"
+                android:screenOrientation="landscape">
+            <meta-data android:name="android.app.lib_name" android:value="example" />
+            <meta-data android:name="scale" android:value="1.5" />
+            <intent-filter>
+                <action android:name="android.intent.action.MAIN" />
+                <category android:name="android.intent.category.LAUNCHER" />
+            </intent-filter>
+        </activity>
+    </application>
+</manifest>"#;
+
+    #[test]
+    fn round_trip() {
+        let manifest = parse(MANIFEST).unwrap();
+        let decoded = decode(&encode(&manifest).unwrap()).unwrap();
+        assert_round_trip(&manifest, &decoded);
+
+        assert_eq!(
+            decoded.attributes,
+            vec![
+                DecodedAttribute {
+                    namespace: Some(ANDROID_NAMESPACE_URI.to_owned()),
+                    name: "versionCode".to_owned(),
+                    resource_id: Some(0x0101_021b),
+                    value: Value::IntDec(3),
+                },
+                DecodedAttribute {
+                    namespace: Some(ANDROID_NAMESPACE_URI.to_owned()),
+                    name: "versionName".to_owned(),
+                    resource_id: Some(0x0101_021c),
+                    value: Value::String("1.0 \"beta\"".to_owned()),
+                },
+                DecodedAttribute {
+                    namespace: None,
+                    name: "package".to_owned(),
+                    resource_id: None,
+                    value: Value::String("rust.example".to_owned()),
+                },
+            ]
+        );
+        let application = decoded.children("application").next().unwrap();
+        let activity = &application.children[0];
+        assert_eq!(activity.children("meta-data").count(), 2);
+        assert_eq!(
+            activity.android_attribute("screenOrientation"),
+            Some(&Value::IntDec(0))
+        );
+        assert_eq!(
+            activity.android_attribute("configChanges"),
+            Some(&Value::IntHex(0x4a0))
+        );
+        assert_eq!(
+            activity.children[1].android_attribute("value"),
+            Some(&Value::Float(1.5))
+        );
+        assert_eq!(
+            decoded.children[1].android_attribute("glEsVersion"),
+            Some(&Value::IntHex(0x0002_0000))
+        );
+    }
+
+    #[test]
+    fn unsupported_attributes() {
+        let manifest = parse(
+            r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android">
+                <application android:icon="@mipmap/icon" />
+            </manifest>"#,
+        )
+        .unwrap();
+        assert!(encode(&manifest).is_err());
+
+        let manifest = parse(
+            r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android">
+                <application android:unknownAttribute="true" />
+            </manifest>"#,
+        )
+        .unwrap();
+        assert!(encode(&manifest).is_err());
+    }
+
+    #[test]
+    fn invalid_xml() {
+        assert!(parse("<manifest>").is_err());
+        assert!(parse("<manifest></application>").is_err());
+        assert!(parse("<manifest a=\"&unknown;\"/>").is_err());
+        assert!(parse("<manifest android:a=\"b\"/>").is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! Android App Bundle output. The bundle contains a single base module whose manifest and
+//! resources are linked by aapt2 in protobuf format.
+
+use super::apk::{ApkReader, ApkWriter, Compression, DosDateTime};
+use super::assets;
+use super::compile::SharedLibrary;
+use super::resources::{self, ResourceTool};
+use super::sign::{self, SigningKey};
+use crate::config::{AndroidConfig, AndroidTargetConfig};
+use cargo::core::Target;
+use cargo::util::CargoResult;
+use failure::format_err;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Version of bundletool whose bundle format is produced
+const BUNDLETOOL_VERSION: &str = "0.13.0";
+
+/// Builds a signed bundle containing the manifest, resources, assets and shared libraries
+pub fn build_bundle(
+    config: &AndroidConfig,
+    target_config: &AndroidTargetConfig,
+    target: &Target,
+    target_directory: &Path,
+    shared_libraries: &[SharedLibrary],
+    signing_key: &SigningKey,
+    bundle_path: &Path,
+) -> CargoResult<()> {
+    let aapt2_path = match ResourceTool::find(config)? {
+        ResourceTool::Aapt2(aapt2_path) => aapt2_path,
+        ResourceTool::Aapt(_) => {
+            return Err(format_err!(
+                "Building an app bundle requires aapt2 which is missing from build-tools {}",
+                config.build_tools_version
+            ))
+        }
+    };
+
+    // Link the manifest, resources and assets in protobuf format
+    let proto_apk_path = target_directory.join(format!("{}_proto.apk", target.name()));
+    resources::aapt2_link(
+        &aapt2_path,
+        config,
+        target_config,
+        target,
+        target_directory,
+        &proto_apk_path,
+        true,
+    )?;
+
+    // Lay out the base module
+    let unsigned_bundle_path = target_directory.join(format!("{}_unsigned.aab", target.name()));
+    let proto_apk = ApkReader::open(&proto_apk_path)?;
+    let mut bundle = ApkWriter::create(&unsigned_bundle_path)?;
+    if let Some(timestamp) = config.reproducible_timestamp {
+        bundle.set_timestamp(DosDateTime::from_unix_timestamp(timestamp));
+    }
+    let modified = DosDateTime::from_system_time(SystemTime::now());
+    bundle.add_entry(
+        "BundleConfig.pb",
+        &bundle_config(),
+        Compression::Deflated,
+        modified,
+    )?;
+
+    // Entries are sorted by name so that their order doesn't depend on aapt2 or on the build order
+    let mut proto_entries = proto_apk.entries().iter().collect::<Vec<_>>();
+    proto_entries.sort_by(|a, b| a.name.cmp(&b.name));
+    for entry in proto_entries {
+        let name = &entry.name;
+        let module_path = if name == "AndroidManifest.xml" {
+            "base/manifest/AndroidManifest.xml".to_owned()
+        } else if name == "resources.pb" || name.starts_with("res/") || name.starts_with("assets/")
+        {
+            format!("base/{}", name)
+        } else {
+            format!("base/root/{}", name)
+        };
+        bundle.copy_entry_as(&proto_apk, entry, &module_path)?;
+    }
+
+    let out_dirs = shared_libraries
+        .iter()
+        .filter_map(|shared_library| shared_library.out_dir.as_deref())
+        .collect::<Vec<_>>();
+    for asset in assets::collect(target_config, &out_dirs)? {
+        bundle.add_file(
+            &format!("base/{}", asset.apk_path),
+            &asset.path,
+            asset.compression,
+        )?;
+    }
+
+    let mut so_paths = shared_libraries
+        .iter()
+        .map(|shared_library| {
+            let so_path = format!(
+                "base/lib/{}/{}",
+                &shared_library.abi.android_abi(),
+                shared_library.filename
+            );
+            (so_path, &shared_library.path)
+        })
+        .collect::<Vec<_>>();
+    so_paths.sort();
+    for (so_path, path) in so_paths {
+        bundle.add_file(&so_path, path, Compression::Deflated)?;
+    }
+    bundle.finish()?;
+
+    sign::sign_bundle(&unsigned_bundle_path, bundle_path, signing_key)
+}
+
+/// Returns the protobuf encoded `BundleConfig` which only contains the bundletool version
+fn bundle_config() -> Vec<u8> {
+    // message Bundletool { string version = 2; }
+    let mut bundletool = vec![0x12, BUNDLETOOL_VERSION.len() as u8];
+    bundletool.extend_from_slice(BUNDLETOOL_VERSION.as_bytes());
+
+    // message BundleConfig { Bundletool bundletool = 1; }
+    let mut bundle_config = vec![0x0a, bundletool.len() as u8];
+    bundle_config.extend_from_slice(&bundletool);
+    bundle_config
+}
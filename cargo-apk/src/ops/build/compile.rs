@@ -24,6 +24,10 @@ pub struct SharedLibrary {
     pub abi: AndroidBuildTarget,
     pub path: PathBuf,
     pub filename: String,
+    /// Unstripped copy of the library, kept when the packaged library is stripped
+    pub debug_symbols_path: Option<PathBuf>,
+    /// `OUT_DIR` of the build script of the package, for the library built from the cargo target
+    pub out_dir: Option<PathBuf>,
 }
 
 pub struct SharedLibraries {
@@ -77,12 +81,14 @@ pub fn build_shared_libraries(
         )?;
         opts.build_config.requested_kind =
             CompileKind::Target(CompileTarget::new(build_target.rust_triple())?);
+        opts.features.extend(config.cargo_features());
 
         // Create executor
         let config = Arc::new(config.clone());
         let executor: Arc<dyn Executor> = Arc::new(SharedLibraryExecutor {
             config: Arc::clone(&config),
             build_target_dir: build_target_dir.clone(),
+            symbols_dir: root_build_dir.join("symbols").join(build_target.android_abi()),
             android_native_glue_object,
             build_target,
             shared_libraries: shared_libraries.clone(),
@@ -103,6 +109,8 @@ pub fn build_shared_libraries(
 struct SharedLibraryExecutor {
     config: Arc<AndroidConfig>,
     build_target_dir: PathBuf,
+    // Directory in which the unstripped shared libraries are kept in release builds
+    symbols_dir: PathBuf,
     android_native_glue_object: PathBuf,
     build_target: AndroidBuildTarget,
 
@@ -295,10 +303,8 @@ mod cargo_apk_glue_code {
             // Add android native glue
             new_args.push(build_arg("-Clink-arg=", &self.android_native_glue_object));
 
-            // Strip symbols for release builds
-            if self.config.release {
-                new_args.push("-Clink-arg=-strip-all".into());
-            }
+            // Add a GNU build ID so that crash reports can be matched with the debug symbols
+            new_args.push("-Clink-arg=--build-id=sha1".into());
 
             // Require position independent code
             new_args.push("-Crelocation-model=pic".into());
@@ -317,6 +323,28 @@ mod cargo_apk_glue_code {
             let stdout = cmd.arg("--print").arg("file-names").exec_with_output()?;
             let stdout = String::from_utf8(stdout.stdout).unwrap();
             let library_path = build_path.join(stdout.lines().next().unwrap());
+            let filename = format!("lib{}.so", target.name());
+
+            // Strip symbols for release builds. The unstripped library is kept in the symbols
+            // directory so that crash reports can be symbolicated.
+            let debug_symbols_path = if self.config.release {
+                fs::create_dir_all(&self.symbols_dir)?;
+                let debug_symbols_path = self.symbols_dir.join(&filename);
+                fs::copy(&library_path, &debug_symbols_path).map_err(|e| format_err!(
+                    "Unable to copy `{}` to `{}`. {}", library_path.display(), debug_symbols_path.display(), e)
+                )?;
+                process(util::find_strip(&self.config, self.build_target)?)
+                    .arg("--strip-all")
+                    .arg(&library_path)
+                    .exec()?;
+                Some(debug_symbols_path)
+            } else {
+                None
+            };
+
+            // The build script of the package may list generated assets in its OUT_DIR. Only the
+            // variables set by cargo are considered, not the ones cargo-apk was run with.
+            let out_dir = cmd.get_envs().get("OUT_DIR").and_then(Option::as_ref).map(PathBuf::from);
 
             let mut shared_libraries = self.shared_libraries.lock().unwrap();
             shared_libraries.insert(
@@ -324,7 +352,9 @@ mod cargo_apk_glue_code {
                 SharedLibrary {
                     abi: self.build_target,
                     path: library_path.clone(),
-                    filename: format!("lib{}.so", target.name()),
+                    filename,
+                    debug_symbols_path,
+                    out_dir,
                 },
             );
 
@@ -381,6 +411,8 @@ mod cargo_apk_glue_code {
                             abi: self.build_target,
                             path,
                             filename: dylib.clone(),
+                            debug_symbols_path: None,
+                            out_dir: None,
                         },
                     );
                 } else {
@@ -494,12 +526,16 @@ fn write_native_app_glue_src(android_artifacts_dir: &Path) -> CargoResult<PathBu
     let output_dir = android_artifacts_dir.join("native_app_glue");
     fs::create_dir_all(&output_dir).unwrap();
 
-    let mut h_file = File::create(output_dir.join("android_native_app_glue.h"))?;
-    h_file.write_all(&include_bytes!("../../../native_app_glue/android_native_app_glue.h")[..])?;
+    util::write_if_changed(
+        &output_dir.join("android_native_app_glue.h"),
+        &include_bytes!("../../../native_app_glue/android_native_app_glue.h")[..],
+    )?;
 
     let c_path = output_dir.join("android_native_app_glue.c");
-    let mut c_file = File::create(&c_path)?;
-    c_file.write_all(&include_bytes!("../../../native_app_glue/android_native_app_glue.c")[..])?;
+    util::write_if_changed(
+        &c_path,
+        &include_bytes!("../../../native_app_glue/android_native_app_glue.c")[..],
+    )?;
 
     Ok(c_path)
 }
@@ -507,7 +543,7 @@ fn write_native_app_glue_src(android_artifacts_dir: &Path) -> CargoResult<PathBu
 /// Returns the path to the built object file for the android native glue
 fn build_android_native_glue(
     config: &AndroidConfig,
-    android_native_glue_src_path: &PathBuf,
+    android_native_glue_src_path: &Path,
     build_target_dir: &PathBuf,
     build_target: AndroidBuildTarget,
 ) -> CargoResult<PathBuf> {
@@ -518,12 +554,15 @@ fn build_android_native_glue(
     let android_native_glue_object_path =
         android_native_glue_build_path.join("android_native_glue.o");
 
+    // The source is compiled from its directory with a relative path so that the object file
+    // doesn't depend on the location of the target directory
     // Will produce warnings when bulding on linux? Create constants for extensions that can be used.. Or have separate functions?
     util::script_process(clang)
-        .arg(android_native_glue_src_path)
+        .arg(android_native_glue_src_path.file_name().unwrap())
         .arg("-c")
         .arg("-o")
         .arg(&android_native_glue_object_path)
+        .cwd(android_native_glue_src_path.parent().unwrap())
         .exec()?;
 
     Ok(android_native_glue_object_path)
@@ -0,0 +1,44 @@
+//! Archive of the unstripped shared libraries, in the layout expected by the Play Console
+
+use super::apk::{ApkWriter, Compression, DosDateTime};
+use super::compile::SharedLibrary;
+use cargo::util::CargoResult;
+use std::path::Path;
+
+/// Writes the unstripped shared libraries to a zip with an `<abi>/<library>` entry for each
+/// library. Returns false without writing the zip if no library has debug symbols.
+pub fn write_zip(
+    shared_libraries: &[SharedLibrary],
+    reproducible_timestamp: Option<u64>,
+    zip_path: &Path,
+) -> CargoResult<bool> {
+    let mut libraries = shared_libraries
+        .iter()
+        .filter_map(|shared_library| {
+            shared_library.debug_symbols_path.as_ref().map(|path| {
+                (
+                    format!(
+                        "{}/{}",
+                        shared_library.abi.android_abi(),
+                        shared_library.filename
+                    ),
+                    path,
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    if libraries.is_empty() {
+        return Ok(false);
+    }
+    libraries.sort();
+
+    let mut zip = ApkWriter::create(zip_path)?;
+    if let Some(timestamp) = reproducible_timestamp {
+        zip.set_timestamp(DosDateTime::from_unix_timestamp(timestamp));
+    }
+    for (name, path) in libraries {
+        zip.add_file(&name, path, Compression::Deflated)?;
+    }
+    zip.finish()?;
+    Ok(true)
+}
@@ -0,0 +1,203 @@
+//! Minimal reader of the little endian ELF shared libraries produced for the Android targets
+
+use cargo::util::CargoResult;
+use failure::format_err;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELF_CLASS_32: u8 = 1;
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_LITTLE_ENDIAN: u8 = 1;
+
+const SECTION_TYPE_SYMTAB: u32 = 2;
+const SECTION_TYPE_DYNSYM: u32 = 11;
+
+const SYMBOL_TYPE_OBJECT: u8 = 1;
+const SYMBOL_TYPE_FUNC: u8 = 2;
+const SECTION_INDEX_UNDEFINED: u16 = 0;
+
+/// Section header of an ELF file
+#[derive(Debug)]
+pub struct Section {
+    pub name: String,
+    pub section_type: u32,
+    pub offset: usize,
+    pub size: usize,
+    /// Index of the associated section. For symbol tables, the index of the string table.
+    pub link: usize,
+}
+
+/// Function or data symbol defined by an ELF file
+#[derive(Debug)]
+pub struct Symbol {
+    /// Mangled name of the symbol
+    pub name: String,
+    pub address: u64,
+    pub size: u64,
+}
+
+/// Symbol table from which the symbols of an ELF file were read
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SymbolTable {
+    /// Complete symbol table, which is removed by stripping
+    Full,
+    /// Exported symbols only, as found in stripped libraries
+    Dynamic,
+}
+
+pub struct Elf<'a> {
+    data: &'a [u8],
+    is_64_bit: bool,
+    sections: Vec<Section>,
+}
+
+impl<'a> Elf<'a> {
+    pub fn parse(data: &'a [u8]) -> CargoResult<Elf<'a>> {
+        if data.get(..4) != Some(ELF_MAGIC) {
+            return Err(format_err!("Not an ELF file"));
+        }
+        let is_64_bit = match data.get(4) {
+            Some(&ELF_CLASS_32) => false,
+            Some(&ELF_CLASS_64) => true,
+            _ => return Err(format_err!("Unsupported ELF class")),
+        };
+        if data.get(5) != Some(&ELF_DATA_LITTLE_ENDIAN) {
+            return Err(format_err!("Unsupported big endian ELF file"));
+        }
+
+        let mut elf = Elf {
+            data,
+            is_64_bit,
+            sections: Vec::new(),
+        };
+        let (section_headers_offset, header_fields_offset) = if is_64_bit {
+            (read_u64(data, 0x28)? as usize, 0x3a)
+        } else {
+            (read_u32(data, 0x20)? as usize, 0x2e)
+        };
+        let section_header_len = usize::from(read_u16(data, header_fields_offset)?);
+        let section_count = usize::from(read_u16(data, header_fields_offset + 2)?);
+        let section_names_index = usize::from(read_u16(data, header_fields_offset + 4)?);
+
+        // Section headers, with the offset of their name in the section name string table
+        let mut sections = Vec::new();
+        for index in 0..section_count {
+            let offset = section_headers_offset + index * section_header_len;
+            let name_offset = read_u32(data, offset)? as usize;
+            let section = if is_64_bit {
+                Section {
+                    name: String::new(),
+                    section_type: read_u32(data, offset + 4)?,
+                    offset: read_u64(data, offset + 0x18)? as usize,
+                    size: read_u64(data, offset + 0x20)? as usize,
+                    link: read_u32(data, offset + 0x28)? as usize,
+                }
+            } else {
+                Section {
+                    name: String::new(),
+                    section_type: read_u32(data, offset + 4)?,
+                    offset: read_u32(data, offset + 0x10)? as usize,
+                    size: read_u32(data, offset + 0x14)? as usize,
+                    link: read_u32(data, offset + 0x18)? as usize,
+                }
+            };
+            sections.push((name_offset, section));
+        }
+
+        if let Some((_, names)) = sections.get(section_names_index) {
+            let names = elf.section_data(names)?;
+            for (name_offset, section) in &mut sections {
+                section.name = read_string(names, *name_offset)?;
+            }
+        }
+        elf.sections = sections.into_iter().map(|(_, section)| section).collect();
+        Ok(elf)
+    }
+
+    pub fn section_data(&self, section: &Section) -> CargoResult<&'a [u8]> {
+        self.data
+            .get(section.offset..section.offset + section.size)
+            .ok_or_else(|| format_err!("Section `{}` is out of bounds", section.name))
+    }
+
+    /// Returns the defined function and data symbols. The full symbol table is used when present,
+    /// otherwise the dynamic symbol table.
+    pub fn symbols(&self) -> CargoResult<(SymbolTable, Vec<Symbol>)> {
+        let (symbol_table, section) = match self.find_section_of_type(SECTION_TYPE_SYMTAB) {
+            Some(section) => (SymbolTable::Full, section),
+            None => match self.find_section_of_type(SECTION_TYPE_DYNSYM) {
+                Some(section) => (SymbolTable::Dynamic, section),
+                None => return Ok((SymbolTable::Dynamic, Vec::new())),
+            },
+        };
+        let strings = self.section_data(
+            self.sections
+                .get(section.link)
+                .ok_or_else(|| format_err!("Invalid string table of `{}`", section.name))?,
+        )?;
+        let data = self.section_data(section)?;
+
+        let symbol_len = if self.is_64_bit { 24 } else { 16 };
+        let mut symbols = Vec::new();
+        for offset in (0..data.len() / symbol_len).map(|index| index * symbol_len) {
+            let (info, section_index, address, size) = if self.is_64_bit {
+                (
+                    data[offset + 4],
+                    read_u16(data, offset + 6)?,
+                    read_u64(data, offset + 8)?,
+                    read_u64(data, offset + 16)?,
+                )
+            } else {
+                (
+                    data[offset + 12],
+                    read_u16(data, offset + 14)?,
+                    u64::from(read_u32(data, offset + 4)?),
+                    u64::from(read_u32(data, offset + 8)?),
+                )
+            };
+            let symbol_type = info & 0xf;
+            if (symbol_type != SYMBOL_TYPE_FUNC && symbol_type != SYMBOL_TYPE_OBJECT)
+                || section_index == SECTION_INDEX_UNDEFINED
+                || size == 0
+            {
+                continue;
+            }
+            symbols.push(Symbol {
+                name: read_string(strings, read_u32(data, offset)? as usize)?,
+                address,
+                size,
+            });
+        }
+        Ok((symbol_table, symbols))
+    }
+
+    fn find_section_of_type(&self, section_type: u32) -> Option<&Section> {
+        self.sections
+            .iter()
+            .find(|section| section.section_type == section_type)
+    }
+}
+
+/// Reads a null terminated string
+fn read_string(data: &[u8], offset: usize) -> CargoResult<String> {
+    let bytes = data
+        .get(offset..)
+        .ok_or_else(|| format_err!("Invalid ELF string offset {}", offset))?;
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+}
+
+fn read_u16(data: &[u8], offset: usize) -> CargoResult<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| format_err!("Unexpected end of ELF file"))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> CargoResult<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| format_err!("Unexpected end of ELF file"))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> CargoResult<u64> {
+    Ok(u64::from(read_u32(data, offset)?) | u64::from(read_u32(data, offset + 4)?) << 32)
+}
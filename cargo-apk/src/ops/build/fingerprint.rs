@@ -0,0 +1,239 @@
+//! Fingerprints of the inputs of the packaging steps. They are used to skip the steps whose
+//! inputs didn't change since the previous build.
+
+use cargo::util::CargoResult;
+use failure::format_err;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Fingerprints of the previous and of the current build of a target
+pub struct Fingerprints {
+    path: PathBuf,
+    previous: BTreeMap<String, String>,
+    current: BTreeMap<String, String>,
+}
+
+impl Fingerprints {
+    /// Loads the fingerprints stored by the previous build. Missing or invalid files are treated
+    /// as if nothing had been built.
+    pub fn load(path: PathBuf) -> Fingerprints {
+        let previous = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default();
+        Fingerprints {
+            path,
+            previous,
+            current: BTreeMap::new(),
+        }
+    }
+
+    /// Records the fingerprint of an input. Returns true if it changed since the previous build.
+    pub fn update(&mut self, name: &str, fingerprint: String) -> bool {
+        let changed = self.previous.get(name) != Some(&fingerprint);
+        self.current.insert(name.to_owned(), fingerprint);
+        changed
+    }
+
+    /// Returns true if any input was added, removed or changed since the previous build
+    pub fn is_changed(&self) -> bool {
+        self.previous != self.current
+    }
+
+    /// Removes the stored fingerprints. Must be called before modifying the outputs of a step so
+    /// that outputs of a failed build are not reused.
+    pub fn invalidate(&self) -> CargoResult<()> {
+        if self.path.exists() {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+
+    /// Stores the fingerprints of the current build. Must be called once all steps succeeded.
+    pub fn save(&self) -> CargoResult<()> {
+        let content = toml::to_string(&self.current)?;
+        fs::write(&self.path, content).map_err(|e| {
+            format_err!(
+                "Unable to write fingerprints `{}`. {}",
+                self.path.display(),
+                e
+            )
+        })?;
+        Ok(())
+    }
+}
+
+/// Computes the fingerprint of a set of inputs
+#[derive(Default)]
+pub struct FingerprintHasher {
+    hasher: Sha256,
+}
+
+impl FingerprintHasher {
+    pub fn new() -> FingerprintHasher {
+        FingerprintHasher {
+            hasher: Sha256::new(),
+        }
+    }
+
+    pub fn bytes(&mut self, data: &[u8]) -> &mut FingerprintHasher {
+        self.hasher.update((data.len() as u64).to_le_bytes());
+        self.hasher.update(data);
+        self
+    }
+
+    pub fn path(&mut self, path: &Path) -> &mut FingerprintHasher {
+        self.bytes(path.to_string_lossy().as_bytes())
+    }
+
+    /// Adds the content of a file. Used for files which are rewritten by every build, such as
+    /// generated files and relinked libraries.
+    pub fn file_content(&mut self, path: &Path) -> CargoResult<&mut FingerprintHasher> {
+        let data = fs::read(path)
+            .map_err(|e| format_err!("Unable to read `{}`. {}", path.display(), e))?;
+        Ok(self.bytes(&data))
+    }
+
+    /// Adds the size and modification time of a file
+    pub fn file_metadata(&mut self, path: &Path) -> CargoResult<&mut FingerprintHasher> {
+        let metadata = fs::metadata(path)
+            .map_err(|e| format_err!("Unable to read `{}`. {}", path.display(), e))?;
+        let modified = metadata
+            .modified()?
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        self.bytes(&metadata.len().to_le_bytes());
+        Ok(self.bytes(&modified.to_le_bytes()))
+    }
+
+    /// Adds the relative paths, sizes and modification times of all files in a directory tree
+    pub fn tree(&mut self, root: &Path) -> CargoResult<&mut FingerprintHasher> {
+        let mut directories = vec![root.to_owned()];
+        let mut files = Vec::new();
+        while let Some(directory) = directories.pop() {
+            for entry in fs::read_dir(&directory).map_err(|e| {
+                format_err!("Unable to read directory `{}`. {}", directory.display(), e)
+            })? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    directories.push(path);
+                } else {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+
+        for file in files {
+            self.path(file.strip_prefix(root).unwrap_or(&file));
+            self.file_metadata(&file)?;
+        }
+        Ok(self)
+    }
+
+    pub fn finish(self) -> String {
+        self.hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::build::tempfile::TempDir;
+
+    #[test]
+    fn fingerprints() {
+        let dir = TempDir::new("fingerprints").unwrap();
+        let path = dir.path.join("fingerprints.toml");
+
+        let mut fingerprints = Fingerprints::load(path.clone());
+        assert!(fingerprints.update("a", "1".to_owned()));
+        assert!(fingerprints.update("b", "2".to_owned()));
+        assert!(fingerprints.is_changed());
+        fingerprints.save().unwrap();
+
+        let mut fingerprints = Fingerprints::load(path.clone());
+        assert!(!fingerprints.update("a", "1".to_owned()));
+        assert!(fingerprints.update("b", "3".to_owned()));
+        assert!(fingerprints.is_changed());
+        fingerprints.save().unwrap();
+
+        // Removed input
+        let mut fingerprints = Fingerprints::load(path.clone());
+        assert!(!fingerprints.update("a", "1".to_owned()));
+        assert!(fingerprints.is_changed());
+        fingerprints.save().unwrap();
+
+        let mut fingerprints = Fingerprints::load(path.clone());
+        assert!(!fingerprints.update("a", "1".to_owned()));
+        assert!(!fingerprints.is_changed());
+
+        // A build which failed after invalidating the fingerprints
+        fingerprints.invalidate().unwrap();
+        let mut fingerprints = Fingerprints::load(path);
+        assert!(fingerprints.update("a", "1".to_owned()));
+        assert!(fingerprints.is_changed());
+    }
+
+    #[test]
+    fn relinked_library() {
+        let dir = TempDir::new("fingerprint-relinked").unwrap();
+        let library = dir.path.join("libtest.so");
+        let fingerprint = || {
+            let mut hasher = FingerprintHasher::new();
+            hasher.file_content(&library).unwrap();
+            hasher.finish()
+        };
+
+        fs::write(&library, b"library").unwrap();
+        let mut fingerprints = Fingerprints::load(dir.path.join("fingerprints.toml"));
+        assert!(fingerprints.update("lib/arm64-v8a/libtest.so", fingerprint()));
+        fingerprints.save().unwrap();
+
+        // Relinking rewrites the library with the same content
+        fs::remove_file(&library).unwrap();
+        fs::write(&library, b"library").unwrap();
+        let mut fingerprints = Fingerprints::load(dir.path.join("fingerprints.toml"));
+        assert!(!fingerprints.update("lib/arm64-v8a/libtest.so", fingerprint()));
+        assert!(!fingerprints.is_changed());
+
+        fs::write(&library, b"changed").unwrap();
+        assert!(fingerprints.update("lib/arm64-v8a/libtest.so", fingerprint()));
+    }
+
+    #[test]
+    fn tree() {
+        let dir = TempDir::new("fingerprint-tree").unwrap();
+        let root = dir.path.join("tree");
+        fs::create_dir_all(root.join("d")).unwrap();
+        // Created out of order so that the files aren't listed sorted
+        for name in &["d/c", "b", "a"] {
+            fs::write(root.join(name), name).unwrap();
+        }
+
+        let mut expected = FingerprintHasher::new();
+        for name in &["a", "b", "d/c"] {
+            expected
+                .path(Path::new(name))
+                .file_metadata(&root.join(name))
+                .unwrap();
+        }
+        let mut hasher = FingerprintHasher::new();
+        hasher.tree(&root).unwrap();
+        let fingerprint = hasher.finish();
+        assert_eq!(fingerprint, expected.finish());
+
+        fs::write(root.join("d/e"), "e").unwrap();
+        let mut hasher = FingerprintHasher::new();
+        hasher.tree(&root).unwrap();
+        assert_ne!(hasher.finish(), fingerprint);
+    }
+}
@@ -0,0 +1,138 @@
+//! Generation of the launcher icon resources from the images of the `icon` metadata entry
+
+use super::png::Image;
+use super::util;
+use crate::config::{AndroidIconBackground, AndroidIconImages};
+use cargo::util::CargoResult;
+use failure::format_err;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Densities of the mipmaps and their scale relative to mdpi, in quarters
+const DENSITIES: &[(&str, u32)] = &[
+    ("mdpi", 4),
+    ("hdpi", 6),
+    ("xhdpi", 8),
+    ("xxhdpi", 12),
+    ("xxxhdpi", 16),
+];
+
+/// Size of the legacy icons in dp
+const LEGACY_SIZE: u32 = 48;
+
+/// Size of the adaptive icon layers in dp. Only the 72 dp at the center of the layers are
+/// visible.
+const LAYER_SIZE: u32 = 108;
+const LAYER_VISIBLE_SIZE: u32 = 72;
+
+/// Generates the resources of the launcher icon `@mipmap/ic_launcher` in a resources directory.
+/// Files which didn't change are not rewritten.
+pub fn generate(images: &AndroidIconImages, res_path: &Path) -> CargoResult<()> {
+    // Contents of the files of the resources directory, by path in the directory
+    let mut files = BTreeMap::new();
+
+    let foreground = images
+        .foreground
+        .as_ref()
+        .map(|path| load(path))
+        .transpose()?;
+    let background = match &images.background {
+        AndroidIconBackground::Color(color) => {
+            let size = foreground.as_ref().map_or(1, |foreground| foreground.width);
+            Image::filled(size, size, *color)
+        }
+        AndroidIconBackground::Image(path) => load(path)?,
+    };
+
+    let legacy_image = match (&images.image, &foreground) {
+        (Some(path), _) => load(path)?,
+        // Without a legacy image, the visible part of the layers is used
+        (None, Some(foreground)) => {
+            let background = background.resize(foreground.width, foreground.height);
+            let visible_size = foreground.width * LAYER_VISIBLE_SIZE / LAYER_SIZE;
+            foreground
+                .over(&background)
+                .crop_center(visible_size, visible_size)
+        }
+        (None, None) => unreachable!("The icon requires an image or a foreground"),
+    };
+    add_mipmaps(&mut files, "ic_launcher", &legacy_image, LEGACY_SIZE)?;
+
+    if let Some(foreground) = &foreground {
+        add_mipmaps(&mut files, "ic_launcher_foreground", foreground, LAYER_SIZE)?;
+        let background_drawable = match &images.background {
+            AndroidIconBackground::Color([red, green, blue, alpha]) => {
+                files.insert(
+                    PathBuf::from("values/ic_launcher_background.xml"),
+                    format!(
+                        r#"<?xml version="1.0" encoding="utf-8"?>
+<resources>
+    <color name="ic_launcher_background">#{:02X}{:02X}{:02X}{:02X}</color>
+</resources>
+"#,
+                        alpha, red, green, blue
+                    )
+                    .into_bytes(),
+                );
+                "@color/ic_launcher_background"
+            }
+            AndroidIconBackground::Image(_) => {
+                add_mipmaps(
+                    &mut files,
+                    "ic_launcher_background",
+                    &background,
+                    LAYER_SIZE,
+                )?;
+                "@mipmap/ic_launcher_background"
+            }
+        };
+        files.insert(
+            PathBuf::from("mipmap-anydpi-v26/ic_launcher.xml"),
+            format!(
+                r#"<?xml version="1.0" encoding="utf-8"?>
+<adaptive-icon xmlns:android="http://schemas.android.com/apk/res/android">
+    <background android:drawable="{}" />
+    <foreground android:drawable="@mipmap/ic_launcher_foreground" />
+</adaptive-icon>
+"#,
+                background_drawable
+            )
+            .into_bytes(),
+        );
+    }
+
+    util::write_generated_directory(res_path, &files)
+}
+
+/// Loads a square PNG image
+fn load(path: &Path) -> CargoResult<Image> {
+    let data = fs::read(path)
+        .map_err(|e| format_err!("Unable to read icon `{}`. {}", path.display(), e))?;
+    let image = Image::decode(&data)
+        .map_err(|e| format_err!("Unable to decode icon `{}`. {}", path.display(), e))?;
+    if image.width != image.height {
+        return Err(format_err!(
+            "Icon `{}` is {}x{} pixels but must be square",
+            path.display(),
+            image.width,
+            image.height
+        ));
+    }
+    Ok(image)
+}
+
+/// Adds the image scaled for each density, given its size in dp
+fn add_mipmaps(
+    files: &mut BTreeMap<PathBuf, Vec<u8>>,
+    name: &str,
+    image: &Image,
+    size: u32,
+) -> CargoResult<()> {
+    for (density, scale) in DENSITIES {
+        let path = PathBuf::from(format!("mipmap-{}/{}.png", density, name));
+        let pixels = size * scale / 4;
+        files.insert(path, image.resize(pixels, pixels).encode()?);
+    }
+    Ok(())
+}
@@ -0,0 +1,771 @@
+//! Typed model of the manifest, converted to an XML element tree and serialized with escaping
+
+use super::axml::{Attribute, Element, ANDROID_NAMESPACE_URI};
+use cargo::util::CargoResult;
+use failure::format_err;
+use std::collections::{BTreeMap, BTreeSet};
+
+pub struct Manifest {
+    pub package: String,
+    pub version_code: i32,
+    pub version_name: String,
+    pub uses_sdk: UsesSdk,
+    pub uses_features: Vec<UsesFeature>,
+    pub uses_permissions: Vec<UsesPermission>,
+    pub application: Application,
+}
+
+pub struct UsesSdk {
+    pub min_sdk_version: u32,
+    pub target_sdk_version: u32,
+}
+
+pub struct UsesFeature {
+    pub name: Option<String>,
+    /// OpenGL ES version, with the major version in the upper 16 bits
+    pub gl_es_version: Option<u32>,
+    pub required: bool,
+    pub version: Option<String>,
+}
+
+pub struct UsesPermission {
+    pub name: String,
+    pub max_sdk_version: Option<u32>,
+}
+
+pub struct Application {
+    pub label: String,
+    pub icon: Option<String>,
+    pub theme: Option<String>,
+    pub has_code: bool,
+    pub debuggable: bool,
+    pub extract_native_libs: Option<bool>,
+    /// Attributes set by the metadata. They replace the attributes of the model with the same name.
+    pub extra_attributes: Vec<Attribute>,
+    pub meta_data: Vec<MetaData>,
+    pub activities: Vec<Activity>,
+}
+
+pub struct Activity {
+    pub name: String,
+    pub label: String,
+    pub config_changes: Option<String>,
+    /// Attributes set by the metadata. They replace the attributes of the model with the same name.
+    pub extra_attributes: Vec<Attribute>,
+    pub meta_data: Vec<MetaData>,
+    pub intent_filters: Vec<IntentFilter>,
+}
+
+#[derive(Default)]
+pub struct IntentFilter {
+    pub actions: Vec<String>,
+    pub categories: Vec<String>,
+    pub data: Vec<IntentFilterData>,
+    pub auto_verify: bool,
+}
+
+/// `<data>` element of an intent filter
+#[derive(Default)]
+pub struct IntentFilterData {
+    pub scheme: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<String>,
+    pub path: Option<String>,
+    pub path_prefix: Option<String>,
+    pub path_pattern: Option<String>,
+    pub mime_type: Option<String>,
+}
+
+pub struct MetaData {
+    pub name: String,
+    pub value: MetaDataValue,
+}
+
+pub enum MetaDataValue {
+    /// Value of the `android:value` attribute
+    Value(String),
+    /// Reference of the `android:resource` attribute
+    Resource(String),
+}
+
+impl Manifest {
+    pub fn to_element(&self) -> Element {
+        let mut manifest = element("manifest");
+        set_attribute(&mut manifest, None, "package", &self.package);
+        set_android_attribute(&mut manifest, "versionCode", &self.version_code);
+        set_android_attribute(&mut manifest, "versionName", &self.version_name);
+
+        let mut uses_sdk = element("uses-sdk");
+        set_android_attribute(
+            &mut uses_sdk,
+            "minSdkVersion",
+            &self.uses_sdk.min_sdk_version,
+        );
+        set_android_attribute(
+            &mut uses_sdk,
+            "targetSdkVersion",
+            &self.uses_sdk.target_sdk_version,
+        );
+        manifest.children.push(uses_sdk);
+
+        for feature in &self.uses_features {
+            let mut uses_feature = element("uses-feature");
+            if let Some(name) = &feature.name {
+                set_android_attribute(&mut uses_feature, "name", name);
+            }
+            if let Some(gl_es_version) = feature.gl_es_version {
+                set_android_attribute(
+                    &mut uses_feature,
+                    "glEsVersion",
+                    &format!("{:#010x}", gl_es_version),
+                );
+            }
+            set_android_attribute(&mut uses_feature, "required", &feature.required);
+            if let Some(version) = &feature.version {
+                set_android_attribute(&mut uses_feature, "version", version);
+            }
+            manifest.children.push(uses_feature);
+        }
+
+        for permission in &self.uses_permissions {
+            let mut uses_permission = element("uses-permission");
+            set_android_attribute(&mut uses_permission, "name", &permission.name);
+            if let Some(max_sdk_version) = permission.max_sdk_version {
+                set_android_attribute(&mut uses_permission, "maxSdkVersion", &max_sdk_version);
+            }
+            manifest.children.push(uses_permission);
+        }
+
+        manifest.children.push(self.application.to_element());
+        manifest
+    }
+}
+
+impl Application {
+    fn to_element(&self) -> Element {
+        let mut application = element("application");
+        set_android_attribute(&mut application, "label", &self.label);
+        if let Some(icon) = &self.icon {
+            set_android_attribute(&mut application, "icon", icon);
+        }
+        if let Some(theme) = &self.theme {
+            set_android_attribute(&mut application, "theme", theme);
+        }
+        set_android_attribute(&mut application, "hasCode", &self.has_code);
+        set_android_attribute(&mut application, "debuggable", &self.debuggable);
+        if let Some(extract_native_libs) = self.extract_native_libs {
+            set_android_attribute(&mut application, "extractNativeLibs", &extract_native_libs);
+        }
+        for attribute in &self.extra_attributes {
+            set_attribute(
+                &mut application,
+                attribute.namespace.as_deref(),
+                &attribute.name,
+                &attribute.value,
+            );
+        }
+
+        for meta_data in &self.meta_data {
+            application.children.push(meta_data.to_element());
+        }
+        for activity in &self.activities {
+            application.children.push(activity.to_element());
+        }
+        application
+    }
+}
+
+impl Activity {
+    fn to_element(&self) -> Element {
+        let mut activity = element("activity");
+        set_android_attribute(&mut activity, "name", &self.name);
+        set_android_attribute(&mut activity, "label", &self.label);
+        if let Some(config_changes) = &self.config_changes {
+            set_android_attribute(&mut activity, "configChanges", config_changes);
+        }
+        for attribute in &self.extra_attributes {
+            set_attribute(
+                &mut activity,
+                attribute.namespace.as_deref(),
+                &attribute.name,
+                &attribute.value,
+            );
+        }
+
+        for meta_data in &self.meta_data {
+            activity.children.push(meta_data.to_element());
+        }
+        for intent_filter in &self.intent_filters {
+            activity.children.push(intent_filter.to_element());
+        }
+        activity
+    }
+}
+
+impl IntentFilter {
+    fn to_element(&self) -> Element {
+        let mut intent_filter = element("intent-filter");
+        if self.auto_verify {
+            set_android_attribute(&mut intent_filter, "autoVerify", &true);
+        }
+        for action in &self.actions {
+            let mut element = element("action");
+            set_android_attribute(&mut element, "name", action);
+            intent_filter.children.push(element);
+        }
+        for category in &self.categories {
+            let mut element = element("category");
+            set_android_attribute(&mut element, "name", category);
+            intent_filter.children.push(element);
+        }
+        for data in &self.data {
+            let mut element = element("data");
+            let attributes = [
+                ("scheme", &data.scheme),
+                ("host", &data.host),
+                ("port", &data.port),
+                ("path", &data.path),
+                ("pathPrefix", &data.path_prefix),
+                ("pathPattern", &data.path_pattern),
+                ("mimeType", &data.mime_type),
+            ];
+            for (name, value) in attributes.iter() {
+                if let Some(value) = value {
+                    set_android_attribute(&mut element, name, value);
+                }
+            }
+            intent_filter.children.push(element);
+        }
+        intent_filter
+    }
+}
+
+impl MetaData {
+    fn to_element(&self) -> Element {
+        let mut meta_data = element("meta-data");
+        set_android_attribute(&mut meta_data, "name", &self.name);
+        match &self.value {
+            MetaDataValue::Value(value) => set_android_attribute(&mut meta_data, "value", value),
+            MetaDataValue::Resource(resource) => {
+                set_android_attribute(&mut meta_data, "resource", resource)
+            }
+        }
+        meta_data
+    }
+}
+
+/// Parses the attributes of an `application_attributes` or `activity_attributes` table. Names
+/// are either unprefixed or prefixed with `android:`.
+pub fn parse_attributes(attributes: &BTreeMap<String, String>) -> CargoResult<Vec<Attribute>> {
+    attributes
+        .iter()
+        .map(|(qualified_name, value)| {
+            let (namespace, name) = match qualified_name.find(':') {
+                Some(index) if &qualified_name[..index] == "android" => (
+                    Some(ANDROID_NAMESPACE_URI.to_owned()),
+                    &qualified_name[index + 1..],
+                ),
+                Some(index) => {
+                    return Err(format_err!(
+                        "Unsupported namespace prefix `{}` in attribute `{}`",
+                        &qualified_name[..index],
+                        qualified_name
+                    ))
+                }
+                None => (None, qualified_name.as_str()),
+            };
+            let mut chars = name.chars();
+            let valid_name = match chars.next() {
+                Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+                }
+                _ => false,
+            };
+            if !valid_name {
+                return Err(format_err!("Invalid attribute name `{}`", qualified_name));
+            }
+            Ok(Attribute {
+                namespace,
+                name: name.to_owned(),
+                value: value.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Merges a generated manifest into a manifest template, following the rules of the manifest
+/// merger of the Android build tools. Elements are identified by their name and `android:name`
+/// attribute, and intent filters by their contents. The attributes of the generated elements are
+/// added to the elements of the template, and the generated elements missing from the template
+/// are inserted before its `<application>`, or appended within it. An attribute with different
+/// values in both manifests is a conflict, unless the template lists it in `tools:replace`.
+///
+/// The template can also drop generated attributes with `tools:remove`, and set `tools:node` to
+/// `merge-only-attributes`, `replace`, `remove` or `removeAll`. The `tools` attributes are removed
+/// from the merged manifest.
+pub fn merge(template: &mut Element, generated: Element) -> CargoResult<()> {
+    if template.name != generated.name {
+        return Err(format_err!(
+            "The root element of the manifest template is `{}` instead of `{}`",
+            template.name,
+            generated.name
+        ));
+    }
+    let path = generated.name.clone();
+    merge_element(template, generated, &path)?;
+    remove_tools_markup(template);
+    Ok(())
+}
+
+fn merge_element(template: &mut Element, generated: Element, path: &str) -> CargoResult<()> {
+    match tools_attribute(template, "node") {
+        None | Some("merge") | Some("merge-only-attributes") => {}
+        // The element of the template is kept as is, or removed once merged
+        Some("replace") | Some("remove") | Some("removeAll") => return Ok(()),
+        Some(node) => {
+            return Err(format_err!(
+                "Unsupported value `{}` of `tools:node` in `{}`",
+                node,
+                path
+            ))
+        }
+    }
+
+    let replaced = tools_attribute_list(template, "replace");
+    let removed = tools_attribute_list(template, "remove");
+    for attribute in generated.attributes {
+        let name = qualified_name(&attribute);
+        if removed.contains(&name) {
+            continue;
+        }
+        match template.attributes.iter().find(|existing| {
+            existing.namespace == attribute.namespace && existing.name == attribute.name
+        }) {
+            Some(existing) if existing.value != attribute.value && !replaced.contains(&name) => {
+                return Err(format_err!(
+                    "Attribute `{}` of `{}` is `{}` in the manifest template but `{}` in the generated manifest. Add it to `tools:replace` to keep the value of the template.",
+                    name,
+                    path,
+                    existing.value,
+                    attribute.value
+                ))
+            }
+            Some(_) => {}
+            None => template.attributes.push(attribute),
+        }
+    }
+
+    if tools_attribute(template, "node") == Some("merge-only-attributes") {
+        return Ok(());
+    }
+    for child in generated.children {
+        let child_path = match android_attribute(&child, "name") {
+            Some(name) => format!("{}/{}[{}]", path, child.name, name),
+            None => format!("{}/{}", path, child.name),
+        };
+        let removes_all = template.children.iter().any(|existing| {
+            existing.name == child.name && tools_attribute(existing, "node") == Some("removeAll")
+        });
+        match template
+            .children
+            .iter_mut()
+            .find(|existing| is_same_element(existing, &child))
+        {
+            Some(existing) => merge_element(existing, child, &child_path)?,
+            None if removes_all => {}
+            None => {
+                // Android expects the elements such as `<uses-sdk>` before `<application>`
+                match template
+                    .children
+                    .iter()
+                    .position(|existing| existing.name == "application")
+                {
+                    Some(index) => template.children.insert(index, child),
+                    None => template.children.push(child),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes the elements marked for removal and the attributes of the `tools` namespace
+fn remove_tools_markup(element: &mut Element) {
+    let removed_names: BTreeSet<String> = element
+        .children
+        .iter()
+        .filter(|child| tools_attribute(child, "node") == Some("removeAll"))
+        .map(|child| child.name.clone())
+        .collect();
+    element.children.retain(|child| {
+        !removed_names.contains(&child.name) && tools_attribute(child, "node") != Some("remove")
+    });
+    element
+        .attributes
+        .retain(|attribute| attribute.namespace.as_deref() != Some(TOOLS_NAMESPACE_URI));
+    for child in &mut element.children {
+        remove_tools_markup(child);
+    }
+}
+
+/// Returns whether an element of the template and a generated element are the same element
+fn is_same_element(template: &Element, generated: &Element) -> bool {
+    if template.name != generated.name {
+        return false;
+    }
+    match generated.name.as_str() {
+        "application" | "uses-sdk" => true,
+        "intent-filter" => template == generated,
+        // There is a single OpenGL ES version, so that different versions conflict
+        "uses-feature" if android_attribute(generated, "glEsVersion").is_some() => {
+            android_attribute(template, "glEsVersion").is_some()
+        }
+        _ => android_attribute(template, "name") == android_attribute(generated, "name"),
+    }
+}
+
+fn android_attribute<'a>(element: &'a Element, name: &str) -> Option<&'a str> {
+    element
+        .attributes
+        .iter()
+        .find(|attribute| {
+            attribute.namespace.as_deref() == Some(ANDROID_NAMESPACE_URI) && attribute.name == name
+        })
+        .map(|attribute| attribute.value.as_str())
+}
+
+fn tools_attribute<'a>(element: &'a Element, name: &str) -> Option<&'a str> {
+    element
+        .attributes
+        .iter()
+        .find(|attribute| {
+            attribute.namespace.as_deref() == Some(TOOLS_NAMESPACE_URI) && attribute.name == name
+        })
+        .map(|attribute| attribute.value.as_str())
+}
+
+/// Returns the qualified attribute names listed by a `tools` attribute, eg. `tools:replace`
+fn tools_attribute_list(element: &Element, name: &str) -> Vec<String> {
+    tools_attribute(element, name)
+        .map(|value| {
+            value
+                .split(',')
+                .map(|name| name.trim().to_owned())
+                .filter(|name| !name.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn qualified_name(attribute: &Attribute) -> String {
+    match attribute.namespace.as_deref() {
+        Some(ANDROID_NAMESPACE_URI) => format!("android:{}", attribute.name),
+        _ => attribute.name.clone(),
+    }
+}
+
+/// Returns a manifest as an XML document. The namespaces are declared by the root element.
+pub fn to_xml(manifest: &Element) -> String {
+    let mut namespaces = BTreeSet::new();
+    collect_namespaces(manifest, &mut namespaces);
+    namespaces.remove(ANDROID_NAMESPACE_URI);
+    let mut prefixes = BTreeMap::new();
+    prefixes.insert(ANDROID_NAMESPACE_URI.to_owned(), "android".to_owned());
+    for (index, namespace) in namespaces.into_iter().enumerate() {
+        let prefix = if namespace == TOOLS_NAMESPACE_URI {
+            "tools".to_owned()
+        } else {
+            format!("ns{}", index)
+        };
+        prefixes.insert(namespace, prefix);
+    }
+
+    let mut xml = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n".to_owned();
+    write_element(&mut xml, manifest, &prefixes, 0);
+    xml
+}
+
+/// URI of the namespace of the attributes read by the manifest merger of the Android build tools
+const TOOLS_NAMESPACE_URI: &str = "http://schemas.android.com/tools";
+
+fn collect_namespaces(element: &Element, namespaces: &mut BTreeSet<String>) {
+    for attribute in &element.attributes {
+        if let Some(namespace) = &attribute.namespace {
+            namespaces.insert(namespace.clone());
+        }
+    }
+    for child in &element.children {
+        collect_namespaces(child, namespaces);
+    }
+}
+
+fn element(name: &str) -> Element {
+    Element {
+        name: name.to_owned(),
+        attributes: Vec::new(),
+        children: Vec::new(),
+    }
+}
+
+fn set_android_attribute(element: &mut Element, name: &str, value: &dyn ToString) {
+    set_attribute(
+        element,
+        Some(ANDROID_NAMESPACE_URI),
+        name,
+        &value.to_string(),
+    );
+}
+
+/// Sets an attribute, replacing the attribute with the same name if there is one
+fn set_attribute(element: &mut Element, namespace: Option<&str>, name: &str, value: &str) {
+    let attribute = Attribute {
+        namespace: namespace.map(str::to_owned),
+        name: name.to_owned(),
+        value: value.to_owned(),
+    };
+    match element
+        .attributes
+        .iter_mut()
+        .find(|existing| existing.namespace.as_deref() == namespace && existing.name == name)
+    {
+        Some(existing) => *existing = attribute,
+        None => element.attributes.push(attribute),
+    }
+}
+
+/// Writes an element and its children, given the prefixes of the namespaces by URI
+fn write_element(
+    xml: &mut String,
+    element: &Element,
+    prefixes: &BTreeMap<String, String>,
+    depth: usize,
+) {
+    let indentation = "    ".repeat(depth);
+    xml.push_str(&indentation);
+    xml.push('<');
+    xml.push_str(&element.name);
+    if depth == 0 {
+        for (namespace, prefix) in prefixes {
+            xml.push_str(&format!(" xmlns:{}=\"{}\"", prefix, escape(namespace)));
+        }
+    }
+    // Elements with several attributes have one attribute per line
+    let separator = if depth > 0 && element.attributes.len() == 1 {
+        " ".to_owned()
+    } else {
+        format!("\n{}        ", indentation)
+    };
+    for attribute in &element.attributes {
+        xml.push_str(&separator);
+        if let Some(namespace) = &attribute.namespace {
+            xml.push_str(&prefixes[namespace]);
+            xml.push(':');
+        }
+        xml.push_str(&attribute.name);
+        xml.push_str("=\"");
+        xml.push_str(&escape(&attribute.value));
+        xml.push('"');
+    }
+    if element.children.is_empty() {
+        xml.push_str(" />\n");
+        return;
+    }
+    xml.push_str(">\n");
+    for child in &element.children {
+        write_element(xml, child, prefixes, depth + 1);
+    }
+    xml.push_str(&format!("{}</{}>\n", indentation, element.name));
+}
+
+/// Escapes an attribute value. Whitespace characters other than spaces are escaped so that they
+/// are not normalized to spaces by XML parsers.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\r' => escaped.push_str("&#13;"),
+            '\t' => escaped.push_str("&#9;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::axml;
+    use super::*;
+
+    #[test]
+    fn escaped_manifest_round_trip() {
+        let mut attributes = BTreeMap::new();
+        attributes.insert("android:label".to_owned(), "Override".to_owned());
+        attributes.insert("android:debuggable".to_owned(), "true".to_owned());
+        let manifest = Manifest {
+            package: "rust.app".to_owned(),
+            version_code: 1,
+            version_name: "1.0".to_owned(),
+            uses_sdk: UsesSdk {
+                min_sdk_version: 18,
+                target_sdk_version: 29,
+            },
+            uses_features: vec![UsesFeature {
+                name: None,
+                gl_es_version: Some(0x0003_0001),
+                required: true,
+                version: None,
+            }],
+            uses_permissions: Vec::new(),
+            application: Application {
+                label: "Tom & \"Jerry\" <3".to_owned(),
+                icon: None,
+                theme: None,
+                has_code: false,
+                debuggable: false,
+                extract_native_libs: None,
+                extra_attributes: parse_attributes(&attributes).unwrap(),
+                meta_data: Vec::new(),
+                activities: vec![Activity {
+                    name: "android.app.NativeActivity".to_owned(),
+                    label: "Tom & \"Jerry\" <3".to_owned(),
+                    config_changes: None,
+                    extra_attributes: Vec::new(),
+                    meta_data: vec![MetaData {
+                        name: "android.app.lib_name".to_owned(),
+                        value: MetaDataValue::Value("app".to_owned()),
+                    }],
+                    intent_filters: vec![IntentFilter {
+                        actions: vec!["android.intent.action.MAIN".to_owned()],
+                        ..IntentFilter::default()
+                    }],
+                }],
+            },
+        };
+
+        let element = axml::parse(&to_xml(&manifest.to_element())).unwrap();
+        assert_eq!(element, manifest.to_element());
+        let application = &element.children[2];
+        assert_eq!(application.attributes[0].value, "Override");
+        assert_eq!(application.attributes[2].name, "debuggable");
+        assert_eq!(application.attributes[2].value, "true");
+        assert_eq!(
+            application.children[0].attributes[1].value,
+            "Tom & \"Jerry\" <3"
+        );
+        assert_eq!(element.children[1].attributes[0].value, "0x00030001");
+
+        attributes.insert("tools:ignore".to_owned(), "x".to_owned());
+        assert!(parse_attributes(&attributes).is_err());
+    }
+
+    #[test]
+    fn merge_template() {
+        let mut template = axml::parse(
+            r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+                xmlns:tools="http://schemas.android.com/tools" package="rust.app">
+                <application android:allowBackup="false" tools:ignore="AllowBackup">
+                    <receiver android:name=".Receiver" />
+                    <activity android:name="android.app.NativeActivity">
+                        <intent-filter><action android:name="VIEW" /></intent-filter>
+                    </activity>
+                </application>
+            </manifest>"#,
+        )
+        .unwrap();
+        let generated = axml::parse(
+            r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+                package="rust.app" android:versionCode="1">
+                <uses-sdk android:minSdkVersion="18" />
+                <application android:label="App">
+                    <activity android:name="android.app.NativeActivity" android:label="App">
+                        <intent-filter><action android:name="MAIN" /></intent-filter>
+                    </activity>
+                </application>
+            </manifest>"#,
+        )
+        .unwrap();
+        merge(&mut template, generated.clone()).unwrap();
+
+        assert_eq!(template.attributes.len(), 2);
+        assert_eq!(template.children[0].name, "uses-sdk");
+        let application = &template.children[1];
+        // `tools:ignore` is removed
+        assert_eq!(application.attributes.len(), 2);
+        assert_eq!(application.children.len(), 2);
+        assert_eq!(application.children[1].attributes[1].value, "App");
+        assert_eq!(application.children[1].children.len(), 2);
+        assert_eq!(axml::parse(&to_xml(&template)).unwrap(), template);
+
+        let mut conflicting = axml::parse(
+            r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+                package="rust.other" />"#,
+        )
+        .unwrap();
+        assert!(merge(&mut conflicting, generated).is_err());
+    }
+
+    #[test]
+    fn merge_tools_attributes() {
+        let generated = axml::parse(
+            r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+                package="rust.app">
+                <uses-permission android:name="INTERNET" />
+                <uses-permission android:name="CAMERA" />
+                <application android:label="App" android:debuggable="true">
+                    <activity android:name="android.app.NativeActivity" android:label="App" />
+                </application>
+            </manifest>"#,
+        )
+        .unwrap();
+
+        let mut template = axml::parse(
+            r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+                xmlns:tools="http://schemas.android.com/tools" package="rust.app">
+                <uses-permission android:name="CAMERA" tools:node="remove" />
+                <application android:debuggable="false" tools:replace="android:debuggable"
+                    tools:remove="android:label">
+                    <activity android:name="android.app.NativeActivity"
+                        tools:node="merge-only-attributes" />
+                </application>
+            </manifest>"#,
+        )
+        .unwrap();
+        merge(&mut template, generated.clone()).unwrap();
+        let expected = axml::parse(
+            r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+                package="rust.app">
+                <uses-permission android:name="INTERNET" />
+                <application android:debuggable="false">
+                    <activity android:name="android.app.NativeActivity" android:label="App" />
+                </application>
+            </manifest>"#,
+        )
+        .unwrap();
+        assert_eq!(template, expected);
+
+        let mut template = axml::parse(
+            r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+                xmlns:tools="http://schemas.android.com/tools" package="rust.app">
+                <uses-permission android:name="ACCESS_WIFI_STATE" tools:node="removeAll" />
+                <application android:debuggable="false" />
+            </manifest>"#,
+        )
+        .unwrap();
+        assert!(merge(&mut template.clone(), generated.clone()).is_err());
+        template.children[1].attributes.push(Attribute {
+            namespace: Some(TOOLS_NAMESPACE_URI.to_owned()),
+            name: "node".to_owned(),
+            value: "replace".to_owned(),
+        });
+        merge(&mut template, generated).unwrap();
+        assert_eq!(template.children.len(), 1);
+        assert_eq!(template.children[0].name, "application");
+        assert_eq!(template.children[0].attributes.len(), 1);
+        assert!(template.children[0].children.is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+//! Copying of the final artifacts to the directory passed with `--out-dir`
+
+use super::BuildResult;
+use crate::config::AndroidConfig;
+use cargo::util::CargoResult;
+use failure::format_err;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// ABI placeholder value of artifacts containing all ABIs
+const UNIVERSAL_ABI: &str = "universal";
+
+/// Copies the APKs, split APKs, bundles and debug symbols of the build to the output directory.
+/// File names are produced by the `out_file_name` template.
+pub fn copy_artifacts(
+    config: &AndroidConfig,
+    build_result: &BuildResult,
+    out_dir: &Path,
+) -> CargoResult<()> {
+    let mut artifacts = Vec::new();
+    for (target, path) in &build_result.target_to_apk_map {
+        artifacts.push((target, UNIVERSAL_ABI, 0, ".apk", path));
+    }
+    for (target, split_apks) in &build_result.target_to_split_apks_map {
+        for (build_target, path) in split_apks {
+            artifacts.push((
+                target,
+                build_target.android_abi(),
+                build_target.version_code_offset(),
+                ".apk",
+                path,
+            ));
+        }
+    }
+    for (target, path) in &build_result.target_to_bundle_map {
+        artifacts.push((target, UNIVERSAL_ABI, 0, ".aab", path));
+    }
+    for (target, path) in &build_result.target_to_debug_symbols_map {
+        artifacts.push((target, UNIVERSAL_ABI, 0, "-native-debug-symbols.zip", path));
+    }
+
+    // Source path of each destination file name, to detect templates producing the same name
+    let mut copies = BTreeMap::new();
+    for (target, abi, version_code_offset, suffix, path) in artifacts {
+        let target_config = config.resolve(target.clone())?;
+        let version_code = (target_config.version_code + version_code_offset).to_string();
+        let file_name = format!(
+            "{}{}",
+            render_template(
+                &config.out_file_name,
+                &[
+                    ("target", &target.1),
+                    ("package_name", &target_config.package_name),
+                    ("version_name", &target_config.version_name),
+                    ("version_code", &version_code),
+                    ("abi", abi),
+                    ("profile", if config.release { "release" } else { "debug" }),
+                    ("flavor", config.flavor.as_deref().unwrap_or("")),
+                ],
+            )?,
+            suffix
+        );
+        if let Some(previous_path) = copies.insert(file_name.clone(), path) {
+            return Err(format_err!(
+                "`{}` and `{}` would both be copied to `{}`. Add placeholders to out_file_name to \
+                 distinguish them.",
+                previous_path.display(),
+                path.display(),
+                file_name
+            ));
+        }
+    }
+
+    fs::create_dir_all(out_dir)
+        .map_err(|e| format_err!("Unable to create `{}`. {}", out_dir.display(), e))?;
+    for (file_name, path) in copies {
+        let destination = out_dir.join(file_name);
+        fs::copy(path, &destination).map_err(|e| {
+            format_err!(
+                "Unable to copy `{}` to `{}`. {}",
+                path.display(),
+                destination.display(),
+                e
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Replaces the `{name}` placeholders of a template by their values
+fn render_template(template: &str, values: &[(&str, &str)]) -> CargoResult<String> {
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| {
+            format_err!("Unterminated placeholder in out_file_name `{}`", template)
+        })?;
+        let name = &rest[start + 1..start + end];
+        let value = values
+            .iter()
+            .find(|(placeholder, _)| *placeholder == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| {
+                format_err!(
+                    "Unknown placeholder `{{{}}}` in out_file_name. Supported placeholders are {}.",
+                    name,
+                    values
+                        .iter()
+                        .map(|(placeholder, _)| format!("`{{{}}}`", placeholder))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?;
+        result.push_str(value);
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cargo::core::manifest::TargetKind;
+    use std::path::PathBuf;
+
+    const VALUES: &[(&str, &str)] = &[("target", "app"), ("abi", "x86"), ("flavor", "")];
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(
+            render_template("{target}-{abi}", VALUES).unwrap(),
+            "app-x86"
+        );
+        assert_eq!(render_template("app", VALUES).unwrap(), "app");
+        assert_eq!(
+            render_template("{target}{flavor}-}", VALUES).unwrap(),
+            "app-}"
+        );
+        assert_eq!(render_template("", VALUES).unwrap(), "");
+    }
+
+    #[test]
+    fn unknown_placeholder() {
+        let error = render_template("{target}-{version}", VALUES)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("`{version}`"), "{}", error);
+        assert!(
+            error.contains("`{target}`, `{abi}`, `{flavor}`"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn unterminated_placeholder() {
+        let error = render_template("{target}-{abi", VALUES)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Unterminated placeholder"), "{}", error);
+        assert!(error.contains("`{target}-{abi`"), "{}", error);
+    }
+
+    #[test]
+    fn duplicate_destination() {
+        let mut config = crate::config::tests::config(Path::new("."), "");
+        config.out_file_name = "{profile}".to_owned();
+        let build_result = BuildResult {
+            target_to_apk_map: vec![
+                (
+                    (TargetKind::Bin, "app".to_owned()),
+                    PathBuf::from("app.apk"),
+                ),
+                (
+                    (TargetKind::ExampleBin, "demo".to_owned()),
+                    PathBuf::from("demo.apk"),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            target_to_split_apks_map: BTreeMap::new(),
+            target_to_bundle_map: BTreeMap::new(),
+            target_to_debug_symbols_map: BTreeMap::new(),
+        };
+
+        let out_dir = Path::new("never-created");
+        let error = copy_artifacts(&config, &build_result, out_dir)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("`app.apk` and `demo.apk` would both be copied to `debug.apk`"),
+            "{}",
+            error
+        );
+        assert!(!out_dir.exists());
+    }
+}
@@ -0,0 +1,437 @@
+//! Decoding and encoding of PNG images, and the image operations used to generate launcher icons.
+//! Images are decoded to 8-bit RGBA. Interlaced images are not supported.
+
+use cargo::util::CargoResult;
+use failure::format_err;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use std::io::{Read, Write};
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+const COLOR_TYPE_GRAY: u8 = 0;
+const COLOR_TYPE_RGB: u8 = 2;
+const COLOR_TYPE_PALETTE: u8 = 3;
+const COLOR_TYPE_GRAY_ALPHA: u8 = 4;
+const COLOR_TYPE_RGBA: u8 = 6;
+
+/// 8-bit RGBA image with non-premultiplied alpha
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// Rows of RGBA pixels, from top to bottom
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Creates an image filled with a color
+    pub fn filled(width: u32, height: u32, color: [u8; 4]) -> Image {
+        let pixel_count = width as usize * height as usize;
+        Image {
+            width,
+            height,
+            pixels: color
+                .iter()
+                .cycle()
+                .take(pixel_count * 4)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn decode(data: &[u8]) -> CargoResult<Image> {
+        if !data.starts_with(SIGNATURE) {
+            return Err(format_err!("Not a PNG image"));
+        }
+
+        let mut header = None;
+        let mut palette: &[u8] = &[];
+        let mut transparency: &[u8] = &[];
+        let mut compressed = Vec::new();
+        let mut rest = &data[SIGNATURE.len()..];
+        loop {
+            if rest.len() < 12 {
+                return Err(format_err!("Truncated PNG image"));
+            }
+            let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            if rest.len() < 12 + length {
+                return Err(format_err!("Truncated PNG image"));
+            }
+            let chunk_type = &rest[4..8];
+            let chunk = &rest[8..8 + length];
+            match chunk_type {
+                b"IHDR" if chunk.len() == 13 => header = Some(chunk),
+                b"PLTE" => palette = chunk,
+                b"tRNS" => transparency = chunk,
+                b"IDAT" => compressed.extend_from_slice(chunk),
+                b"IEND" => break,
+                _ => {}
+            }
+            // Skip the chunk and its CRC
+            rest = &rest[12 + length..];
+        }
+
+        let header = header.ok_or_else(|| format_err!("PNG image without header"))?;
+        let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let bit_depth = header[8];
+        let color_type = header[9];
+        if header[12] != 0 {
+            return Err(format_err!("Interlaced PNG images are not supported"));
+        }
+        let channels = match (color_type, bit_depth) {
+            (COLOR_TYPE_GRAY, 1) | (COLOR_TYPE_GRAY, 2) | (COLOR_TYPE_GRAY, 4) => 1,
+            (COLOR_TYPE_PALETTE, 1) | (COLOR_TYPE_PALETTE, 2) | (COLOR_TYPE_PALETTE, 4) => 1,
+            (COLOR_TYPE_GRAY, 8) | (COLOR_TYPE_GRAY, 16) | (COLOR_TYPE_PALETTE, 8) => 1,
+            (COLOR_TYPE_GRAY_ALPHA, 8) | (COLOR_TYPE_GRAY_ALPHA, 16) => 2,
+            (COLOR_TYPE_RGB, 8) | (COLOR_TYPE_RGB, 16) => 3,
+            (COLOR_TYPE_RGBA, 8) | (COLOR_TYPE_RGBA, 16) => 4,
+            _ => {
+                return Err(format_err!(
+                    "Unsupported PNG color type {} with bit depth {}",
+                    color_type,
+                    bit_depth
+                ))
+            }
+        };
+
+        // Bytes per complete pixel, used by the filters, and bytes per row
+        let bits_per_pixel = channels * bit_depth as usize;
+        let filter_distance = (bits_per_pixel / 8).max(1);
+        let row_bits = width as usize * bits_per_pixel;
+        let row_length = row_bits / 8 + ((row_bits & 7) != 0) as usize;
+
+        let mut filtered = Vec::new();
+        ZlibDecoder::new(&compressed[..])
+            .read_to_end(&mut filtered)
+            .map_err(|e| format_err!("Invalid PNG image data. {}", e))?;
+        if filtered.len() < (row_length + 1) * height as usize {
+            return Err(format_err!("Truncated PNG image data"));
+        }
+
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        let mut previous_row = vec![0; row_length];
+        let mut row = vec![0; row_length];
+        for filtered_row in filtered.chunks(row_length + 1).take(height as usize) {
+            unfilter(
+                filtered_row[0],
+                &filtered_row[1..],
+                &previous_row,
+                filter_distance,
+                &mut row,
+            )?;
+            for x in 0..width as usize {
+                let sample = |channel: usize| -> u16 {
+                    let index = x * channels + channel;
+                    match bit_depth {
+                        16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+                        8 => u16::from(row[index]),
+                        _ => {
+                            let bit = index * bit_depth as usize;
+                            let shift = 8 - bit_depth as usize - bit % 8;
+                            u16::from(row[bit / 8] >> shift) & ((1 << bit_depth) - 1)
+                        }
+                    }
+                };
+                // Scales a sample to 8 bits
+                let scale = |value: u16| -> u8 {
+                    match bit_depth {
+                        16 => (value >> 8) as u8,
+                        _ => (u32::from(value) * 255 / ((1 << bit_depth) - 1)) as u8,
+                    }
+                };
+                // Sample of the color key of gray and RGB images
+                let key = |channel: usize| {
+                    transparency
+                        .get(channel * 2..channel * 2 + 2)
+                        .map(|key| u16::from_be_bytes([key[0], key[1]]))
+                };
+                let pixel = match color_type {
+                    COLOR_TYPE_GRAY => {
+                        let gray = sample(0);
+                        let alpha = if key(0) == Some(gray) { 0 } else { 255 };
+                        [scale(gray), scale(gray), scale(gray), alpha]
+                    }
+                    COLOR_TYPE_GRAY_ALPHA => {
+                        let gray = scale(sample(0));
+                        [gray, gray, gray, scale(sample(1))]
+                    }
+                    COLOR_TYPE_RGB => {
+                        let rgb = [sample(0), sample(1), sample(2)];
+                        let is_key = (0..3).all(|channel| key(channel) == Some(rgb[channel]));
+                        let alpha = if is_key { 0 } else { 255 };
+                        [scale(rgb[0]), scale(rgb[1]), scale(rgb[2]), alpha]
+                    }
+                    COLOR_TYPE_PALETTE => {
+                        let index = sample(0) as usize;
+                        let color = palette
+                            .get(index * 3..index * 3 + 3)
+                            .ok_or_else(|| format_err!("Invalid PNG palette index {}", index))?;
+                        let alpha = transparency.get(index).cloned().unwrap_or(255);
+                        [color[0], color[1], color[2], alpha]
+                    }
+                    _ => [
+                        scale(sample(0)),
+                        scale(sample(1)),
+                        scale(sample(2)),
+                        scale(sample(3)),
+                    ],
+                };
+                pixels.extend_from_slice(&pixel);
+            }
+            std::mem::swap(&mut previous_row, &mut row);
+        }
+
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Encodes the image as an 8-bit RGBA PNG
+    pub fn encode(&self) -> CargoResult<Vec<u8>> {
+        let row_length = self.width as usize * 4;
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        for row in self.pixels.chunks(row_length) {
+            // Each row is filtered with the Sub filter, which suits the smooth gradients of icons
+            let mut filtered = Vec::with_capacity(row_length + 1);
+            filtered.push(1);
+            for (index, byte) in row.iter().enumerate() {
+                let left = if index >= 4 { row[index - 4] } else { 0 };
+                filtered.push(byte.wrapping_sub(left));
+            }
+            encoder.write_all(&filtered)?;
+        }
+        let compressed = encoder.finish()?;
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&self.width.to_be_bytes());
+        header.extend_from_slice(&self.height.to_be_bytes());
+        // 8 bits per sample, RGBA, deflate compression, adaptive filtering, no interlacing
+        header.extend_from_slice(&[8, COLOR_TYPE_RGBA, 0, 0, 0]);
+
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"IDAT", &compressed);
+        write_chunk(&mut png, b"IEND", &[]);
+        Ok(png)
+    }
+
+    /// Returns the part of the image of the given size at its center
+    pub fn crop_center(&self, width: u32, height: u32) -> Image {
+        let left = (self.width - width) / 2;
+        let top = (self.height - height) / 2;
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in top..top + height {
+            let start = (y as usize * self.width as usize + left as usize) * 4;
+            pixels.extend_from_slice(&self.pixels[start..start + width as usize * 4]);
+        }
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Returns the image drawn over another image of the same size
+    pub fn over(&self, background: &Image) -> Image {
+        let pixels = self
+            .pixels
+            .chunks(4)
+            .zip(background.pixels.chunks(4))
+            .flat_map(|(foreground, background)| {
+                let foreground = premultiply(foreground);
+                let background = premultiply(background);
+                let mut pixel = [0.0; 4];
+                for channel in 0..4 {
+                    pixel[channel] =
+                        foreground[channel] + background[channel] * (1.0 - foreground[3]);
+                }
+                unpremultiply(pixel).to_vec()
+            })
+            .collect();
+        Image {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+
+    /// Returns the image scaled to the given size. Each pixel of the scaled image is the average
+    /// of the area of the image it covers, weighted by alpha.
+    pub fn resize(&self, width: u32, height: u32) -> Image {
+        let premultiplied = self.pixels.chunks(4).map(premultiply).collect::<Vec<_>>();
+        let horizontal_weights = area_weights(self.width, width);
+        let vertical_weights = area_weights(self.height, height);
+
+        // Scale the rows, then the columns
+        let mut rows = vec![[0.0; 4]; width as usize * self.height as usize];
+        for y in 0..self.height as usize {
+            for (x, weights) in horizontal_weights.iter().enumerate() {
+                let pixel = &mut rows[y * width as usize + x];
+                for &(source_x, weight) in weights {
+                    let source = premultiplied[y * self.width as usize + source_x];
+                    for channel in 0..4 {
+                        pixel[channel] += source[channel] * weight;
+                    }
+                }
+            }
+        }
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for weights in &vertical_weights {
+            for x in 0..width as usize {
+                let mut pixel = [0.0; 4];
+                for &(source_y, weight) in weights {
+                    let source = rows[source_y * width as usize + x];
+                    for channel in 0..4 {
+                        pixel[channel] += source[channel] * weight;
+                    }
+                }
+                pixels.extend_from_slice(&unpremultiply(pixel));
+            }
+        }
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// Reverses the filter of a row
+fn unfilter(
+    filter: u8,
+    filtered: &[u8],
+    previous: &[u8],
+    distance: usize,
+    row: &mut [u8],
+) -> CargoResult<()> {
+    for index in 0..row.len() {
+        let left = if index >= distance {
+            row[index - distance]
+        } else {
+            0
+        };
+        let up = previous[index];
+        let up_left = if index >= distance {
+            previous[index - distance]
+        } else {
+            0
+        };
+        let predictor = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((u16::from(left) + u16::from(up)) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return Err(format_err!("Invalid PNG filter type {}", filter)),
+        };
+        row[index] = filtered[index].wrapping_add(predictor);
+    }
+    Ok(())
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = i16::from(left) + i16::from(up) - i16::from(up_left);
+    let left_distance = (estimate - i16::from(left)).abs();
+    let up_distance = (estimate - i16::from(up)).abs();
+    let up_left_distance = (estimate - i16::from(up_left)).abs();
+    if left_distance <= up_distance && left_distance <= up_left_distance {
+        left
+    } else if up_distance <= up_left_distance {
+        up
+    } else {
+        up_left
+    }
+}
+
+fn write_chunk(png: &mut Vec<u8>, chunk_type: &[u8], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(chunk_type);
+    hasher.update(data);
+    png.extend_from_slice(&hasher.finalize().to_be_bytes());
+}
+
+/// Returns, for each pixel of a scaled axis, the source pixels it covers and their weights
+fn area_weights(source_length: u32, length: u32) -> Vec<Vec<(usize, f32)>> {
+    let scale = source_length as f32 / length as f32;
+    (0..length)
+        .map(|index| {
+            let start = index as f32 * scale;
+            let end = start + scale;
+            let mut weights = Vec::new();
+            let mut source_index = start.floor() as usize;
+            while (source_index as f32) < end && source_index < source_length as usize {
+                let coverage = end.min(source_index as f32 + 1.0) - start.max(source_index as f32);
+                weights.push((source_index, coverage / scale));
+                source_index += 1;
+            }
+            weights
+        })
+        .collect()
+}
+
+fn premultiply(pixel: &[u8]) -> [f32; 4] {
+    let alpha = f32::from(pixel[3]) / 255.0;
+    [
+        f32::from(pixel[0]) / 255.0 * alpha,
+        f32::from(pixel[1]) / 255.0 * alpha,
+        f32::from(pixel[2]) / 255.0 * alpha,
+        alpha,
+    ]
+}
+
+fn unpremultiply(pixel: [f32; 4]) -> [u8; 4] {
+    let alpha = pixel[3];
+    if alpha <= 0.0 {
+        return [0, 0, 0, 0];
+    }
+    let to_byte = |value: f32| {
+        let value = (value * 255.0).round();
+        if value <= 0.0 {
+            0
+        } else if value >= 255.0 {
+            255
+        } else {
+            value as u8
+        }
+    };
+    [
+        to_byte(pixel[0] / alpha),
+        to_byte(pixel[1] / alpha),
+        to_byte(pixel[2] / alpha),
+        to_byte(alpha),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode_resize() {
+        let mut image = Image::filled(6, 6, [255, 0, 0, 255]);
+        // Make the right half transparent
+        for (index, pixel) in image.pixels.chunks_mut(4).enumerate() {
+            if index % 6 >= 3 {
+                pixel.copy_from_slice(&[0, 0, 0, 0]);
+            }
+        }
+        let decoded = Image::decode(&image.encode().unwrap()).unwrap();
+        assert_eq!(decoded, image);
+
+        // Transparent pixels don't darken the color
+        let resized = image.resize(2, 2);
+        assert_eq!(resized.pixels[..8], [255, 0, 0, 255, 0, 0, 0, 0]);
+        let resized = image.resize(1, 1);
+        assert_eq!(resized.pixels, [255, 0, 0, 128]);
+
+        let cropped = image.crop_center(2, 2);
+        assert_eq!(cropped.pixels[..8], [255, 0, 0, 255, 0, 0, 0, 0]);
+    }
+}
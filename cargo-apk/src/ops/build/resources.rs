@@ -0,0 +1,477 @@
+//! Linking of the manifest and resources using aapt2, or aapt for build-tools which don't provide
+//! aapt2. Targets without resources are linked without aapt. Assets are added to the packages
+//! afterwards.
+//!
+//! Resource directories are linked as overlays: the resources of a directory override the
+//! resources of the previous directories with the same type, qualifiers and name.
+
+use super::apk::{ApkWriter, Compression, DosDateTime};
+use super::axml;
+use super::util;
+use crate::config::{AndroidConfig, AndroidTargetConfig};
+use cargo::core::Target;
+use cargo::util::process_builder::process;
+use cargo::util::CargoResult;
+use failure::format_err;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Tool used to compile and link resources
+pub enum ResourceTool {
+    Aapt2(PathBuf),
+    Aapt(PathBuf),
+}
+
+impl ResourceTool {
+    /// Finds aapt2 in the build-tools. Falls back to aapt when aapt2 is missing.
+    pub fn find(config: &AndroidConfig) -> CargoResult<ResourceTool> {
+        let build_tools_path = config
+            .sdk_path
+            .join("build-tools")
+            .join(&config.build_tools_version);
+        let aapt2_path = build_tools_path.join(format!("aapt2{}", util::EXECUTABLE_SUFFIX_EXE));
+        let aapt_path = build_tools_path.join(format!("aapt{}", util::EXECUTABLE_SUFFIX_EXE));
+        if aapt2_path.exists() {
+            Ok(ResourceTool::Aapt2(aapt2_path))
+        } else if aapt_path.exists() {
+            Ok(ResourceTool::Aapt(aapt_path))
+        } else {
+            Err(format_err!(
+                "Neither aapt2 nor aapt found in build-tools {}",
+                config.build_tools_version
+            ))
+        }
+    }
+}
+
+/// Creates an APK containing the compiled manifest and resources of the target. The manifest must
+/// have been generated in the target directory.
+pub fn link(
+    config: &AndroidConfig,
+    target_config: &AndroidTargetConfig,
+    target: &Target,
+    target_directory: &Path,
+    resources_apk_path: &Path,
+) -> CargoResult<()> {
+    // Targets without resources don't need aapt unless their manifest can't be encoded in-tree
+    let manifest_error = if target_config.res_paths.is_empty() {
+        match link_without_aapt(target_directory, resources_apk_path) {
+            Ok(()) => return Ok(()),
+            Err(e) => Some(e),
+        }
+    } else {
+        None
+    };
+    let tool = match (ResourceTool::find(config), manifest_error) {
+        (Ok(tool), _) => tool,
+        (Err(_), Some(manifest_error)) => return Err(manifest_error),
+        (Err(e), None) => return Err(e),
+    };
+
+    if resources_apk_path.exists() {
+        fs::remove_file(resources_apk_path)
+            .map_err(|e| format_err!("Unable to delete APK file. {}", e))?;
+    }
+
+    match &tool {
+        ResourceTool::Aapt2(aapt2_path) => aapt2_link(
+            aapt2_path,
+            config,
+            target_config,
+            target,
+            target_directory,
+            resources_apk_path,
+            false,
+        )?,
+        ResourceTool::Aapt(aapt_path) => {
+            check_duplicate_resources(&target_config.res_paths)?;
+
+            let mut aapt_package_cmd = process(aapt_path);
+            aapt_package_cmd
+                .arg("package")
+                .arg("-F")
+                .arg(resources_apk_path)
+                .arg("-M")
+                .arg("AndroidManifest.xml")
+                .arg("-I")
+                .arg(&config.android_jar_path);
+
+            aapt_package_cmd.arg("--auto-add-overlay");
+            aapt_package_cmd.args(&aapt_resource_args(&target_config.res_paths));
+
+            aapt_package_cmd.cwd(target_directory).exec()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates an APK containing the manifest encoded to binary XML
+fn link_without_aapt(target_directory: &Path, resources_apk_path: &Path) -> CargoResult<()> {
+    let manifest_path = target_directory.join("AndroidManifest.xml");
+    let manifest = fs::read_to_string(&manifest_path)
+        .map_err(|e| format_err!("Unable to read `{}`. {}", manifest_path.display(), e))?;
+    let manifest = axml::encode(&axml::parse(&manifest)?)?;
+
+    let mut apk = ApkWriter::create(resources_apk_path)?;
+    apk.add_entry(
+        "AndroidManifest.xml",
+        &manifest,
+        Compression::Deflated,
+        DosDateTime::from_system_time(SystemTime::now()),
+    )?;
+    apk.finish()
+}
+
+/// Compiles each resource file to a `.flat` file and links them with the manifest.
+/// The protobuf format is used by app bundles.
+pub fn aapt2_link(
+    aapt2_path: &Path,
+    config: &AndroidConfig,
+    target_config: &AndroidTargetConfig,
+    target: &Target,
+    target_directory: &Path,
+    output_path: &Path,
+    proto_format: bool,
+) -> CargoResult<()> {
+    check_duplicate_resources(&target_config.res_paths)?;
+
+    // Remove the output of previous builds so that deleted resources are not linked
+    let compiled_directory = target_directory.join(format!("{}_compiled_resources", target.name()));
+    if compiled_directory.exists() {
+        fs::remove_dir_all(&compiled_directory)?;
+    }
+    let mut compiled_resources = Vec::new();
+    for (index, res_path) in target_config.res_paths.iter().enumerate() {
+        compiled_resources.push(aapt2_compile(
+            aapt2_path,
+            res_path,
+            &compiled_directory.join(index.to_string()),
+        )?);
+    }
+
+    let mut aapt2_link_cmd = process(aapt2_path);
+    aapt2_link_cmd
+        .arg("link")
+        .arg("-o")
+        .arg(output_path)
+        .arg("-I")
+        .arg(&config.android_jar_path)
+        .arg("--manifest")
+        .arg("AndroidManifest.xml")
+        .arg("--auto-add-overlay");
+
+    if proto_format {
+        aapt2_link_cmd.arg("--proto-format");
+    }
+
+    aapt2_link_cmd.args(&aapt2_resource_args(compiled_resources));
+    aapt2_link_cmd.cwd(target_directory).exec()?;
+    Ok(())
+}
+
+/// Returns the aapt arguments adding the resource directories, of which the last one takes
+/// precedence
+fn aapt_resource_args(res_paths: &[PathBuf]) -> Vec<OsString> {
+    // aapt gives precedence to the first resource directory
+    let mut args = Vec::new();
+    for res_path in res_paths.iter().rev() {
+        args.push(OsString::from("-S"));
+        args.push(res_path.clone().into_os_string());
+    }
+    args
+}
+
+/// Returns the aapt2 link arguments adding the compiled resources of each resource directory, of
+/// which the last one takes precedence
+fn aapt2_resource_args(compiled_resources: Vec<Vec<PathBuf>>) -> Vec<OsString> {
+    // The resources of the first directory are linked as is, and the following ones as overlays
+    // of which the last one takes precedence
+    let mut args = Vec::new();
+    for (index, compiled_resources) in compiled_resources.into_iter().enumerate() {
+        for compiled_resource in compiled_resources {
+            if index > 0 {
+                args.push(OsString::from("-R"));
+            }
+            args.push(compiled_resource.into_os_string());
+        }
+    }
+    args
+}
+
+/// Compiles the files of a resource directory. Returns the paths of the `.flat` files.
+fn aapt2_compile(
+    aapt2_path: &Path,
+    res_path: &Path,
+    compiled_directory: &Path,
+) -> CargoResult<Vec<PathBuf>> {
+    fs::create_dir_all(compiled_directory)?;
+
+    let mut resource_files = Vec::new();
+    for resource_type_dir in read_dir_sorted(res_path)? {
+        if !resource_type_dir.is_dir() {
+            continue;
+        }
+        for resource_file in read_dir_sorted(&resource_type_dir)? {
+            if resource_file.is_file() {
+                resource_files.push(resource_file);
+            }
+        }
+    }
+
+    if resource_files.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    process(aapt2_path)
+        .arg("compile")
+        .arg("-o")
+        .arg(compiled_directory)
+        .args(&resource_files)
+        .exec()?;
+
+    read_dir_sorted(compiled_directory)
+}
+
+/// Checks that no resource is defined twice within a resource directory, so that the files
+/// defining it are reported instead of aapt failing with a duplicate resource error. Definitions
+/// in different directories are overlays and are not conflicts.
+fn check_duplicate_resources(res_paths: &[PathBuf]) -> CargoResult<()> {
+    for res_path in res_paths {
+        if !res_path.is_dir() {
+            return Err(format_err!(
+                "Resources directory `{}` does not exist",
+                res_path.display()
+            ));
+        }
+
+        // Files defining each resource, keyed by type, qualifiers and name
+        let mut definitions = HashMap::<_, PathBuf>::new();
+        for resource_type_dir in read_dir_sorted(res_path)? {
+            if !resource_type_dir.is_dir() {
+                continue;
+            }
+            let dir_name = resource_type_dir
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned();
+            let (dir_type, qualifiers) = match dir_name.find('-') {
+                Some(index) => (&dir_name[..index], &dir_name[index + 1..]),
+                None => (dir_name.as_str(), ""),
+            };
+            for resource_file in read_dir_sorted(&resource_type_dir)? {
+                if !resource_file.is_file() {
+                    continue;
+                }
+                let resources = if dir_type == "values" {
+                    value_resources(&resource_file)
+                } else {
+                    let file_name = resource_file.file_name().unwrap().to_string_lossy();
+                    let name = file_name.split('.').next().unwrap_or_default().to_owned();
+                    vec![(dir_type.to_owned(), name)]
+                };
+                for (resource_type, name) in resources {
+                    let key = (resource_type, qualifiers.to_owned(), name);
+                    if let Some(previous_file) = definitions.get(&key) {
+                        let (resource_type, qualifiers, name) = key;
+                        return Err(format_err!(
+                            "`{}` and `{}` both define the resource `@{}/{}`{}",
+                            previous_file.display(),
+                            resource_file.display(),
+                            resource_type,
+                            name,
+                            if qualifiers.is_empty() {
+                                String::new()
+                            } else {
+                                format!(" for `{}`", qualifiers)
+                            }
+                        ));
+                    }
+                    definitions.insert(key, resource_file.clone());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the type and name of the resources defined by a values file. Files which can't be
+/// parsed are left to aapt to report.
+fn value_resources(path: &Path) -> Vec<(String, String)> {
+    let root = match fs::read_to_string(path)
+        .ok()
+        .and_then(|text| axml::parse(&text).ok())
+    {
+        Some(root) => root,
+        None => return Vec::new(),
+    };
+    root.children
+        .iter()
+        .filter_map(|element| {
+            let attribute = |name| {
+                element
+                    .attributes
+                    .iter()
+                    .find(|attribute| attribute.namespace.is_none() && attribute.name == name)
+                    .map(|attribute| attribute.value.clone())
+            };
+            let resource_type = match element.name.as_str() {
+                "item" => attribute("type")?,
+                "array" | "integer-array" | "string-array" => "array".to_owned(),
+                "declare-styleable" => "styleable".to_owned(),
+                "eat-comment" | "skip" | "public" | "public-group" | "java-symbol" => return None,
+                name => name.to_owned(),
+            };
+            // IDs can be declared several times
+            if resource_type == "id" {
+                return None;
+            }
+            Some((resource_type, attribute("name")?))
+        })
+        .collect()
+}
+
+/// Returns the paths of the entries of a directory, ignoring hidden files, in a stable order
+fn read_dir_sorted(path: &Path) -> CargoResult<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(path)
+        .map_err(|e| format_err!("Unable to read directory `{}`. {}", path.display(), e))?
+    {
+        let entry = entry?;
+        if !entry.file_name().to_string_lossy().starts_with('.') {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::TempDir;
+
+    #[test]
+    fn value_resource_types() {
+        let dir = TempDir::new("resources-values").unwrap();
+        let path = dir.path.join("values.xml");
+        fs::write(
+            &path,
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<resources>
+    <!-- Branding -->
+    <string name="app_name">App</string>
+    <string-array name="planets"><item>Mercury</item></string-array>
+    <item type="dimen" name="margin">4dp</item>
+    <item type="id" name="button" />
+    <declare-styleable name="View"><attr name="color" format="color" /></declare-styleable>
+    <eat-comment />
+</resources>
+"#,
+        )
+        .unwrap();
+        let resources = value_resources(&path);
+        assert_eq!(
+            resources,
+            vec![
+                ("string".to_owned(), "app_name".to_owned()),
+                ("array".to_owned(), "planets".to_owned()),
+                ("dimen".to_owned(), "margin".to_owned()),
+                ("styleable".to_owned(), "View".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn overlay_order() {
+        let res_paths = vec![PathBuf::from("res"), PathBuf::from("overlay")];
+        assert_eq!(
+            aapt_resource_args(&res_paths),
+            vec!["-S", "overlay", "-S", "res"]
+        );
+
+        let compiled_resources = vec![
+            vec![
+                PathBuf::from("0/values_strings.arsc.flat"),
+                PathBuf::from("0/drawable_icon.png.flat"),
+            ],
+            Vec::new(),
+            vec![PathBuf::from("2/values_strings.arsc.flat")],
+        ];
+        assert_eq!(
+            aapt2_resource_args(compiled_resources),
+            vec![
+                "0/values_strings.arsc.flat",
+                "0/drawable_icon.png.flat",
+                "-R",
+                "2/values_strings.arsc.flat"
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_resources() {
+        let dir = TempDir::new("resources-duplicates").unwrap();
+        let write = |path: &str, contents: &str| {
+            let path = dir.path.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        };
+        let strings = |name: &str| {
+            format!(
+                r#"<resources><string name="{}">App</string></resources>"#,
+                name
+            )
+        };
+        write("res/values/strings.xml", &strings("app_name"));
+        write("res/values-fr/strings.xml", &strings("app_name"));
+        write("res/drawable/icon.png", "");
+        write("overlay/values/strings.xml", &strings("app_name"));
+        write("overlay/drawable/icon.xml", "");
+        let res_paths = vec![dir.path.join("res"), dir.path.join("overlay")];
+
+        // Qualified definitions and overlays are not conflicts
+        check_duplicate_resources(&res_paths).unwrap();
+
+        write("overlay/values-fr/other.xml", &strings("title"));
+        write("overlay/values-fr/strings.xml", &strings("title"));
+        let error = check_duplicate_resources(&res_paths)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            format!(
+                "`{}` and `{}` both define the resource `@string/title` for `fr`",
+                dir.path.join("overlay/values-fr/other.xml").display(),
+                dir.path.join("overlay/values-fr/strings.xml").display()
+            )
+        );
+
+        fs::remove_dir_all(dir.path.join("overlay/values-fr")).unwrap();
+        write("res/drawable/icon.xml", "");
+        let error = check_duplicate_resources(&res_paths)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            format!(
+                "`{}` and `{}` both define the resource `@drawable/icon`",
+                dir.path.join("res/drawable/icon.png").display(),
+                dir.path.join("res/drawable/icon.xml").display()
+            )
+        );
+
+        let missing = dir.path.join("missing");
+        let error = check_duplicate_resources(std::slice::from_ref(&missing))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            format!("Resources directory `{}` does not exist", missing.display())
+        );
+    }
+}
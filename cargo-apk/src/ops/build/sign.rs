@@ -0,0 +1,323 @@
+//! Signing of APKs using the v1 (JAR), v2 and v3 APK signature schemes without requiring the
+//! JDK or `apksigner`.
+
+mod block;
+mod der;
+mod jar;
+mod keystore;
+mod oids;
+mod x509;
+
+use self::x509::Certificate;
+use super::apk::{ApkReader, ApkWriter, Compression, DosDateTime};
+use cargo::util::CargoResult;
+use failure::format_err;
+use rsa::{Hash, PaddingScheme, RSAPrivateKey};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+const DEBUG_KEYSTORE_PASSWORD: &str = "android";
+const DEBUG_KEY_ALIAS: &str = "androiddebugkey";
+
+/// SHA-256 digests in JAR signatures are only supported starting with Android 4.3
+const JAR_SHA256_MIN_SDK_VERSION: u32 = 18;
+
+#[derive(Debug, Copy, Clone)]
+pub enum DigestAlgorithm {
+    Sha1,
+    Sha256,
+}
+
+impl DigestAlgorithm {
+    /// Name of the algorithm used in the JAR manifest attributes
+    fn jar_name(self) -> &'static str {
+        match self {
+            DigestAlgorithm::Sha1 => "SHA1",
+            DigestAlgorithm::Sha256 => "SHA-256",
+        }
+    }
+
+    fn oid(self) -> &'static str {
+        match self {
+            DigestAlgorithm::Sha1 => oids::SHA1,
+            DigestAlgorithm::Sha256 => oids::SHA256,
+        }
+    }
+
+    fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            DigestAlgorithm::Sha1 => Sha1::digest(data).to_vec(),
+            DigestAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+        }
+    }
+}
+
+/// RSA private key and certificate used to sign APKs
+pub struct SigningKey {
+    key: RSAPrivateKey,
+    pub certificate: Certificate,
+}
+
+impl SigningKey {
+    /// Loads a key from a JKS or PKCS#12 keystore or from a PEM/PK8 key file. When no alias is
+    /// specified, the keystore must contain a single key.
+    pub fn load(
+        path: &Path,
+        store_password: &str,
+        alias: Option<&str>,
+        key_password: &str,
+    ) -> CargoResult<SigningKey> {
+        let mut entries = keystore::read_key_entries(path, store_password, key_password)?;
+        let index = match alias {
+            Some(alias) => entries
+                .iter()
+                .position(|e| e.alias.eq_ignore_ascii_case(alias))
+                .ok_or_else(|| {
+                    format_err!("Key `{}` not found in keystore `{}`", alias, path.display())
+                })?,
+            None if entries.len() == 1 => 0,
+            None => {
+                return Err(format_err!(
+                    "Keystore `{}` contains {} keys. Specify the alias of the key to use.",
+                    path.display(),
+                    entries.len()
+                ))
+            }
+        };
+        let entry = entries.swap_remove(index);
+
+        let key = RSAPrivateKey::from_pkcs8(&entry.private_key).map_err(|e| {
+            format_err!(
+                "Unsupported key in keystore `{}`. Only RSA keys are supported. {}",
+                path.display(),
+                e
+            )
+        })?;
+        let certificate =
+            entry.certificates.into_iter().next().ok_or_else(|| {
+                format_err!("No certificate found for key in `{}`", path.display())
+            })?;
+
+        Ok(SigningKey {
+            key,
+            certificate: Certificate::from_der(certificate)?,
+        })
+    }
+
+    /// Signs the data using RSA PKCS#1 v1.5
+    fn sign(&self, digest_algorithm: DigestAlgorithm, data: &[u8]) -> CargoResult<Vec<u8>> {
+        let hash = match digest_algorithm {
+            DigestAlgorithm::Sha1 => Hash::SHA1,
+            DigestAlgorithm::Sha256 => Hash::SHA2_256,
+        };
+        self.key
+            .sign(
+                PaddingScheme::new_pkcs1v15_sign(Some(hash)),
+                &digest_algorithm.digest(data),
+            )
+            .map_err(|e| format_err!("Unable to sign APK. {}", e))
+    }
+}
+
+/// Returns the key of the debug keystore used by the Android SDK. If it does not exist, it is
+/// created.
+pub fn debug_signing_key() -> CargoResult<SigningKey> {
+    let android_directory = dirs::home_dir()
+        .ok_or_else(|| format_err!("Unable to determine home directory"))?
+        .join(".android");
+    fs::create_dir_all(&android_directory)?;
+    let keystore_path = android_directory.join("debug.keystore");
+
+    if !keystore_path.exists() {
+        let key = RSAPrivateKey::new(&mut rand::rngs::OsRng, 2048)
+            .map_err(|e| format_err!("Unable to generate debug key. {}", e))?;
+        let certificate = Certificate::self_signed(
+            &key,
+            &[("C", "US"), ("O", "Android"), ("CN", "Android Debug")],
+            10000,
+        )?;
+        keystore::write_jks(
+            &keystore_path,
+            DEBUG_KEY_ALIAS,
+            &x509::encode_private_key(&key),
+            &[&certificate.der],
+            DEBUG_KEYSTORE_PASSWORD,
+            DEBUG_KEYSTORE_PASSWORD,
+        )?;
+    }
+
+    load_debug_key(&keystore_path)
+}
+
+/// Loads the key of a debug keystore. Keystores created by older versions of cargo-apk use the
+/// alias `androidebugkey`, so the only key of the keystore is used if none has the usual alias.
+fn load_debug_key(keystore_path: &Path) -> CargoResult<SigningKey> {
+    let load = |alias| {
+        SigningKey::load(
+            keystore_path,
+            DEBUG_KEYSTORE_PASSWORD,
+            alias,
+            DEBUG_KEYSTORE_PASSWORD,
+        )
+    };
+    load(Some(DEBUG_KEY_ALIAS)).or_else(|e| load(None).map_err(|_| e))
+}
+
+/// Signs an APK using the v1, v2 and v3 signature schemes
+pub fn sign_apk(
+    unsigned_apk_path: &Path,
+    signed_apk_path: &Path,
+    key: &SigningKey,
+    min_sdk_version: u32,
+) -> CargoResult<()> {
+    let digest_algorithm = if min_sdk_version >= JAR_SHA256_MIN_SDK_VERSION {
+        DigestAlgorithm::Sha256
+    } else {
+        DigestAlgorithm::Sha1
+    };
+    write_jar_signed(
+        unsigned_apk_path,
+        signed_apk_path,
+        key,
+        digest_algorithm,
+        Some("2, 3"),
+    )?;
+
+    // v2 and v3 signatures cover the whole APK including the v1 signature
+    let data = fs::read(signed_apk_path)?;
+    let signed = block::add_signing_block(&data, key, min_sdk_version)?;
+    fs::write(signed_apk_path, signed)
+        .map_err(|e| format_err!("Unable to write APK `{}`. {}", signed_apk_path.display(), e))?;
+
+    Ok(())
+}
+
+/// Signs an Android App Bundle. Bundles are only signed using JAR signing like `jarsigner` does.
+pub fn sign_bundle(
+    unsigned_bundle_path: &Path,
+    signed_bundle_path: &Path,
+    key: &SigningKey,
+) -> CargoResult<()> {
+    write_jar_signed(
+        unsigned_bundle_path,
+        signed_bundle_path,
+        key,
+        DigestAlgorithm::Sha256,
+        None,
+    )
+}
+
+/// Returns the certificates of the signers of an APK for each signature scheme
+pub fn signer_certificates(apk: &ApkReader) -> CargoResult<Vec<(&'static str, Certificate)>> {
+    let mut certificates = Vec::new();
+    for entry in apk.entries() {
+        if jar::is_signature_block(&entry.name) {
+            for der in jar::signer_certificates(&apk.read(entry)?)? {
+                certificates.push(("v1", Certificate::from_der(der)?));
+            }
+        }
+    }
+    for (scheme, ders) in block::signer_certificates(apk.data())? {
+        for der in ders {
+            certificates.push((scheme, Certificate::from_der(der)?));
+        }
+    }
+    Ok(certificates)
+}
+
+/// Copies an archive and adds a JAR signature. Existing signature files are replaced.
+fn write_jar_signed(
+    unsigned_path: &Path,
+    signed_path: &Path,
+    key: &SigningKey,
+    digest_algorithm: DigestAlgorithm,
+    signature_schemes: Option<&str>,
+) -> CargoResult<()> {
+    let unsigned = ApkReader::open(unsigned_path)?;
+    let signature_files = jar::sign(&unsigned, key, digest_algorithm, signature_schemes)?;
+
+    let mut signed = ApkWriter::create(signed_path)?;
+    for entry in unsigned.entries() {
+        if !jar::is_signature_file(&entry.name) {
+            signed.copy_entry(&unsigned, entry)?;
+        }
+    }
+    // The signature files are dated like the newest entry so that the signed archive only
+    // depends on its contents
+    let modified = unsigned
+        .entries()
+        .iter()
+        .map(|entry| entry.modified)
+        .max_by_key(|modified| (modified.date, modified.time))
+        .unwrap_or_else(|| DosDateTime::from_system_time(SystemTime::now()));
+    for (name, data) in signature_files {
+        signed.add_entry(name, &data, Compression::Deflated, modified)?;
+    }
+    signed.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::build::tempfile::TempDir;
+    use std::path::PathBuf;
+
+    /// Returns the path of a file generated by `tests/fixtures/generate.sh`
+    pub fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    pub fn test_key() -> SigningKey {
+        SigningKey::load(&fixture("test.pk8"), "", None, "").unwrap()
+    }
+
+    /// Returns the data of a small unsigned APK
+    pub fn test_apk(name: &str) -> Vec<u8> {
+        let dir = TempDir::new(name).unwrap();
+        let path = dir.path.join("test.apk");
+        let modified = DosDateTime::from_unix_timestamp(1_574_519_518);
+        let mut apk = ApkWriter::create(&path).unwrap();
+        apk.add_entry(
+            "AndroidManifest.xml",
+            b"manifest",
+            Compression::Stored,
+            modified,
+        )
+        .unwrap();
+        apk.add_entry("classes.dex", &[1; 1000], Compression::Deflated, modified)
+            .unwrap();
+        apk.finish().unwrap();
+        fs::read(&path).unwrap()
+    }
+
+    #[test]
+    fn load_debug_key_with_other_alias() {
+        let dir = TempDir::new("debug-keystore").unwrap();
+        let path = dir.path.join("debug.keystore");
+        let key = test_key();
+        keystore::write_jks(
+            &path,
+            "androidebugkey",
+            &fs::read(fixture("test.pk8")).unwrap(),
+            &[&key.certificate.der],
+            DEBUG_KEYSTORE_PASSWORD,
+            DEBUG_KEYSTORE_PASSWORD,
+        )
+        .unwrap();
+        let debug_key = load_debug_key(&path).unwrap();
+        assert_eq!(debug_key.certificate.der, key.certificate.der);
+    }
+
+    #[test]
+    fn load_key_by_alias() {
+        let keystore = fixture("test.p12");
+        assert!(SigningKey::load(&keystore, "android", None, "android").is_ok());
+        assert!(SigningKey::load(&keystore, "android", Some("TEST"), "android").is_ok());
+        assert!(SigningKey::load(&keystore, "android", Some("other"), "android").is_err());
+    }
+}
@@ -0,0 +1,257 @@
+//! APK Signature Scheme v2 and v3
+//! See https://source.android.com/security/apksigning/v2 and
+//! https://source.android.com/security/apksigning/v3
+
+use super::{DigestAlgorithm, SigningKey};
+use crate::ops::build::apk;
+use cargo::util::CargoResult;
+use failure::format_err;
+use sha2::{Digest, Sha256};
+
+const APK_SIGNING_BLOCK_MAGIC: &[u8; 16] = b"APK Sig Block 42";
+const V2_BLOCK_ID: u32 = 0x7109_871a;
+const V3_BLOCK_ID: u32 = 0xf053_68c0;
+const STRIPPING_PROTECTION_ATTRIBUTE_ID: u32 = 0xbeef_f00d;
+const RSA_PKCS1_V1_5_WITH_SHA256: u32 = 0x0103;
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// APK Signature Scheme v3 is only verified starting with Android 9
+const V3_MIN_SDK_VERSION: u32 = 28;
+
+/// Inserts an APK Signing Block containing v2 and v3 signatures before the central directory
+pub fn add_signing_block(
+    apk: &[u8],
+    key: &SigningKey,
+    min_sdk_version: u32,
+) -> CargoResult<Vec<u8>> {
+    let (cd_offset, eocd_offset) = apk::central_directory_offsets(apk)?;
+    let digest = content_digest(&[
+        &apk[..cd_offset],
+        &apk[cd_offset..eocd_offset],
+        &apk[eocd_offset..],
+    ]);
+
+    let digests = length_prefixed(&length_prefixed(
+        &[
+            &RSA_PKCS1_V1_5_WITH_SHA256.to_le_bytes()[..],
+            &length_prefixed(&digest),
+        ]
+        .concat(),
+    ));
+    let certificates = length_prefixed(&length_prefixed(&key.certificate.der));
+    let public_key = length_prefixed(&key.certificate.public_key_info);
+
+    // v2 signer. The stripping protection attribute prevents removing the v3 signature.
+    let stripping_protection = length_prefixed(
+        &[
+            &STRIPPING_PROTECTION_ATTRIBUTE_ID.to_le_bytes()[..],
+            &3u32.to_le_bytes()[..],
+        ]
+        .concat(),
+    );
+    let v2_signed_data = [
+        &digests[..],
+        &certificates,
+        &length_prefixed(&stripping_protection),
+    ]
+    .concat();
+    let v2_signer = [
+        &length_prefixed(&v2_signed_data)[..],
+        &signatures(key, &v2_signed_data)?,
+        &public_key,
+    ]
+    .concat();
+
+    // v3 signer
+    let min_sdk = min_sdk_version.max(V3_MIN_SDK_VERSION).to_le_bytes();
+    let max_sdk = 0x7fff_ffffu32.to_le_bytes();
+    let v3_signed_data = [
+        &digests[..],
+        &certificates,
+        &min_sdk,
+        &max_sdk,
+        &length_prefixed(&[]),
+    ]
+    .concat();
+    let v3_signer = [
+        &length_prefixed(&v3_signed_data)[..],
+        &min_sdk,
+        &max_sdk,
+        &signatures(key, &v3_signed_data)?,
+        &public_key,
+    ]
+    .concat();
+
+    let block = signing_block(&[
+        (V2_BLOCK_ID, length_prefixed(&length_prefixed(&v2_signer))),
+        (V3_BLOCK_ID, length_prefixed(&length_prefixed(&v3_signer))),
+    ]);
+
+    // Insert the block and update the offset of the central directory
+    let mut signed = Vec::with_capacity(apk.len() + block.len());
+    signed.extend_from_slice(&apk[..cd_offset]);
+    signed.extend_from_slice(&block);
+    signed.extend_from_slice(&apk[cd_offset..eocd_offset]);
+    signed.extend_from_slice(&apk[eocd_offset..]);
+    let new_cd_offset = ((cd_offset + block.len()) as u32).to_le_bytes();
+    let cd_offset_field = cd_offset + block.len() + (eocd_offset - cd_offset) + 16;
+    signed[cd_offset_field..cd_offset_field + 4].copy_from_slice(&new_cd_offset);
+
+    Ok(signed)
+}
+
+/// Signature scheme and DER encoded certificates of a signer
+pub type SignerCertificates = (&'static str, Vec<Vec<u8>>);
+
+/// Returns the certificates of the first signer of the v2 and v3 signatures of a signed APK
+pub fn signer_certificates(apk: &[u8]) -> CargoResult<Vec<SignerCertificates>> {
+    let invalid = || format_err!("Invalid APK Signing Block");
+    let (cd_offset, _) = apk::central_directory_offsets(apk)?;
+    if cd_offset < 24 || &apk[cd_offset - 16..cd_offset] != APK_SIGNING_BLOCK_MAGIC {
+        return Ok(Vec::new());
+    }
+    let size = read_u64(apk, cd_offset - 24)? as usize;
+    // The size excludes the leading size field
+    let start = cd_offset.checked_sub(size).ok_or_else(invalid)?;
+    let mut pairs = apk.get(start..cd_offset - 24).ok_or_else(invalid)?;
+
+    let mut result = Vec::new();
+    while !pairs.is_empty() {
+        let len = read_u64(pairs, 0)? as usize;
+        let end = len.checked_add(8).ok_or_else(invalid)?;
+        let pair = pairs.get(8..end).ok_or_else(invalid)?;
+        let scheme = match read_u32(pair, 0)? {
+            V2_BLOCK_ID => "v2",
+            V3_BLOCK_ID => "v3",
+            _ => {
+                pairs = &pairs[end..];
+                continue;
+            }
+        };
+
+        // Sequence of signers, signed data, certificates
+        let signers = read_length_prefixed(pair, 4)?;
+        let signer = read_length_prefixed(signers, 0)?;
+        let signed_data = read_length_prefixed(signer, 0)?;
+        let digests = read_length_prefixed(signed_data, 0)?;
+        let mut certificates = read_length_prefixed(signed_data, 4 + digests.len())?;
+        let mut ders = Vec::new();
+        while !certificates.is_empty() {
+            let certificate = read_length_prefixed(certificates, 0)?;
+            ders.push(certificate.to_vec());
+            certificates = &certificates[4 + certificate.len()..];
+        }
+        result.push((scheme, ders));
+        pairs = &pairs[end..];
+    }
+    Ok(result)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> CargoResult<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| format_err!("Invalid APK Signing Block"))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> CargoResult<u64> {
+    Ok(u64::from(read_u32(data, offset)?) | u64::from(read_u32(data, offset + 4)?) << 32)
+}
+
+fn read_length_prefixed(data: &[u8], offset: usize) -> CargoResult<&[u8]> {
+    let len = read_u32(data, offset)? as usize;
+    data.get(offset + 4..offset + 4 + len)
+        .ok_or_else(|| format_err!("Invalid APK Signing Block"))
+}
+
+/// Computes the chunked SHA-256 digest of the contents of the APK
+fn content_digest(sections: &[&[u8]]) -> Vec<u8> {
+    let chunk_digests: Vec<_> = sections
+        .iter()
+        .flat_map(|section| section.chunks(CHUNK_SIZE))
+        .map(|chunk| {
+            Sha256::new()
+                .chain([0xa5])
+                .chain((chunk.len() as u32).to_le_bytes())
+                .chain(chunk)
+                .finalize()
+        })
+        .collect();
+
+    let mut hasher = Sha256::new()
+        .chain([0x5a])
+        .chain((chunk_digests.len() as u32).to_le_bytes());
+    for chunk_digest in &chunk_digests {
+        hasher.update(chunk_digest);
+    }
+    hasher.finalize().to_vec()
+}
+
+/// Returns the length prefixed sequence of signatures of the signed data
+fn signatures(key: &SigningKey, signed_data: &[u8]) -> CargoResult<Vec<u8>> {
+    let signature = key.sign(DigestAlgorithm::Sha256, signed_data)?;
+    Ok(length_prefixed(&length_prefixed(
+        &[
+            &RSA_PKCS1_V1_5_WITH_SHA256.to_le_bytes()[..],
+            &length_prefixed(&signature),
+        ]
+        .concat(),
+    )))
+}
+
+fn signing_block(pairs: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut content = Vec::new();
+    for (id, value) in pairs {
+        content.extend_from_slice(&(4 + value.len() as u64).to_le_bytes());
+        content.extend_from_slice(&id.to_le_bytes());
+        content.extend_from_slice(value);
+    }
+
+    let size = (content.len() + 8 + APK_SIGNING_BLOCK_MAGIC.len()) as u64;
+    let mut block = Vec::with_capacity(size as usize + 8);
+    block.extend_from_slice(&size.to_le_bytes());
+    block.extend_from_slice(&content);
+    block.extend_from_slice(&size.to_le_bytes());
+    block.extend_from_slice(APK_SIGNING_BLOCK_MAGIC);
+    block
+}
+
+fn length_prefixed(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 4);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::build::apk::ApkReader;
+    use crate::ops::build::sign::tests::{test_apk, test_key};
+
+    #[test]
+    fn signing_block() {
+        let unsigned = test_apk("signing-block");
+        let key = test_key();
+        let signed = add_signing_block(&unsigned, &key, 21).unwrap();
+
+        let certificates = vec![key.certificate.der.clone()];
+        assert_eq!(
+            signer_certificates(&signed).unwrap(),
+            [("v2", certificates.clone()), ("v3", certificates)]
+        );
+        assert!(signer_certificates(&unsigned).unwrap().is_empty());
+
+        // The central directory follows the block, which ends with the magic
+        let (unsigned_cd_offset, _) = apk::central_directory_offsets(&unsigned).unwrap();
+        let (cd_offset, _) = apk::central_directory_offsets(&signed).unwrap();
+        assert_eq!(
+            cd_offset,
+            unsigned_cd_offset + signed.len() - unsigned.len()
+        );
+        assert_eq!(&signed[cd_offset - 16..cd_offset], APK_SIGNING_BLOCK_MAGIC);
+        assert_eq!(&signed[cd_offset..cd_offset + 4], b"PK\x01\x02");
+
+        let apk = ApkReader::from_bytes(signed).unwrap();
+        assert_eq!(apk.read(&apk.entries()[1]).unwrap(), [1; 1000].to_vec());
+    }
+}
@@ -0,0 +1,287 @@
+//! Minimal DER encoder and decoder for the structures used when signing APKs
+
+use cargo::util::CargoResult;
+use failure::format_err;
+
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_BIT_STRING: u8 = 0x03;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_PRINTABLE_STRING: u8 = 0x13;
+pub const TAG_UTC_TIME: u8 = 0x17;
+pub const TAG_GENERALIZED_TIME: u8 = 0x18;
+pub const TAG_BMP_STRING: u8 = 0x1e;
+pub const TAG_SEQUENCE: u8 = 0x30;
+pub const TAG_SET: u8 = 0x31;
+
+/// Returns the tag of a context specific constructed element. Eg. `[0] EXPLICIT`
+pub const fn context(number: u8) -> u8 {
+    0xa0 | number
+}
+
+/// Returns the tag of a context specific primitive element. Eg. `[0] IMPLICIT OCTET STRING`
+pub const fn context_primitive(number: u8) -> u8 {
+    0x80 | number
+}
+
+/// Element read from DER encoded data
+#[derive(Debug, Copy, Clone)]
+pub struct Element<'a> {
+    pub tag: u8,
+    pub value: &'a [u8],
+    /// Complete encoding of the element including the tag and length
+    pub raw: &'a [u8],
+}
+
+impl<'a> Element<'a> {
+    /// Returns a reader over the contents of a constructed element
+    pub fn reader(&self) -> Reader<'a> {
+        Reader::new(self.value)
+    }
+
+    pub fn expect(self, tag: u8) -> CargoResult<Element<'a>> {
+        if self.tag == tag {
+            Ok(self)
+        } else {
+            Err(format_err!(
+                "Unexpected DER tag 0x{:02x}. Expected 0x{:02x}",
+                self.tag,
+                tag
+            ))
+        }
+    }
+
+    /// Decodes an OBJECT IDENTIFIER to its dotted representation
+    pub fn oid(&self) -> CargoResult<String> {
+        self.expect(TAG_OID)?;
+        decode_oid(self.value)
+    }
+
+    /// Decodes a small non-negative INTEGER
+    pub fn u64(&self) -> CargoResult<u64> {
+        self.expect(TAG_INTEGER)?;
+        if self.value.is_empty() || self.value.len() > 9 || self.value[0] & 0x80 != 0 {
+            return Err(format_err!("Unsupported DER integer"));
+        }
+        Ok(self
+            .value
+            .iter()
+            .fold(0u64, |acc, b| (acc << 8) | u64::from(*b)))
+    }
+}
+
+/// Sequential reader of DER elements
+#[derive(Debug, Copy, Clone)]
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.data.first().cloned()
+    }
+
+    pub fn read(&mut self) -> CargoResult<Element<'a>> {
+        let truncated = || format_err!("Truncated DER data");
+        let tag = *self.data.first().ok_or_else(truncated)?;
+        let first_len = *self.data.get(1).ok_or_else(truncated)?;
+        let (len, header_len) = if first_len & 0x80 == 0 {
+            (first_len as usize, 2)
+        } else {
+            let count = (first_len & 0x7f) as usize;
+            if count == 0 || count > 4 {
+                return Err(format_err!("Unsupported DER length encoding"));
+            }
+            let bytes = self.data.get(2..2 + count).ok_or_else(truncated)?;
+            let len = bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+            (len, 2 + count)
+        };
+
+        let raw = self.data.get(..header_len + len).ok_or_else(truncated)?;
+        self.data = &self.data[header_len + len..];
+        Ok(Element {
+            tag,
+            value: &raw[header_len..],
+            raw,
+        })
+    }
+
+    pub fn read_tag(&mut self, tag: u8) -> CargoResult<Element<'a>> {
+        self.read()?.expect(tag)
+    }
+
+    /// Reads an element only if it has the specified tag
+    pub fn read_optional(&mut self, tag: u8) -> CargoResult<Option<Element<'a>>> {
+        if self.peek_tag() == Some(tag) {
+            self.read().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Encodes an element with the specified tag
+pub fn element(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = value.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = (len as u32).to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (4 - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(value);
+    out
+}
+
+pub fn sequence(elements: &[&[u8]]) -> Vec<u8> {
+    element(TAG_SEQUENCE, &elements.concat())
+}
+
+/// Encodes a SET OF. The elements are sorted as required by DER.
+pub fn set(elements: &[&[u8]]) -> Vec<u8> {
+    let mut elements = elements.to_vec();
+    elements.sort();
+    element(TAG_SET, &elements.concat())
+}
+
+/// Encodes an unsigned big endian integer
+pub fn integer(bytes: &[u8]) -> Vec<u8> {
+    let skip = bytes
+        .iter()
+        .take_while(|b| **b == 0)
+        .count()
+        .min(bytes.len().saturating_sub(1));
+    let bytes = &bytes[skip..];
+    if bytes.is_empty() {
+        return element(TAG_INTEGER, &[0]);
+    }
+    if bytes[0] & 0x80 != 0 {
+        let mut value = vec![0];
+        value.extend_from_slice(bytes);
+        element(TAG_INTEGER, &value)
+    } else {
+        element(TAG_INTEGER, bytes)
+    }
+}
+
+pub fn integer_u64(value: u64) -> Vec<u8> {
+    integer(&value.to_be_bytes())
+}
+
+pub fn null() -> Vec<u8> {
+    element(TAG_NULL, &[])
+}
+
+pub fn octet_string(value: &[u8]) -> Vec<u8> {
+    element(TAG_OCTET_STRING, value)
+}
+
+/// Encodes a BIT STRING without unused bits
+pub fn bit_string(value: &[u8]) -> Vec<u8> {
+    let mut content = vec![0];
+    content.extend_from_slice(value);
+    element(TAG_BIT_STRING, &content)
+}
+
+pub fn oid(dotted: &str) -> Vec<u8> {
+    let parts: Vec<u64> = dotted
+        .split('.')
+        .map(|p| p.parse().expect("invalid object identifier"))
+        .collect();
+    let mut content = vec![(parts[0] * 40 + parts[1]) as u8];
+    for &part in &parts[2..] {
+        let mut encoded = vec![(part & 0x7f) as u8];
+        let mut rest = part >> 7;
+        while rest > 0 {
+            encoded.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        encoded.reverse();
+        content.extend_from_slice(&encoded);
+    }
+    element(TAG_OID, &content)
+}
+
+/// Encodes an AlgorithmIdentifier with NULL parameters
+pub fn algorithm(dotted: &str) -> Vec<u8> {
+    sequence(&[&oid(dotted), &null()])
+}
+
+fn decode_oid(value: &[u8]) -> CargoResult<String> {
+    let first = *value
+        .first()
+        .ok_or_else(|| format_err!("Empty object identifier"))?;
+    let mut parts = vec![u64::from(first / 40), u64::from(first % 40)];
+    let mut current = 0u64;
+    for b in &value[1..] {
+        current = (current << 7) | u64::from(b & 0x7f);
+        if b & 0x80 == 0 {
+            parts.push(current);
+            current = 0;
+        }
+    }
+    Ok(parts
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oid_round_trip() {
+        for dotted in &["2.5.4.3", "1.2.840.113549.1.1.11", "2.16.840.1.101.3.4.2.1"] {
+            let encoded = oid(dotted);
+            assert_eq!(
+                Reader::new(&encoded).read().unwrap().oid().unwrap(),
+                *dotted
+            );
+        }
+        assert_eq!(oid("2.5.4.3"), [TAG_OID, 3, 0x55, 0x04, 0x03]);
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(integer(&[0, 0]), [TAG_INTEGER, 1, 0]);
+        assert_eq!(integer(&[0, 0, 0x7f]), [TAG_INTEGER, 1, 0x7f]);
+        assert_eq!(integer(&[0x80]), [TAG_INTEGER, 2, 0, 0x80]);
+        let encoded = integer_u64(300);
+        assert_eq!(encoded, [TAG_INTEGER, 2, 0x01, 0x2c]);
+        assert_eq!(Reader::new(&encoded).read().unwrap().u64().unwrap(), 300);
+    }
+
+    #[test]
+    fn long_form_length() {
+        let value = vec![0xab; 300];
+        let encoded = sequence(&[&octet_string(&value), &null()]);
+        assert_eq!(encoded[..4], [TAG_SEQUENCE, 0x82, 0x01, 0x32]);
+
+        let mut sequence = Reader::new(&encoded)
+            .read_tag(TAG_SEQUENCE)
+            .unwrap()
+            .reader();
+        assert_eq!(
+            sequence.read_tag(TAG_OCTET_STRING).unwrap().value,
+            &value[..]
+        );
+        assert!(sequence.read_optional(TAG_INTEGER).unwrap().is_none());
+        assert!(sequence.read_tag(TAG_NULL).unwrap().value.is_empty());
+        assert!(sequence.is_empty());
+        assert!(Reader::new(&encoded[..encoded.len() - 1]).read().is_err());
+    }
+}
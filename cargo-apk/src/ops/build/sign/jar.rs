@@ -0,0 +1,241 @@
+//! JAR signing (APK Signature Scheme v1)
+
+use super::der;
+use super::oids;
+use super::{DigestAlgorithm, SigningKey};
+use crate::ops::build::apk::ApkReader;
+use cargo::util::CargoResult;
+use failure::format_err;
+
+pub const MANIFEST_NAME: &str = "META-INF/MANIFEST.MF";
+pub const SIGNATURE_FILE_NAME: &str = "META-INF/CERT.SF";
+pub const SIGNATURE_BLOCK_NAME: &str = "META-INF/CERT.RSA";
+
+const CREATED_BY: &str = "1.0 (Android)";
+
+/// Returns true for the files produced by JAR signing which must not be part of the signature
+pub fn is_signature_file(name: &str) -> bool {
+    if !name.starts_with("META-INF/") || name["META-INF/".len()..].contains('/') {
+        return false;
+    }
+    let upper = name.to_uppercase();
+    upper == MANIFEST_NAME
+        || upper.ends_with(".SF")
+        || upper.ends_with(".RSA")
+        || upper.ends_with(".DSA")
+        || upper.ends_with(".EC")
+}
+
+/// Returns true for the signature blocks produced by JAR signing, which contain the certificates
+pub fn is_signature_block(name: &str) -> bool {
+    let upper = name.to_uppercase();
+    is_signature_file(name)
+        && (upper.ends_with(".RSA") || upper.ends_with(".DSA") || upper.ends_with(".EC"))
+}
+
+/// Returns the contents of the manifest, signature file and signature block for the APK. The
+/// APK signature schemes used in addition to JAR signing are listed in the signature file to
+/// protect against stripping of the v2+ signatures.
+pub fn sign(
+    apk: &ApkReader,
+    key: &SigningKey,
+    digest_algorithm: DigestAlgorithm,
+    signature_schemes: Option<&str>,
+) -> CargoResult<Vec<(&'static str, Vec<u8>)>> {
+    let digest_name = digest_algorithm.jar_name();
+
+    // Build the manifest which contains the digest of each entry
+    let mut manifest = Vec::new();
+    write_attribute(&mut manifest, "Manifest-Version", "1.0");
+    write_attribute(&mut manifest, "Created-By", CREATED_BY);
+    manifest.extend_from_slice(b"\r\n");
+
+    let mut entries: Vec<_> = apk
+        .entries()
+        .iter()
+        .filter(|e| !e.name.ends_with('/') && !is_signature_file(&e.name))
+        .collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut sections = Vec::new();
+    for entry in entries {
+        let mut section = Vec::new();
+        write_attribute(&mut section, "Name", &entry.name);
+        write_attribute(
+            &mut section,
+            &format!("{}-Digest", digest_name),
+            &base64::encode(&digest_algorithm.digest(&apk.read(entry)?)),
+        );
+        section.extend_from_slice(b"\r\n");
+        manifest.extend_from_slice(&section);
+        sections.push((entry.name.clone(), section));
+    }
+
+    // Build the signature file which contains the digest of the manifest and of each section
+    let mut signature_file = Vec::new();
+    write_attribute(&mut signature_file, "Signature-Version", "1.0");
+    write_attribute(&mut signature_file, "Created-By", CREATED_BY);
+    write_attribute(
+        &mut signature_file,
+        &format!("{}-Digest-Manifest", digest_name),
+        &base64::encode(&digest_algorithm.digest(&manifest)),
+    );
+    if let Some(signature_schemes) = signature_schemes {
+        write_attribute(
+            &mut signature_file,
+            "X-Android-APK-Signed",
+            signature_schemes,
+        );
+    }
+    signature_file.extend_from_slice(b"\r\n");
+    for (name, section) in sections {
+        write_attribute(&mut signature_file, "Name", &name);
+        write_attribute(
+            &mut signature_file,
+            &format!("{}-Digest", digest_name),
+            &base64::encode(&digest_algorithm.digest(&section)),
+        );
+        signature_file.extend_from_slice(b"\r\n");
+    }
+
+    let signature_block = signature_block(&signature_file, key, digest_algorithm)?;
+
+    Ok(vec![
+        (MANIFEST_NAME, manifest),
+        (SIGNATURE_FILE_NAME, signature_file),
+        (SIGNATURE_BLOCK_NAME, signature_block),
+    ])
+}
+
+/// Returns the certificates contained in a PKCS#7 signature block
+pub fn signer_certificates(signature_block: &[u8]) -> CargoResult<Vec<Vec<u8>>> {
+    let mut content_info = der::Reader::new(signature_block)
+        .read_tag(der::TAG_SEQUENCE)?
+        .reader();
+    if content_info.read_tag(der::TAG_OID)?.oid()? != oids::PKCS7_SIGNED_DATA {
+        return Err(format_err!(
+            "Signature block doesn't contain PKCS#7 signed data"
+        ));
+    }
+    let mut signed_data = content_info
+        .read_tag(der::context(0))?
+        .reader()
+        .read_tag(der::TAG_SEQUENCE)?
+        .reader();
+    signed_data.read_tag(der::TAG_INTEGER)?; // Version
+    signed_data.read_tag(der::TAG_SET)?; // Digest algorithms
+    signed_data.read_tag(der::TAG_SEQUENCE)?; // Content info
+
+    let mut certificates = Vec::new();
+    if let Some(set) = signed_data.read_optional(der::context(0))? {
+        let mut reader = set.reader();
+        while !reader.is_empty() {
+            certificates.push(reader.read_tag(der::TAG_SEQUENCE)?.raw.to_vec());
+        }
+    }
+    Ok(certificates)
+}
+
+/// Writes a manifest attribute. Lines are limited to 72 bytes and continued on the next line
+/// starting with a space.
+fn write_attribute(out: &mut Vec<u8>, name: &str, value: &str) {
+    let line = format!("{}: {}", name, value);
+    let bytes = line.as_bytes();
+    let mut start = 0;
+    let mut max_len = 72;
+    while start < bytes.len() {
+        let end = (start + max_len).min(bytes.len());
+        if start > 0 {
+            out.push(b' ');
+        }
+        out.extend_from_slice(&bytes[start..end]);
+        out.extend_from_slice(b"\r\n");
+        start = end;
+        max_len = 71;
+    }
+}
+
+/// Creates the PKCS#7 SignedData structure containing the signature of the signature file
+fn signature_block(
+    signature_file: &[u8],
+    key: &SigningKey,
+    digest_algorithm: DigestAlgorithm,
+) -> CargoResult<Vec<u8>> {
+    let signature = key.sign(digest_algorithm, signature_file)?;
+    let digest_algorithm_identifier = der::algorithm(digest_algorithm.oid());
+
+    let signer_info = der::sequence(&[
+        &der::integer_u64(1),
+        &der::sequence(&[&key.certificate.issuer, &key.certificate.serial_number]),
+        &digest_algorithm_identifier,
+        &der::algorithm(oids::RSA_ENCRYPTION),
+        &der::octet_string(&signature),
+    ]);
+
+    let signed_data = der::sequence(&[
+        &der::integer_u64(1),
+        &der::set(&[&digest_algorithm_identifier]),
+        &der::sequence(&[&der::oid(oids::PKCS7_DATA)]),
+        &der::element(der::context(0), &key.certificate.der),
+        &der::set(&[&signer_info]),
+    ]);
+
+    Ok(der::sequence(&[
+        &der::oid(oids::PKCS7_SIGNED_DATA),
+        &der::element(der::context(0), &signed_data),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::build::sign::tests::{test_apk, test_key};
+
+    #[test]
+    fn short_attribute() {
+        let mut out = Vec::new();
+        write_attribute(&mut out, "Manifest-Version", "1.0");
+        assert_eq!(out, b"Manifest-Version: 1.0\r\n");
+
+        // Exactly 72 bytes fit on a single line
+        let mut out = Vec::new();
+        write_attribute(&mut out, "Name", &"a".repeat(66));
+        assert_eq!(out.len(), 72 + 2);
+    }
+
+    #[test]
+    fn continued_attribute() {
+        let value = "a".repeat(150);
+        let mut out = Vec::new();
+        write_attribute(&mut out, "Name", &value);
+
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = text.split_terminator("\r\n").collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].len(), 72);
+        assert_eq!(lines[1].len(), 72);
+        assert!(lines[1].starts_with(' ') && lines[2].starts_with(' '));
+
+        let joined: String = lines[1..].iter().map(|line| &line[1..]).collect();
+        assert_eq!(
+            format!("{}{}", lines[0], joined),
+            format!("Name: {}", value)
+        );
+    }
+
+    #[test]
+    fn signature_block_certificates() {
+        let apk = ApkReader::from_bytes(test_apk("jar-sign")).unwrap();
+        let key = test_key();
+        let files = sign(&apk, &key, DigestAlgorithm::Sha256, Some("2, 3")).unwrap();
+        let names: Vec<_> = files.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            [MANIFEST_NAME, SIGNATURE_FILE_NAME, SIGNATURE_BLOCK_NAME]
+        );
+        assert_eq!(
+            signer_certificates(&files[2].1).unwrap(),
+            vec![key.certificate.der.clone()]
+        );
+    }
+}
@@ -0,0 +1,776 @@
+use super::der::{self, Reader};
+use super::oids;
+use crate::ops::build::util;
+use aes::{Aes128, Aes192, Aes256};
+use block_modes::block_padding::Pkcs7;
+use block_modes::{BlockMode, Cbc};
+use cargo::util::CargoResult;
+use des::TdesEde3;
+use failure::format_err;
+use hmac::{Hmac, Mac, NewMac};
+use rand::RngCore;
+use rc2::Rc2;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+const JKS_MAGIC: u32 = 0xfeed_feed;
+const JCEKS_MAGIC: u32 = 0xcece_cece;
+const JKS_VERSION: u32 = 2;
+const JKS_PRIVATE_KEY_TAG: u32 = 1;
+const JKS_TRUSTED_CERT_TAG: u32 = 2;
+
+/// Private key and certificate chain read from a keystore
+pub struct KeyEntry {
+    pub alias: String,
+    /// DER encoded PKCS#8 PrivateKeyInfo
+    pub private_key: Vec<u8>,
+    /// DER encoded certificates. The first one is the certificate of the key.
+    pub certificates: Vec<Vec<u8>>,
+}
+
+/// Reads the key entries of a JKS or PKCS#12 keystore, or of a PEM/PK8 key.
+///
+/// For PK8 and PEM keys which don't contain a certificate, the certificate is read from the
+/// `.x509.pem` file next to the key like the keys of the Android platform.
+pub fn read_key_entries(
+    path: &Path,
+    store_password: &str,
+    key_password: &str,
+) -> CargoResult<Vec<KeyEntry>> {
+    let data = fs::read(path)
+        .map_err(|e| format_err!("Unable to read keystore `{}`. {}", path.display(), e))?;
+
+    let entries = if data.starts_with(&JKS_MAGIC.to_be_bytes()) {
+        read_jks(&data, store_password, key_password)
+    } else if data.starts_with(&JCEKS_MAGIC.to_be_bytes()) {
+        Err(format_err!(
+            "JCEKS keystores are not supported. Convert the keystore to PKCS#12 using keytool."
+        ))
+    } else if is_pem(&data) {
+        read_pem_key(path, &data)
+    } else if is_pkcs12(&data) {
+        read_pkcs12(&data, store_password, key_password)
+    } else {
+        Ok(vec![KeyEntry {
+            alias: String::new(),
+            private_key: data,
+            certificates: read_certificate_file(&sibling_certificate_path(path))?,
+        }])
+    };
+
+    entries.map_err(|e| format_err!("Unable to read keystore `{}`. {}", path.display(), e))
+}
+
+/// Writes a JKS keystore containing a single private key
+pub fn write_jks(
+    path: &Path,
+    alias: &str,
+    private_key: &[u8],
+    certificates: &[&[u8]],
+    store_password: &str,
+    key_password: &str,
+) -> CargoResult<()> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&JKS_MAGIC.to_be_bytes());
+    data.extend_from_slice(&JKS_VERSION.to_be_bytes());
+    data.extend_from_slice(&1u32.to_be_bytes());
+
+    data.extend_from_slice(&JKS_PRIVATE_KEY_TAG.to_be_bytes());
+    write_java_utf(&mut data, &alias.to_lowercase());
+    let timestamp_ms = util::unix_timestamp() * 1000;
+    data.extend_from_slice(&timestamp_ms.to_be_bytes());
+
+    let protected_key = der::sequence(&[
+        &der::algorithm(oids::JKS_KEY_PROTECTOR),
+        &der::octet_string(&jks_protect_key(private_key, key_password)),
+    ]);
+    data.extend_from_slice(&(protected_key.len() as u32).to_be_bytes());
+    data.extend_from_slice(&protected_key);
+
+    data.extend_from_slice(&(certificates.len() as u32).to_be_bytes());
+    for certificate in certificates {
+        write_java_utf(&mut data, "X.509");
+        data.extend_from_slice(&(certificate.len() as u32).to_be_bytes());
+        data.extend_from_slice(certificate);
+    }
+
+    let digest = jks_integrity_digest(&data, store_password);
+    data.extend_from_slice(&digest);
+
+    fs::write(path, data)
+        .map_err(|e| format_err!("Unable to write keystore `{}`. {}", path.display(), e))?;
+    Ok(())
+}
+
+//
+// JKS
+//
+
+fn read_jks(data: &[u8], store_password: &str, key_password: &str) -> CargoResult<Vec<KeyEntry>> {
+    if data.len() < 20 {
+        return Err(format_err!("Keystore is truncated"));
+    }
+    let (content, digest) = data.split_at(data.len() - 20);
+    if jks_integrity_digest(content, store_password)[..] != *digest {
+        return Err(format_err!(
+            "Keystore was tampered with, or password was incorrect"
+        ));
+    }
+
+    let mut input = JavaInput { data: content };
+    input.u32()?; // Magic
+    let version = input.u32()?;
+    let count = input.u32()?;
+
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let tag = input.u32()?;
+        let alias = input.utf()?;
+        input.bytes(8)?; // Timestamp
+        match tag {
+            JKS_PRIVATE_KEY_TAG => {
+                let len = input.u32()? as usize;
+                let protected_key = input.bytes(len)?;
+                let mut certificates = Vec::new();
+                for _ in 0..input.u32()? {
+                    if version == JKS_VERSION {
+                        input.utf()?; // Certificate type
+                    }
+                    let len = input.u32()? as usize;
+                    certificates.push(input.bytes(len)?.to_vec());
+                }
+
+                let mut key_info = Reader::new(protected_key)
+                    .read_tag(der::TAG_SEQUENCE)?
+                    .reader();
+                let algorithm = key_info
+                    .read_tag(der::TAG_SEQUENCE)?
+                    .reader()
+                    .read()?
+                    .oid()?;
+                if algorithm != oids::JKS_KEY_PROTECTOR {
+                    return Err(format_err!(
+                        "Unsupported key protection algorithm {} for key `{}`",
+                        algorithm,
+                        alias
+                    ));
+                }
+                let encrypted = key_info.read_tag(der::TAG_OCTET_STRING)?.value;
+                let private_key = jks_recover_key(encrypted, key_password)
+                    .ok_or_else(|| format_err!("Incorrect password for key `{}`", alias))?;
+
+                entries.push(KeyEntry {
+                    alias,
+                    private_key,
+                    certificates,
+                });
+            }
+            JKS_TRUSTED_CERT_TAG => {
+                if version == JKS_VERSION {
+                    input.utf()?;
+                }
+                let len = input.u32()? as usize;
+                input.bytes(len)?;
+            }
+            _ => return Err(format_err!("Unsupported keystore entry type: {}", tag)),
+        }
+    }
+
+    Ok(entries)
+}
+
+struct JavaInput<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> JavaInput<'a> {
+    fn bytes(&mut self, len: usize) -> CargoResult<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(format_err!("Keystore is truncated"));
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> CargoResult<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn utf(&mut self) -> CargoResult<String> {
+        let b = self.bytes(2)?;
+        let len = u16::from_be_bytes([b[0], b[1]]) as usize;
+        Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+}
+
+fn write_java_utf(data: &mut Vec<u8>, value: &str) {
+    data.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    data.extend_from_slice(value.as_bytes());
+}
+
+fn utf16_be(password: &str) -> Vec<u8> {
+    password
+        .encode_utf16()
+        .flat_map(|c| c.to_be_bytes().to_vec())
+        .collect()
+}
+
+fn jks_integrity_digest(data: &[u8], store_password: &str) -> Vec<u8> {
+    let mut hasher = Sha1::new();
+    hasher.update(utf16_be(store_password));
+    hasher.update(b"Mighty Aphrodite");
+    hasher.update(data);
+    hasher.finalize().to_vec()
+}
+
+/// Produces the key stream used by the proprietary JKS key protection algorithm
+fn jks_key_stream(salt: &[u8], password: &[u8], len: usize) -> Vec<u8> {
+    let mut stream = Vec::with_capacity(len + 20);
+    let mut digest = salt.to_vec();
+    while stream.len() < len {
+        let mut hasher = Sha1::new();
+        hasher.update(password);
+        hasher.update(&digest);
+        digest = hasher.finalize().to_vec();
+        stream.extend_from_slice(&digest);
+    }
+    stream.truncate(len);
+    stream
+}
+
+fn jks_recover_key(protected: &[u8], key_password: &str) -> Option<Vec<u8>> {
+    if protected.len() < 40 {
+        return None;
+    }
+    let password = utf16_be(key_password);
+    let (salt, rest) = protected.split_at(20);
+    let (encrypted, check) = rest.split_at(rest.len() - 20);
+
+    let key: Vec<u8> = jks_key_stream(salt, &password, encrypted.len())
+        .iter()
+        .zip(encrypted)
+        .map(|(a, b)| a ^ b)
+        .collect();
+
+    let mut hasher = Sha1::new();
+    hasher.update(&password);
+    hasher.update(&key);
+    if hasher.finalize()[..] == *check {
+        Some(key)
+    } else {
+        None
+    }
+}
+
+fn jks_protect_key(key: &[u8], key_password: &str) -> Vec<u8> {
+    let password = utf16_be(key_password);
+    let mut salt = [0u8; 20];
+    rand::rngs::OsRng.fill_bytes(&mut salt);
+
+    let mut protected = salt.to_vec();
+    protected.extend(
+        jks_key_stream(&salt, &password, key.len())
+            .iter()
+            .zip(key)
+            .map(|(a, b)| a ^ b),
+    );
+
+    let mut hasher = Sha1::new();
+    hasher.update(&password);
+    hasher.update(key);
+    protected.extend_from_slice(&hasher.finalize());
+    protected
+}
+
+//
+// PKCS#12
+//
+
+fn is_pkcs12(data: &[u8]) -> bool {
+    // A PFX starts with version 3 while a PKCS#8 key starts with version 0
+    Reader::new(data)
+        .read_tag(der::TAG_SEQUENCE)
+        .and_then(|pfx| pfx.reader().read())
+        .and_then(|version| version.u64())
+        .map(|version| version == 3)
+        .unwrap_or(false)
+}
+
+fn read_pkcs12(
+    data: &[u8],
+    store_password: &str,
+    key_password: &str,
+) -> CargoResult<Vec<KeyEntry>> {
+    let mut pfx = Reader::new(data).read_tag(der::TAG_SEQUENCE)?.reader();
+    pfx.read_tag(der::TAG_INTEGER)?;
+    let auth_safe = read_data_content_info(&mut pfx.read_tag(der::TAG_SEQUENCE)?.reader())?;
+
+    if let Some(mac_data) = pfx.read_optional(der::TAG_SEQUENCE)? {
+        verify_pkcs12_mac(&mut mac_data.reader(), auth_safe, store_password)?;
+    }
+
+    // Collect all bags. Keys and certificates are matched using their local key id.
+    let mut keys = Vec::new();
+    let mut certificates = Vec::new();
+    let mut content_infos = Reader::new(auth_safe).read_tag(der::TAG_SEQUENCE)?.reader();
+    while !content_infos.is_empty() {
+        let mut content_info = content_infos.read_tag(der::TAG_SEQUENCE)?.reader();
+        let content_type = content_info.read()?.oid()?;
+        let safe_contents = match content_type.as_str() {
+            oids::PKCS7_DATA => content_info
+                .read_tag(der::context(0))?
+                .reader()
+                .read_tag(der::TAG_OCTET_STRING)?
+                .value
+                .to_vec(),
+            oids::PKCS7_ENCRYPTED_DATA => {
+                let mut encrypted_data = content_info
+                    .read_tag(der::context(0))?
+                    .reader()
+                    .read_tag(der::TAG_SEQUENCE)?
+                    .reader();
+                encrypted_data.read_tag(der::TAG_INTEGER)?;
+                let mut encrypted_content_info =
+                    encrypted_data.read_tag(der::TAG_SEQUENCE)?.reader();
+                encrypted_content_info.read()?; // Content type
+                let algorithm = encrypted_content_info.read_tag(der::TAG_SEQUENCE)?;
+                let encrypted = encrypted_content_info
+                    .read_tag(der::context_primitive(0))?
+                    .value;
+                decrypt(algorithm, encrypted, store_password)?
+            }
+            _ => continue,
+        };
+
+        let mut bags = Reader::new(&safe_contents)
+            .read_tag(der::TAG_SEQUENCE)?
+            .reader();
+        while !bags.is_empty() {
+            let mut bag = bags.read_tag(der::TAG_SEQUENCE)?.reader();
+            let bag_id = bag.read()?.oid()?;
+            let mut value = bag.read_tag(der::context(0))?.reader();
+            let (friendly_name, local_key_id) = match bag.read_optional(der::TAG_SET)? {
+                Some(attributes) => read_bag_attributes(&mut attributes.reader())?,
+                None => (None, None),
+            };
+
+            match bag_id.as_str() {
+                oids::PKCS12_KEY_BAG => {
+                    let private_key = value.read_tag(der::TAG_SEQUENCE)?.raw.to_vec();
+                    keys.push((friendly_name, local_key_id, private_key));
+                }
+                oids::PKCS12_SHROUDED_KEY_BAG => {
+                    let mut key_info = value.read_tag(der::TAG_SEQUENCE)?.reader();
+                    let algorithm = key_info.read_tag(der::TAG_SEQUENCE)?;
+                    let encrypted = key_info.read_tag(der::TAG_OCTET_STRING)?.value;
+                    let private_key = decrypt(algorithm, encrypted, key_password)?;
+                    keys.push((friendly_name, local_key_id, private_key));
+                }
+                oids::PKCS12_CERT_BAG => {
+                    let mut cert_bag = value.read_tag(der::TAG_SEQUENCE)?.reader();
+                    if cert_bag.read()?.oid()? != oids::PKCS12_X509_CERTIFICATE {
+                        continue;
+                    }
+                    let certificate = cert_bag
+                        .read_tag(der::context(0))?
+                        .reader()
+                        .read_tag(der::TAG_OCTET_STRING)?
+                        .value
+                        .to_vec();
+                    certificates.push((local_key_id, certificate));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(keys
+        .into_iter()
+        .map(|(friendly_name, local_key_id, private_key)| KeyEntry {
+            alias: friendly_name.unwrap_or_default(),
+            private_key,
+            certificates: certificates
+                .iter()
+                .filter(|(id, _)| local_key_id.is_none() || *id == local_key_id)
+                .map(|(_, certificate)| certificate.clone())
+                .collect(),
+        })
+        .collect())
+}
+
+/// Returns the content of a ContentInfo of type data
+fn read_data_content_info<'a>(content_info: &mut Reader<'a>) -> CargoResult<&'a [u8]> {
+    if content_info.read()?.oid()? != oids::PKCS7_DATA {
+        return Err(format_err!("Unsupported PKCS#12 content type"));
+    }
+    Ok(content_info
+        .read_tag(der::context(0))?
+        .reader()
+        .read_tag(der::TAG_OCTET_STRING)?
+        .value)
+}
+
+/// Returns the friendly name and local key id of a bag
+fn read_bag_attributes(attributes: &mut Reader) -> CargoResult<(Option<String>, Option<Vec<u8>>)> {
+    let mut friendly_name = None;
+    let mut local_key_id = None;
+    while !attributes.is_empty() {
+        let mut attribute = attributes.read_tag(der::TAG_SEQUENCE)?.reader();
+        let attribute_type = attribute.read()?.oid()?;
+        let mut values = attribute.read_tag(der::TAG_SET)?.reader();
+        match attribute_type.as_str() {
+            oids::PKCS9_FRIENDLY_NAME => {
+                let value = values.read_tag(der::TAG_BMP_STRING)?.value;
+                let chars: Vec<u16> = value
+                    .chunks(2)
+                    .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]))
+                    .collect();
+                friendly_name = Some(String::from_utf16_lossy(&chars));
+            }
+            oids::PKCS9_LOCAL_KEY_ID => {
+                local_key_id = Some(values.read_tag(der::TAG_OCTET_STRING)?.value.to_vec());
+            }
+            _ => {}
+        }
+    }
+    Ok((friendly_name, local_key_id))
+}
+
+fn verify_pkcs12_mac(mac_data: &mut Reader, auth_safe: &[u8], password: &str) -> CargoResult<()> {
+    let mut digest_info = mac_data.read_tag(der::TAG_SEQUENCE)?.reader();
+    let digest_algorithm = digest_info
+        .read_tag(der::TAG_SEQUENCE)?
+        .reader()
+        .read()?
+        .oid()?;
+    let expected = digest_info.read_tag(der::TAG_OCTET_STRING)?.value;
+    let salt = mac_data.read_tag(der::TAG_OCTET_STRING)?.value;
+    let iterations = match mac_data.read_optional(der::TAG_INTEGER)? {
+        Some(iterations) => iterations.u64()?,
+        None => 1,
+    };
+
+    let password = bmp_password(password);
+    let valid = match digest_algorithm.as_str() {
+        oids::SHA1 => {
+            let key = pkcs12_kdf::<Sha1>(&password, salt, 3, iterations, 20);
+            let mut mac = Hmac::<Sha1>::new_varkey(&key).unwrap();
+            mac.update(auth_safe);
+            mac.verify(expected).is_ok()
+        }
+        oids::SHA256 => {
+            let key = pkcs12_kdf::<Sha256>(&password, salt, 3, iterations, 32);
+            let mut mac = Hmac::<Sha256>::new_varkey(&key).unwrap();
+            mac.update(auth_safe);
+            mac.verify(expected).is_ok()
+        }
+        _ => {
+            return Err(format_err!(
+                "Unsupported PKCS#12 MAC algorithm: {}",
+                digest_algorithm
+            ))
+        }
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format_err!(
+            "Keystore was tampered with, or password was incorrect"
+        ))
+    }
+}
+
+/// Password encoding used by the PKCS#12 key derivation function
+fn bmp_password(password: &str) -> Vec<u8> {
+    let mut bytes = utf16_be(password);
+    bytes.extend_from_slice(&[0, 0]);
+    bytes
+}
+
+/// Key derivation function defined in RFC 7292 appendix B.2
+fn pkcs12_kdf<D: Digest>(
+    password: &[u8],
+    salt: &[u8],
+    id: u8,
+    iterations: u64,
+    len: usize,
+) -> Vec<u8> {
+    const V: usize = 64;
+    let fill = |input: &[u8]| -> Vec<u8> {
+        let len = input.len() + (V - input.len() % V) % V;
+        input.iter().cycle().take(len).cloned().collect()
+    };
+
+    let mut i = fill(salt);
+    i.extend(fill(password));
+
+    let mut out = Vec::with_capacity(len);
+    while out.len() < len {
+        let mut a = D::new().chain([id; V]).chain(&i).finalize().to_vec();
+        for _ in 1..iterations {
+            a = D::digest(&a).to_vec();
+        }
+        out.extend_from_slice(&a);
+
+        let b: Vec<u8> = a.iter().cycle().take(V).cloned().collect();
+        for block in i.chunks_mut(V) {
+            let mut carry = 1u16;
+            for (x, y) in block.iter_mut().zip(&b).rev() {
+                let sum = u16::from(*x) + u16::from(*y) + carry;
+                *x = sum as u8;
+                carry = sum >> 8;
+            }
+        }
+    }
+    out.truncate(len);
+    out
+}
+
+/// Decrypts data protected with one of the password based encryption schemes used by keytool
+/// and openssl
+fn decrypt(algorithm: der::Element, data: &[u8], password: &str) -> CargoResult<Vec<u8>> {
+    let mut algorithm = algorithm.reader();
+    let oid = algorithm.read()?.oid()?;
+    let mut params = algorithm.read_tag(der::TAG_SEQUENCE)?.reader();
+
+    let decrypted = match oid.as_str() {
+        oids::PBE_SHA1_3DES | oids::PBE_SHA1_RC2_128 | oids::PBE_SHA1_RC2_40 => {
+            let salt = params.read_tag(der::TAG_OCTET_STRING)?.value;
+            let iterations = params.read()?.u64()?;
+            let password = bmp_password(password);
+            let iv = pkcs12_kdf::<Sha1>(&password, salt, 2, iterations, 8);
+            match oid.as_str() {
+                oids::PBE_SHA1_3DES => {
+                    let key = pkcs12_kdf::<Sha1>(&password, salt, 1, iterations, 24);
+                    Cbc::<TdesEde3, Pkcs7>::new_var(&key, &iv)
+                        .unwrap()
+                        .decrypt_vec(data)
+                }
+                oids::PBE_SHA1_RC2_128 => {
+                    let key = pkcs12_kdf::<Sha1>(&password, salt, 1, iterations, 16);
+                    Cbc::<Rc2, Pkcs7>::new(
+                        Rc2::new_with_eff_key_len(&key, 128),
+                        iv.as_slice().into(),
+                    )
+                    .decrypt_vec(data)
+                }
+                _ => {
+                    let key = pkcs12_kdf::<Sha1>(&password, salt, 1, iterations, 5);
+                    Cbc::<Rc2, Pkcs7>::new(
+                        Rc2::new_with_eff_key_len(&key, 40),
+                        iv.as_slice().into(),
+                    )
+                    .decrypt_vec(data)
+                }
+            }
+        }
+        oids::PBES2 => {
+            let mut kdf = params.read_tag(der::TAG_SEQUENCE)?.reader();
+            if kdf.read()?.oid()? != oids::PBKDF2 {
+                return Err(format_err!("Unsupported PBES2 key derivation function"));
+            }
+            let mut kdf_params = kdf.read_tag(der::TAG_SEQUENCE)?.reader();
+            let salt = kdf_params.read_tag(der::TAG_OCTET_STRING)?.value;
+            let iterations = kdf_params.read()?.u64()? as u32;
+            kdf_params.read_optional(der::TAG_INTEGER)?; // Key length
+            let prf = match kdf_params.read_optional(der::TAG_SEQUENCE)? {
+                Some(prf) => prf.reader().read()?.oid()?,
+                None => oids::HMAC_SHA1.to_owned(),
+            };
+
+            let mut scheme = params.read_tag(der::TAG_SEQUENCE)?.reader();
+            let cipher = scheme.read()?.oid()?;
+            let iv = scheme.read_tag(der::TAG_OCTET_STRING)?.value;
+            let key_len = match cipher.as_str() {
+                oids::AES_128_CBC => 16,
+                oids::AES_192_CBC => 24,
+                oids::AES_256_CBC => 32,
+                _ => return Err(format_err!("Unsupported PBES2 cipher: {}", cipher)),
+            };
+
+            let mut key = vec![0u8; key_len];
+            match prf.as_str() {
+                oids::HMAC_SHA1 => {
+                    pbkdf2::pbkdf2::<Hmac<Sha1>>(password.as_bytes(), salt, iterations, &mut key)
+                }
+                oids::HMAC_SHA256 => {
+                    pbkdf2::pbkdf2::<Hmac<Sha256>>(password.as_bytes(), salt, iterations, &mut key)
+                }
+                _ => return Err(format_err!("Unsupported PBKDF2 function: {}", prf)),
+            }
+
+            let invalid_iv = |_| format_err!("Invalid PBES2 initialization vector");
+            match key_len {
+                16 => Cbc::<Aes128, Pkcs7>::new_var(&key, iv)
+                    .map_err(invalid_iv)?
+                    .decrypt_vec(data),
+                24 => Cbc::<Aes192, Pkcs7>::new_var(&key, iv)
+                    .map_err(invalid_iv)?
+                    .decrypt_vec(data),
+                _ => Cbc::<Aes256, Pkcs7>::new_var(&key, iv)
+                    .map_err(invalid_iv)?
+                    .decrypt_vec(data),
+            }
+        }
+        _ => return Err(format_err!("Unsupported encryption algorithm: {}", oid)),
+    };
+
+    decrypted.map_err(|_| format_err!("Unable to decrypt keystore. The password is incorrect."))
+}
+
+//
+// PEM and PK8
+//
+
+fn read_pem_key(path: &Path, data: &[u8]) -> CargoResult<Vec<KeyEntry>> {
+    let blocks = read_pem_blocks(data)?;
+    let private_key = blocks
+        .iter()
+        .find(|(label, _)| label == "PRIVATE KEY" || label == "RSA PRIVATE KEY")
+        .map(|(label, der)| {
+            if label == "RSA PRIVATE KEY" {
+                // Wrap the PKCS#1 key in a PKCS#8 structure
+                der::sequence(&[
+                    &der::integer_u64(0),
+                    &der::algorithm(oids::RSA_ENCRYPTION),
+                    &der::octet_string(der),
+                ])
+            } else {
+                der.clone()
+            }
+        })
+        .ok_or_else(|| format_err!("No unencrypted private key found"))?;
+
+    let mut certificates: Vec<Vec<u8>> = blocks
+        .into_iter()
+        .filter(|(label, _)| label == "CERTIFICATE")
+        .map(|(_, der)| der)
+        .collect();
+    if certificates.is_empty() {
+        certificates = read_certificate_file(&sibling_certificate_path(path))?;
+    }
+
+    Ok(vec![KeyEntry {
+        alias: String::new(),
+        private_key,
+        certificates,
+    }])
+}
+
+/// Returns the path of the certificate for a key. Eg. `platform.x509.pem` for `platform.pk8`.
+fn sibling_certificate_path(key_path: &Path) -> std::path::PathBuf {
+    key_path.with_extension("x509.pem")
+}
+
+/// Reads a PEM or DER encoded certificate
+fn read_certificate_file(path: &Path) -> CargoResult<Vec<Vec<u8>>> {
+    let data = fs::read(path)
+        .map_err(|e| format_err!("Unable to read certificate `{}`. {}", path.display(), e))?;
+    if is_pem(&data) {
+        Ok(read_pem_blocks(&data)?
+            .into_iter()
+            .filter(|(label, _)| label == "CERTIFICATE")
+            .map(|(_, der)| der)
+            .collect())
+    } else {
+        Ok(vec![data])
+    }
+}
+
+/// PEM files written by openssl may start with attributes describing the blocks
+fn is_pem(data: &[u8]) -> bool {
+    data.windows(11).any(|w| w == b"-----BEGIN ")
+}
+
+/// Returns the label and decoded content of each block of a PEM file
+fn read_pem_blocks(data: &[u8]) -> CargoResult<Vec<(String, Vec<u8>)>> {
+    let text = String::from_utf8_lossy(data);
+    let mut blocks = Vec::new();
+    let mut current: Option<(String, String)> = None;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with("-----BEGIN ") && line.ends_with("-----") {
+            let label = &line["-----BEGIN ".len()..line.len() - 5];
+            current = Some((label.to_owned(), String::new()));
+        } else if line.starts_with("-----END ") {
+            if let Some((label, content)) = current.take() {
+                let der = base64::decode(&content)
+                    .map_err(|e| format_err!("Invalid PEM block `{}`. {}", label, e))?;
+                blocks.push((label, der));
+            }
+        } else if let Some((_, content)) = current.as_mut() {
+            if !line.contains(':') {
+                content.push_str(line);
+            }
+        }
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::build::sign::tests::fixture;
+    use crate::ops::build::tempfile::TempDir;
+
+    fn private_key() -> Vec<u8> {
+        fs::read(fixture("test.pk8")).unwrap()
+    }
+
+    fn certificate() -> Vec<u8> {
+        read_certificate_file(&fixture("test.x509.pem"))
+            .unwrap()
+            .remove(0)
+    }
+
+    #[test]
+    fn pkcs12() {
+        let path = fixture("test.p12");
+        let entries = read_key_entries(&path, "android", "android").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].alias, "test");
+        assert_eq!(entries[0].private_key, private_key());
+        assert_eq!(entries[0].certificates, [certificate()]);
+
+        assert!(read_key_entries(&path, "wrong", "wrong").is_err());
+    }
+
+    #[test]
+    fn pem_and_pk8() {
+        for name in &["test.pem", "test.pk8"] {
+            let entries = read_key_entries(&fixture(name), "", "").unwrap();
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].alias, "");
+            assert_eq!(entries[0].private_key, private_key());
+            assert_eq!(entries[0].certificates, [certificate()]);
+        }
+    }
+
+    #[test]
+    fn jks_round_trip() {
+        let dir = TempDir::new("jks-round-trip").unwrap();
+        let path = dir.path.join("test.jks");
+        write_jks(
+            &path,
+            "Test",
+            &private_key(),
+            &[&certificate()],
+            "store password",
+            "key password",
+        )
+        .unwrap();
+
+        let entries = read_key_entries(&path, "store password", "key password").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].alias, "test");
+        assert_eq!(entries[0].private_key, private_key());
+        assert_eq!(entries[0].certificates, [certificate()]);
+
+        assert!(read_key_entries(&path, "wrong", "key password").is_err());
+        assert!(read_key_entries(&path, "store password", "wrong").is_err());
+    }
+}
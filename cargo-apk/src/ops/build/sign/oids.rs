@@ -0,0 +1,38 @@
+//! Object identifiers used by the signing code
+
+pub const RSA_ENCRYPTION: &str = "1.2.840.113549.1.1.1";
+pub const SHA256_WITH_RSA_ENCRYPTION: &str = "1.2.840.113549.1.1.11";
+pub const SHA1: &str = "1.3.14.3.2.26";
+pub const SHA256: &str = "2.16.840.1.101.3.4.2.1";
+
+pub const COMMON_NAME: &str = "2.5.4.3";
+pub const COUNTRY_NAME: &str = "2.5.4.6";
+pub const LOCALITY_NAME: &str = "2.5.4.7";
+pub const STATE_OR_PROVINCE_NAME: &str = "2.5.4.8";
+pub const ORGANIZATION_NAME: &str = "2.5.4.10";
+pub const ORGANIZATIONAL_UNIT_NAME: &str = "2.5.4.11";
+
+pub const PKCS7_DATA: &str = "1.2.840.113549.1.7.1";
+pub const PKCS7_SIGNED_DATA: &str = "1.2.840.113549.1.7.2";
+pub const PKCS7_ENCRYPTED_DATA: &str = "1.2.840.113549.1.7.6";
+
+pub const PKCS12_KEY_BAG: &str = "1.2.840.113549.1.12.10.1.1";
+pub const PKCS12_SHROUDED_KEY_BAG: &str = "1.2.840.113549.1.12.10.1.2";
+pub const PKCS12_CERT_BAG: &str = "1.2.840.113549.1.12.10.1.3";
+pub const PKCS12_X509_CERTIFICATE: &str = "1.2.840.113549.1.9.22.1";
+pub const PKCS9_FRIENDLY_NAME: &str = "1.2.840.113549.1.9.20";
+pub const PKCS9_LOCAL_KEY_ID: &str = "1.2.840.113549.1.9.21";
+
+pub const PBE_SHA1_3DES: &str = "1.2.840.113549.1.12.1.3";
+pub const PBE_SHA1_RC2_128: &str = "1.2.840.113549.1.12.1.5";
+pub const PBE_SHA1_RC2_40: &str = "1.2.840.113549.1.12.1.6";
+pub const PBES2: &str = "1.2.840.113549.1.5.13";
+pub const PBKDF2: &str = "1.2.840.113549.1.5.12";
+pub const HMAC_SHA1: &str = "1.2.840.113549.2.7";
+pub const HMAC_SHA256: &str = "1.2.840.113549.2.9";
+pub const AES_128_CBC: &str = "2.16.840.1.101.3.4.1.2";
+pub const AES_192_CBC: &str = "2.16.840.1.101.3.4.1.22";
+pub const AES_256_CBC: &str = "2.16.840.1.101.3.4.1.42";
+
+/// Algorithm used by the JKS keystore to protect private keys
+pub const JKS_KEY_PROTECTOR: &str = "1.3.6.1.4.1.42.2.17.1.1";
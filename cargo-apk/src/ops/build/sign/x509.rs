@@ -0,0 +1,241 @@
+use super::der::{self, Reader};
+use super::oids;
+use crate::ops::build::util;
+use cargo::util::CargoResult;
+use failure::format_err;
+use rand::RngCore;
+use rsa::{BigUint, Hash, PaddingScheme, PublicKeyParts, RSAPrivateKey};
+use sha2::{Digest, Sha256};
+
+/// Fields of an X.509 certificate needed to sign an APK
+pub struct Certificate {
+    /// DER encoding of the certificate
+    pub der: Vec<u8>,
+    /// DER encoding of the issuer name
+    pub issuer: Vec<u8>,
+    /// DER encoding of the subject name
+    pub subject: Vec<u8>,
+    /// DER encoding of the serial number
+    pub serial_number: Vec<u8>,
+    /// DER encoding of the SubjectPublicKeyInfo
+    pub public_key_info: Vec<u8>,
+}
+
+impl Certificate {
+    pub fn from_der(der: Vec<u8>) -> CargoResult<Certificate> {
+        let invalid = |e| format_err!("Invalid X.509 certificate. {}", e);
+
+        let (issuer, subject, serial_number, public_key_info) = {
+            let mut certificate = Reader::new(&der).read_tag(der::TAG_SEQUENCE)?.reader();
+            let mut tbs = certificate.read_tag(der::TAG_SEQUENCE)?.reader();
+            tbs.read_optional(der::context(0))?;
+            let serial_number = tbs.read_tag(der::TAG_INTEGER).map_err(invalid)?.raw;
+            tbs.read_tag(der::TAG_SEQUENCE).map_err(invalid)?; // Signature algorithm
+            let issuer = tbs.read_tag(der::TAG_SEQUENCE).map_err(invalid)?.raw;
+            tbs.read_tag(der::TAG_SEQUENCE).map_err(invalid)?; // Validity
+            let subject = tbs.read_tag(der::TAG_SEQUENCE).map_err(invalid)?.raw;
+            let public_key_info = tbs.read_tag(der::TAG_SEQUENCE).map_err(invalid)?.raw;
+            (
+                issuer.to_vec(),
+                subject.to_vec(),
+                serial_number.to_vec(),
+                public_key_info.to_vec(),
+            )
+        };
+
+        Ok(Certificate {
+            der,
+            issuer,
+            subject,
+            serial_number,
+            public_key_info,
+        })
+    }
+
+    /// Returns the subject name in the `CN=..., O=...` form
+    pub fn subject_name(&self) -> CargoResult<String> {
+        let mut components = Vec::new();
+        let mut rdns = Reader::new(&self.subject)
+            .read_tag(der::TAG_SEQUENCE)?
+            .reader();
+        while !rdns.is_empty() {
+            let mut attributes = rdns.read_tag(der::TAG_SET)?.reader();
+            while !attributes.is_empty() {
+                let mut attribute = attributes.read_tag(der::TAG_SEQUENCE)?.reader();
+                let oid = attribute.read_tag(der::TAG_OID)?.oid()?;
+                let value = attribute.read()?;
+                let name = match oid.as_str() {
+                    oids::COMMON_NAME => "CN".to_owned(),
+                    oids::COUNTRY_NAME => "C".to_owned(),
+                    oids::LOCALITY_NAME => "L".to_owned(),
+                    oids::STATE_OR_PROVINCE_NAME => "ST".to_owned(),
+                    oids::ORGANIZATION_NAME => "O".to_owned(),
+                    oids::ORGANIZATIONAL_UNIT_NAME => "OU".to_owned(),
+                    _ => oid,
+                };
+                components.push(format!("{}={}", name, String::from_utf8_lossy(value.value)));
+            }
+        }
+        Ok(components.join(", "))
+    }
+
+    /// Returns the SHA-256 digest of the certificate as colon separated hexadecimal bytes
+    pub fn sha256_fingerprint(&self) -> String {
+        Sha256::digest(&self.der)
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    /// Creates a self-signed certificate for the key. Used for the debug keystore.
+    pub fn self_signed(
+        key: &RSAPrivateKey,
+        distinguished_name: &[(&str, &str)],
+        validity_days: u64,
+    ) -> CargoResult<Certificate> {
+        let mut serial = [0u8; 8];
+        rand::rngs::OsRng.fill_bytes(&mut serial);
+        serial[0] &= 0x7f;
+
+        let name_components: Vec<Vec<u8>> = distinguished_name
+            .iter()
+            .map(|(attribute, value)| {
+                let oid = match *attribute {
+                    "C" => oids::COUNTRY_NAME,
+                    "O" => oids::ORGANIZATION_NAME,
+                    "CN" => oids::COMMON_NAME,
+                    _ => unreachable!("Unsupported name attribute"),
+                };
+                let value = der::element(der::TAG_PRINTABLE_STRING, value.as_bytes());
+                der::set(&[&der::sequence(&[&der::oid(oid), &value])])
+            })
+            .collect();
+        let name_components: Vec<&[u8]> = name_components.iter().map(|c| &c[..]).collect();
+        let name = der::sequence(&name_components);
+
+        let now = util::unix_timestamp();
+        let validity =
+            der::sequence(&[&encode_time(now), &encode_time(now + validity_days * 86400)]);
+
+        let public_key = der::sequence(&[
+            &der::integer(&key.n().to_bytes_be()),
+            &der::integer(&key.e().to_bytes_be()),
+        ]);
+        let public_key_info = der::sequence(&[
+            &der::algorithm(oids::RSA_ENCRYPTION),
+            &der::bit_string(&public_key),
+        ]);
+
+        let signature_algorithm = der::algorithm(oids::SHA256_WITH_RSA_ENCRYPTION);
+        let tbs = der::sequence(&[
+            &der::element(der::context(0), &der::integer_u64(2)),
+            &der::integer(&serial),
+            &signature_algorithm,
+            &name,
+            &validity,
+            &name,
+            &public_key_info,
+        ]);
+
+        let signature = key
+            .sign(
+                PaddingScheme::new_pkcs1v15_sign(Some(Hash::SHA2_256)),
+                &Sha256::digest(&tbs),
+            )
+            .map_err(|e| format_err!("Unable to sign certificate. {}", e))?;
+
+        Certificate::from_der(der::sequence(&[
+            &tbs,
+            &signature_algorithm,
+            &der::bit_string(&signature),
+        ]))
+    }
+}
+
+/// Encodes the PKCS#8 PrivateKeyInfo of an RSA key
+pub fn encode_private_key(key: &RSAPrivateKey) -> Vec<u8> {
+    let one = BigUint::from(1u32);
+    let p = &key.primes()[0];
+    let q = &key.primes()[1];
+    let dp = key.d() % (p - &one);
+    let dq = key.d() % (q - &one);
+    // p is prime so q^(p - 2) is the inverse of q modulo p
+    let q_inv = q.modpow(&(p - BigUint::from(2u32)), p);
+
+    let rsa_private_key = der::sequence(&[
+        &der::integer_u64(0),
+        &der::integer(&key.n().to_bytes_be()),
+        &der::integer(&key.e().to_bytes_be()),
+        &der::integer(&key.d().to_bytes_be()),
+        &der::integer(&p.to_bytes_be()),
+        &der::integer(&q.to_bytes_be()),
+        &der::integer(&dp.to_bytes_be()),
+        &der::integer(&dq.to_bytes_be()),
+        &der::integer(&q_inv.to_bytes_be()),
+    ]);
+    der::sequence(&[
+        &der::integer_u64(0),
+        &der::algorithm(oids::RSA_ENCRYPTION),
+        &der::octet_string(&rsa_private_key),
+    ])
+}
+
+/// Encodes a time as UTCTime or GeneralizedTime depending on the year as required by RFC 5280
+fn encode_time(timestamp: u64) -> Vec<u8> {
+    let (year, month, day, hour, minute, second) = util::civil_from_unix_timestamp(timestamp);
+    if year < 2050 {
+        let value = format!(
+            "{:02}{:02}{:02}{:02}{:02}{:02}Z",
+            year % 100,
+            month,
+            day,
+            hour,
+            minute,
+            second
+        );
+        der::element(der::TAG_UTC_TIME, value.as_bytes())
+    } else {
+        let value = format!(
+            "{:04}{:02}{:02}{:02}{:02}{:02}Z",
+            year, month, day, hour, minute, second
+        );
+        der::element(der::TAG_GENERALIZED_TIME, value.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::build::sign::tests::{fixture, test_key};
+    use std::fs;
+
+    #[test]
+    fn fixture_certificate() {
+        let certificate = test_key().certificate;
+        assert_eq!(certificate.subject_name().unwrap(), "CN=cargo-apk test");
+        assert_eq!(certificate.issuer, certificate.subject);
+        // `openssl x509 -in test.x509.pem -noout -fingerprint -sha256`
+        assert_eq!(
+            certificate.sha256_fingerprint(),
+            "4A:02:1E:18:0F:12:9C:B3:BD:8D:1C:7B:27:A2:03:46:\
+             15:65:E9:CD:F8:78:8D:49:80:E8:77:AA:9E:12:16:42"
+        );
+    }
+
+    #[test]
+    fn self_signed() {
+        let private_key = fs::read(fixture("test.pk8")).unwrap();
+        let key = RSAPrivateKey::from_pkcs8(&private_key).unwrap();
+        assert_eq!(encode_private_key(&key), private_key);
+
+        let certificate =
+            Certificate::self_signed(&key, &[("C", "US"), ("CN", "Test")], 1).unwrap();
+        assert_eq!(certificate.subject_name().unwrap(), "C=US, CN=Test");
+        assert_eq!(certificate.issuer, certificate.subject);
+        assert_eq!(
+            certificate.public_key_info,
+            test_key().certificate.public_key_info
+        );
+    }
+}
@@ -0,0 +1,367 @@
+//! Size report of the built APKs, by entry and, for the Rust shared libraries, by crate and symbol
+
+use super::apk::ApkReader;
+use super::compile::{SharedLibraries, SharedLibrary};
+use super::elf::{Elf, SymbolTable};
+use super::BuildResult;
+use cargo::core::TargetKind;
+use cargo::util::CargoResult;
+use failure::format_err;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Number of entries and symbols listed by the table report. The JSON report lists all of them.
+const TABLE_ROW_COUNT: usize = 20;
+
+/// Crate name of symbols which are not mangled Rust symbols
+const NON_RUST_CRATE: &str = "[non-Rust]";
+
+/// Output format of the size report
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SizeReportFormat {
+    Table,
+    Json,
+}
+
+#[derive(Serialize)]
+struct ApkReport {
+    target: String,
+    abi: String,
+    file_name: String,
+    size: u64,
+    categories: BTreeMap<&'static str, EntrySizes>,
+    entries: Vec<EntryReport>,
+    libraries: Vec<LibraryReport>,
+}
+
+#[derive(Default, Serialize)]
+struct EntrySizes {
+    compressed_size: u64,
+    uncompressed_size: u64,
+}
+
+#[derive(Serialize)]
+struct EntryReport {
+    name: String,
+    category: &'static str,
+    #[serde(flatten)]
+    sizes: EntrySizes,
+}
+
+#[derive(Serialize)]
+struct LibraryReport {
+    entry: String,
+    /// Whether the full symbol table was available, or only the exported symbols
+    full_symbol_table: bool,
+    symbols_size: u64,
+    crates: Vec<CrateReport>,
+    symbols: Vec<SymbolReport>,
+}
+
+#[derive(Serialize)]
+struct CrateReport {
+    name: String,
+    size: u64,
+    symbol_count: usize,
+}
+
+#[derive(Serialize)]
+struct SymbolReport {
+    name: String,
+    #[serde(rename = "crate")]
+    crate_name: String,
+    size: u64,
+}
+
+/// Prints the size report of the APKs and split APKs of the build
+pub fn print(
+    build_result: &BuildResult,
+    shared_libraries: &SharedLibraries,
+    format: SizeReportFormat,
+) -> CargoResult<()> {
+    let mut apks = Vec::new();
+    for (target, path) in &build_result.target_to_apk_map {
+        apks.push((target, "universal", path));
+    }
+    for (target, split_apks) in &build_result.target_to_split_apks_map {
+        for (build_target, path) in split_apks {
+            apks.push((target, build_target.android_abi(), path));
+        }
+    }
+
+    let mut reports = Vec::new();
+    for ((kind, name), abi, path) in apks {
+        // The Rust shared library of the target is the one produced by the shared library
+        // executor, as opposed to the C++ standard library
+        let rust_libraries = shared_libraries
+            .shared_libraries
+            .iter_all()
+            .filter(|(target, _)| target.kind() == kind && target.name() == name)
+            .flat_map(|(_, libraries)| libraries)
+            .filter(|library| library.filename == format!("lib{}.so", name))
+            .collect::<Vec<_>>();
+        let target = match kind {
+            TargetKind::ExampleBin => format!("example {}", name),
+            _ => name.clone(),
+        };
+        reports.push(apk_report(target, abi, path, &rust_libraries)?);
+    }
+
+    match format {
+        SizeReportFormat::Table => {
+            for report in &reports {
+                print_table(report);
+            }
+        }
+        SizeReportFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&reports)
+                .map_err(|e| format_err!("Unable to serialize the size report. {}", e))?
+        ),
+    }
+    Ok(())
+}
+
+fn apk_report(
+    target: String,
+    abi: &str,
+    path: &Path,
+    rust_libraries: &[&SharedLibrary],
+) -> CargoResult<ApkReport> {
+    let apk = ApkReader::open(path)?;
+
+    let mut categories = BTreeMap::<_, EntrySizes>::new();
+    let mut entries = Vec::new();
+    for entry in apk.entries() {
+        let category = entry_category(&entry.name);
+        let category_sizes = categories.entry(category).or_default();
+        category_sizes.compressed_size += entry.compressed_size;
+        category_sizes.uncompressed_size += entry.uncompressed_size;
+        entries.push(EntryReport {
+            name: entry.name.clone(),
+            category,
+            sizes: EntrySizes {
+                compressed_size: entry.compressed_size,
+                uncompressed_size: entry.uncompressed_size,
+            },
+        });
+    }
+    entries.sort_by(|a, b| {
+        (b.sizes.compressed_size, &a.name).cmp(&(a.sizes.compressed_size, &b.name))
+    });
+
+    let mut libraries = Vec::new();
+    for library in rust_libraries {
+        let entry = super::shared_library_apk_path(library);
+        if apk
+            .entries()
+            .iter()
+            .any(|apk_entry| apk_entry.name == entry)
+        {
+            // Symbols are read from the unstripped library when the packaged one is stripped
+            let library_path = library.debug_symbols_path.as_ref().unwrap_or(&library.path);
+            libraries.push(library_report(entry, library_path)?);
+        }
+    }
+
+    Ok(ApkReport {
+        target,
+        abi: abi.to_owned(),
+        file_name: path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        size: fs::metadata(path)?.len(),
+        categories,
+        entries,
+        libraries,
+    })
+}
+
+fn entry_category(name: &str) -> &'static str {
+    if name.starts_with("lib/") {
+        "native libraries"
+    } else if name.starts_with("assets/") {
+        "assets"
+    } else if name.starts_with("res/") || name == "resources.arsc" {
+        "resources"
+    } else if name.starts_with("META-INF/") {
+        "signature"
+    } else {
+        "other"
+    }
+}
+
+/// Attributes the symbols of a shared library to crates
+fn library_report(entry: String, library_path: &Path) -> CargoResult<LibraryReport> {
+    let data = fs::read(library_path)
+        .map_err(|e| format_err!("Unable to read `{}`. {}", library_path.display(), e))?;
+    let (symbol_table, symbols) = Elf::parse(&data)
+        .and_then(|elf| elf.symbols())
+        .map_err(|e| format_err!("Unable to read `{}`. {}", library_path.display(), e))?;
+
+    // Aliases share the code of another symbol, so only the first symbol at each address counts
+    let mut addresses = HashSet::new();
+    let mut symbols = symbols
+        .into_iter()
+        .filter(|symbol| addresses.insert(symbol.address))
+        .map(|symbol| {
+            let (name, crate_name) = match rustc_demangle::try_demangle(&symbol.name) {
+                Ok(demangled) => {
+                    let name = format!("{:#}", demangled);
+                    let crate_name = crate_name(&name).to_owned();
+                    (name, crate_name)
+                }
+                Err(_) => (symbol.name, NON_RUST_CRATE.to_owned()),
+            };
+            SymbolReport {
+                name,
+                crate_name,
+                size: symbol.size,
+            }
+        })
+        .collect::<Vec<_>>();
+    symbols.sort_by(|a, b| (b.size, &a.name).cmp(&(a.size, &b.name)));
+
+    let mut crates = BTreeMap::<_, (u64, usize)>::new();
+    for symbol in &symbols {
+        let crate_sizes = crates.entry(symbol.crate_name.clone()).or_default();
+        crate_sizes.0 += symbol.size;
+        crate_sizes.1 += 1;
+    }
+    let mut crates = crates
+        .into_iter()
+        .map(|(name, (size, symbol_count))| CrateReport {
+            name,
+            size,
+            symbol_count,
+        })
+        .collect::<Vec<_>>();
+    crates.sort_by(|a, b| (b.size, &a.name).cmp(&(a.size, &b.name)));
+
+    Ok(LibraryReport {
+        entry,
+        full_symbol_table: symbol_table == SymbolTable::Full,
+        symbols_size: symbols.iter().map(|symbol| symbol.size).sum(),
+        crates,
+        symbols,
+    })
+}
+
+/// Returns the crate defining a demangled Rust symbol. For trait implementations, this is the
+/// crate of the implementing type, or of the trait when the type is a primitive type.
+fn crate_name(symbol: &str) -> &str {
+    let path = match symbol.strip_prefix('<') {
+        Some(qualified_path) => {
+            let self_type = qualified_path
+                .trim_start_matches(&['&', '*', '[', '('][..])
+                .trim_start_matches("mut ")
+                .trim_start_matches("const ")
+                .trim_start_matches("dyn ");
+            match (self_type.find("::"), qualified_path.find(" as ")) {
+                (Some(path_end), Some(as_start)) if path_end > as_start => {
+                    &qualified_path[as_start + 4..]
+                }
+                (None, Some(as_start)) => &qualified_path[as_start + 4..],
+                _ => self_type,
+            }
+        }
+        None => symbol,
+    };
+    path.split("::").next().unwrap_or(path)
+}
+
+fn print_table(report: &ApkReport) {
+    println!();
+    println!(
+        "{} ({}, {}): {} bytes",
+        report.file_name, report.target, report.abi, report.size
+    );
+    println!(
+        "    {:<20} {:>12} {:>12}",
+        "Category", "Compressed", "Uncompressed"
+    );
+    for (category, sizes) in &report.categories {
+        println!(
+            "    {:<20} {:>12} {:>12}",
+            category, sizes.compressed_size, sizes.uncompressed_size
+        );
+    }
+
+    println!();
+    println!("    Largest entries:");
+    println!("    {:>12} {:>12}  Name", "Compressed", "Uncompressed");
+    for entry in report.entries.iter().take(TABLE_ROW_COUNT) {
+        println!(
+            "    {:>12} {:>12}  {}",
+            entry.sizes.compressed_size, entry.sizes.uncompressed_size, entry.name
+        );
+    }
+
+    for library in &report.libraries {
+        println!();
+        println!(
+            "    {}: {} bytes of symbols{}",
+            library.entry,
+            library.symbols_size,
+            if library.full_symbol_table {
+                ""
+            } else {
+                " (stripped library, exported symbols only)"
+            }
+        );
+        println!("    {:>12} {:>7} {:>8}  Crate", "Size", "Share", "Symbols");
+        for crate_report in &library.crates {
+            println!(
+                "    {:>12} {:>6.1}% {:>8}  {}",
+                crate_report.size,
+                percentage(crate_report.size, library.symbols_size),
+                crate_report.symbol_count,
+                crate_report.name
+            );
+        }
+        println!();
+        println!("    Largest symbols:");
+        println!("    {:>12}  {:<20} Symbol", "Size", "Crate");
+        for symbol in library.symbols.iter().take(TABLE_ROW_COUNT) {
+            println!(
+                "    {:>12}  {:<20} {}",
+                symbol.size, symbol.crate_name, symbol.name
+            );
+        }
+    }
+}
+
+fn percentage(size: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        size as f64 * 100.0 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crate_names() {
+        assert_eq!(crate_name("core::fmt::write"), "core");
+        assert_eq!(
+            crate_name("<alloc::vec::Vec<T> as core::ops::drop::Drop>::drop"),
+            "alloc"
+        );
+        assert_eq!(
+            crate_name("<&mut foo::Bar as core::fmt::Debug>::fmt"),
+            "foo"
+        );
+        assert_eq!(crate_name("<u8 as core::fmt::Display>::fmt"), "core");
+        assert_eq!(
+            crate_name("<[T] as alloc::slice::SliceExt>::to_vec"),
+            "alloc"
+        );
+        assert_eq!(crate_name("<dyn core::any::Any>::is"), "core");
+    }
+}
@@ -0,0 +1,138 @@
+//! Generation of the string resources of the `strings` metadata table
+
+use super::util;
+use cargo::util::CargoResult;
+use failure::format_err;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Generates a `strings.xml` file for each locale in a resources directory. The default strings
+/// have an empty locale.
+pub fn generate(
+    strings: &BTreeMap<String, BTreeMap<String, String>>,
+    res_path: &Path,
+) -> CargoResult<()> {
+    let mut files = BTreeMap::new();
+    for (locale, locale_strings) in strings {
+        let values_directory = if locale.is_empty() {
+            "values".to_owned()
+        } else {
+            format!("values-{}", locale_qualifier(locale)?)
+        };
+        let path = PathBuf::from(values_directory).join("strings.xml");
+        if files.contains_key(&path) {
+            return Err(format_err!(
+                "The strings of locale `{}` are defined several times",
+                locale
+            ));
+        }
+
+        let mut xml = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<resources>\n".to_owned();
+        for (name, value) in locale_strings {
+            if !is_resource_name(name) {
+                return Err(format_err!("Invalid string resource name `{}`", name));
+            }
+            xml.push_str(&format!(
+                "    <string name=\"{}\">{}</string>\n",
+                name,
+                escape(value)
+            ));
+        }
+        xml.push_str("</resources>\n");
+        files.insert(path, xml.into_bytes());
+    }
+    util::write_generated_directory(res_path, &files)
+}
+
+/// Returns the resource qualifier of a locale given as a language with an optional region, such
+/// as `fr` or `pt-BR`, or as a BCP 47 tag such as `sr-Latn`. Qualifiers are accepted as is.
+fn locale_qualifier(locale: &str) -> CargoResult<String> {
+    let invalid_locale = || format_err!("Invalid locale `{}`", locale);
+    if locale.starts_with("b+") {
+        return Ok(locale.to_owned());
+    }
+    let subtags = locale.split(&['-', '_'][..]).collect::<Vec<_>>();
+    let language = subtags[0];
+    if language.len() < 2
+        || language.len() > 3
+        || !language.chars().all(|c| c.is_ascii_alphabetic())
+        || !subtags[1..]
+            .iter()
+            .all(|subtag| !subtag.is_empty() && subtag.chars().all(|c| c.is_ascii_alphanumeric()))
+    {
+        return Err(invalid_locale());
+    }
+    match subtags[1..] {
+        [] => Ok(language.to_owned()),
+        // Region qualifier such as `fr-rCA`
+        [region] if region.len() == 3 && region.starts_with('r') => {
+            Ok(format!("{}-r{}", language, region[1..].to_uppercase()))
+        }
+        [region] if region.len() == 2 && region.chars().all(|c| c.is_ascii_alphabetic()) => {
+            Ok(format!("{}-r{}", language, region.to_uppercase()))
+        }
+        _ => Ok(format!("b+{}", subtags.join("+"))),
+    }
+}
+
+/// Returns whether a name is a valid resource name
+fn is_resource_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        }
+        _ => false,
+    }
+}
+
+/// Escapes a string for XML and for aapt, which interprets quotes, backslashes and leading `@` and
+/// `?` characters
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for (index, c) in value.chars().enumerate() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\\' => escaped.push_str("\\\\"),
+            '\'' => escaped.push_str("\\'"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '@' | '?' if index == 0 => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_qualifiers() {
+        assert_eq!(locale_qualifier("fr").unwrap(), "fr");
+        assert_eq!(locale_qualifier("pt-BR").unwrap(), "pt-rBR");
+        assert_eq!(locale_qualifier("pt_br").unwrap(), "pt-rBR");
+        assert_eq!(locale_qualifier("fr-rCA").unwrap(), "fr-rCA");
+        assert_eq!(locale_qualifier("es-419").unwrap(), "b+es+419");
+        assert_eq!(locale_qualifier("sr-Latn").unwrap(), "b+sr+Latn");
+        assert_eq!(locale_qualifier("b+zh+Hant").unwrap(), "b+zh+Hant");
+        assert!(locale_qualifier("french").is_err());
+        assert!(locale_qualifier("fr-").is_err());
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            escape("@Tom's \"<app>\" & co\\"),
+            "\\@Tom\\'s \\\"&lt;app&gt;\\\" &amp; co\\\\"
+        );
+        assert_eq!(escape("a@b?"), "a@b?");
+    }
+}
@@ -11,6 +11,19 @@ impl AndroidBuildTarget {
         }
     }
 
+    /// Offset added to the version code of the APK containing only this ABI. Devices supporting
+    /// several ABIs receive the APK with the highest version code they support, so 64-bit ABIs
+    /// get a higher offset than their 32-bit counterparts. Follows the convention documented for
+    /// Google Play multiple APK support.
+    pub fn version_code_offset(self) -> i32 {
+        match self {
+            AndroidBuildTarget::ArmV7a => 1000,
+            AndroidBuildTarget::Arm64V8a => 2000,
+            AndroidBuildTarget::X86 => 3000,
+            AndroidBuildTarget::X86_64 => 4000,
+        }
+    }
+
     /// Returns the triple used by the rust build tools
     pub fn rust_triple(self) -> &'static str {
         match self {
@@ -37,3 +37,30 @@ impl Drop for TempFile {
         })
     }
 }
+
+/// Temporary directory used by tests which is deleted with its contents when dropped.
+#[cfg(test)]
+pub struct TempDir {
+    pub path: PathBuf,
+}
+
+#[cfg(test)]
+impl TempDir {
+    /// Creates an empty directory in the system temporary directory. `name` has to be unique
+    /// among the tests since they run concurrently.
+    pub fn new(name: &str) -> CargoResult<TempDir> {
+        let path = std::env::temp_dir().join(format!("cargo-apk-{}-{}", name, std::process::id()));
+        if path.exists() {
+            fs::remove_dir_all(&path)?;
+        }
+        fs::create_dir_all(&path)?;
+        Ok(TempDir { path })
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
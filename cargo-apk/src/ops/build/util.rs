@@ -2,21 +2,24 @@ use crate::config::{AndroidBuildTarget, AndroidConfig};
 use cargo::core::{Target, TargetKind, Workspace};
 use cargo::util::{process, CargoResult, ProcessBuilder};
 use failure::format_err;
+use std::collections::BTreeMap;
 use std::ffi::OsStr;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 /// Returns the directory in which all cargo apk artifacts for the current
-/// debug/release configuration should be produced.
+/// debug/release configuration and flavor should be produced.
 pub fn get_root_build_directory(workspace: &Workspace, config: &AndroidConfig) -> PathBuf {
     let android_artifacts_dir = workspace
         .target_dir()
         .join("android-artifacts")
         .into_path_unlocked();
 
-    if config.release {
-        android_artifacts_dir.join("release")
-    } else {
-        android_artifacts_dir.join("debug")
+    let profile = if config.release { "release" } else { "debug" };
+    match &config.flavor {
+        Some(flavor) => android_artifacts_dir.join(format!("{}-{}", flavor, profile)),
+        None => android_artifacts_dir.join(profile),
     }
 }
 
@@ -157,6 +160,99 @@ pub fn find_readelf(
     }
 }
 
+// Returns path to strip
+pub fn find_strip(
+    config: &AndroidConfig,
+    build_target: AndroidBuildTarget,
+) -> CargoResult<PathBuf> {
+    let strip_path = llvm_toolchain_root(config).join("bin").join(format!(
+        "{}-strip{}",
+        build_target.ndk_triple(),
+        EXECUTABLE_SUFFIX_EXE
+    ));
+    if strip_path.exists() {
+        Ok(strip_path)
+    } else {
+        Err(format_err!(
+            "Unable to find strip at `{}`",
+            strip_path.to_string_lossy()
+        ))
+    }
+}
+
+/// Returns the current time in seconds since the unix epoch
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Converts seconds since the unix epoch to (year, month, day, hour, minute, second) in UTC
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+pub fn civil_from_unix_timestamp(secs: u64) -> (u64, u64, u64, u64, u64, u64) {
+    let days = secs / 86400;
+    let seconds_of_day = secs % 86400;
+
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        (seconds_of_day % 3600) / 60,
+        seconds_of_day % 60,
+    )
+}
+
+/// Writes a file unless it already has the given contents, so that its modification time only
+/// changes with its contents
+pub fn write_if_changed(path: &Path, contents: &[u8]) -> CargoResult<()> {
+    if fs::read(path).ok().as_deref() != Some(contents) {
+        fs::write(path, contents)
+            .map_err(|e| format_err!("Unable to write `{}`. {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Writes the files of a generated directory, given by path relative to the directory. Files which
+/// didn't change are not rewritten, and files of previous builds which are not part of the
+/// directory anymore are removed.
+pub fn write_generated_directory(
+    directory: &Path,
+    files: &BTreeMap<PathBuf, Vec<u8>>,
+) -> CargoResult<()> {
+    if directory.exists() {
+        let mut subdirectories = vec![directory.to_owned()];
+        while let Some(subdirectory) = subdirectories.pop() {
+            for entry in fs::read_dir(&subdirectory)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    subdirectories.push(path);
+                } else if !files.contains_key(path.strip_prefix(directory).unwrap()) {
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+    }
+    for (path, contents) in files {
+        let path = directory.join(path);
+        fs::create_dir_all(path.parent().unwrap())?;
+        write_if_changed(&path, contents)?;
+    }
+    Ok(())
+}
+
 /// Returns a ProcessBuilder which runs the specified command. Uses "cmd" on windows in order to
 /// allow execution of batch files.
 pub fn script_process(cmd: impl AsRef<OsStr>) -> ProcessBuilder {
@@ -185,19 +281,13 @@ const HOST_TAG: &str = "darwin-x86_64";
 // On non-windows platforms they are empty.
 
 #[cfg(target_os = "windows")]
-const EXECUTABLE_SUFFIX_EXE: &str = ".exe";
+pub const EXECUTABLE_SUFFIX_EXE: &str = ".exe";
 
 #[cfg(not(target_os = "windows"))]
-const EXECUTABLE_SUFFIX_EXE: &str = "";
+pub const EXECUTABLE_SUFFIX_EXE: &str = "";
 
 #[cfg(target_os = "windows")]
 const EXECUTABLE_SUFFIX_CMD: &str = ".cmd";
 
 #[cfg(not(target_os = "windows"))]
 const EXECUTABLE_SUFFIX_CMD: &str = "";
-
-#[cfg(target_os = "windows")]
-pub const EXECUTABLE_SUFFIX_BAT: &str = ".bat";
-
-#[cfg(not(target_os = "windows"))]
-pub const EXECUTABLE_SUFFIX_BAT: &str = "";
@@ -0,0 +1,138 @@
+use crate::ops::build::apk::{ApkReader, Compression};
+use crate::ops::build::axml::{self, DecodedElement, Value};
+use crate::ops::build::sign;
+use cargo::util::CargoResult;
+use failure::format_err;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Log2 of the largest alignment reported for uncompressed entries
+const MAX_REPORTED_ALIGNMENT_BITS: u32 = 16;
+
+/// Prints the decoded manifest, the entries, the ABIs and the signer certificates of an APK
+pub fn inspect(apk_path: &Path) -> CargoResult<()> {
+    let apk = ApkReader::open(apk_path)?;
+
+    let manifest_entry = apk
+        .entries()
+        .iter()
+        .find(|entry| entry.name == "AndroidManifest.xml")
+        .ok_or_else(|| format_err!("`{}` has no AndroidManifest.xml", apk_path.display()))?;
+    let manifest = axml::decode(&apk.read(manifest_entry)?)
+        .map_err(|e| format_err!("Unable to decode AndroidManifest.xml. {}", e))?;
+    print_manifest(&manifest);
+
+    // ABIs of the shared libraries
+    let abis = apk
+        .entries()
+        .iter()
+        .filter_map(|entry| {
+            let mut components = entry.name.split('/');
+            match (components.next(), components.next(), components.next()) {
+                (Some("lib"), Some(abi), Some(_)) => Some(abi),
+                _ => None,
+            }
+        })
+        .collect::<BTreeSet<_>>();
+    println!();
+    if abis.is_empty() {
+        println!("ABIs: none");
+    } else {
+        println!("ABIs: {}", abis.into_iter().collect::<Vec<_>>().join(", "));
+    }
+
+    println!();
+    println!("Entries:");
+    println!(
+        "    {:<8} {:>12} {:>12} {:>9}  Name",
+        "Method", "Compressed", "Uncompressed", "Alignment"
+    );
+    for entry in apk.entries() {
+        let (method, alignment) = match entry.compression {
+            Compression::Stored => ("Stored", offset_alignment(entry.data_offset).to_string()),
+            Compression::Deflated => ("Deflated", "-".to_owned()),
+        };
+        println!(
+            "    {:<8} {:>12} {:>12} {:>9}  {}",
+            method, entry.compressed_size, entry.uncompressed_size, alignment, entry.name
+        );
+    }
+
+    println!();
+    let certificates = sign::signer_certificates(&apk)?;
+    if certificates.is_empty() {
+        println!("Signers: none");
+    } else {
+        println!("Signers:");
+        for (scheme, certificate) in certificates {
+            println!("    {}: {}", scheme, certificate.subject_name()?);
+            println!("        SHA-256: {}", certificate.sha256_fingerprint());
+        }
+    }
+
+    Ok(())
+}
+
+fn print_manifest(manifest: &DecodedElement) {
+    let value = |value: Option<&Value>| value.map_or("-".to_owned(), Value::to_string);
+
+    println!("Package: {}", value(manifest.attribute("package")));
+    println!(
+        "Version code: {}",
+        value(manifest.android_attribute("versionCode"))
+    );
+    println!(
+        "Version name: {}",
+        value(manifest.android_attribute("versionName"))
+    );
+    let uses_sdk = manifest.children("uses-sdk").next();
+    println!(
+        "Min SDK version: {}",
+        value(uses_sdk.and_then(|e| e.android_attribute("minSdkVersion")))
+    );
+    println!(
+        "Target SDK version: {}",
+        value(uses_sdk.and_then(|e| e.android_attribute("targetSdkVersion")))
+    );
+
+    // The name of the shared library loaded by NativeActivity
+    let lib_name = manifest
+        .children("application")
+        .flat_map(|application| application.children("activity"))
+        .flat_map(|activity| activity.children("meta-data"))
+        .find(|meta_data| {
+            meta_data.android_attribute("name")
+                == Some(&Value::String("android.app.lib_name".to_owned()))
+        })
+        .and_then(|meta_data| meta_data.android_attribute("value"));
+    println!("Library name: {}", value(lib_name));
+
+    println!("Permissions:");
+    for permission in manifest.children("uses-permission") {
+        println!("    {}", value(permission.android_attribute("name")));
+    }
+
+    println!("Features:");
+    for feature in manifest.children("uses-feature") {
+        let name = match (
+            feature.android_attribute("name"),
+            feature.android_attribute("glEsVersion"),
+        ) {
+            (Some(name), _) => name.to_string(),
+            (None, Some(Value::IntHex(version))) => {
+                format!("OpenGL ES {}.{}", version >> 16, version & 0xffff)
+            }
+            (None, version) => format!("OpenGL ES {}", value(version)),
+        };
+        let required = match feature.android_attribute("required") {
+            Some(Value::Boolean(false)) => "not required",
+            _ => "required",
+        };
+        println!("    {} ({})", name, required);
+    }
+}
+
+/// Returns the largest power of two dividing the offset, up to 2^`MAX_REPORTED_ALIGNMENT_BITS`
+fn offset_alignment(offset: u64) -> u64 {
+    1 << offset.trailing_zeros().min(MAX_REPORTED_ALIGNMENT_BITS)
+}
@@ -5,6 +5,8 @@ use cargo::core::Workspace;
 use cargo::util::process_builder::process;
 use cargo::util::CargoResult;
 use clap::ArgMatches;
+use failure::format_err;
+use std::path::PathBuf;
 
 pub fn install(
     workspace: &Workspace,
@@ -15,7 +17,47 @@ pub fn install(
 
     let adb = config.sdk_path.join("platform-tools/adb");
 
-    for apk_path in build_result.target_to_apk_map.values() {
+    // Install the APK built for the preferred ABI of the device when APKs are split per ABI and
+    // no universal APK was built
+    let mut apk_paths: Vec<&PathBuf> = build_result.target_to_apk_map.values().collect();
+    let split_apks = build_result
+        .target_to_split_apks_map
+        .iter()
+        .filter(|(target, _)| !build_result.target_to_apk_map.contains_key(target))
+        .map(|(_, split_apks)| split_apks)
+        .collect::<Vec<_>>();
+    if !split_apks.is_empty() {
+        let output = process(&adb)
+            .arg("shell")
+            .arg("getprop")
+            .arg("ro.product.cpu.abilist")
+            .exec_with_output()?;
+        let device_abis = String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .split(',')
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        for split_apks in split_apks {
+            let apk_path = device_abis
+                .iter()
+                .filter_map(|device_abi| {
+                    split_apks
+                        .iter()
+                        .find(|(abi, _)| abi.android_abi() == device_abi)
+                        .map(|(_, apk_path)| apk_path)
+                })
+                .next()
+                .ok_or_else(|| {
+                    format_err!(
+                        "No APK built for the ABIs supported by the device: {}",
+                        device_abis.join(", ")
+                    )
+                })?;
+            apk_paths.push(apk_path);
+        }
+    }
+
+    for apk_path in apk_paths {
         drop(writeln!(
             workspace.config().shell().err(),
             "Installing apk '{}' to the device",
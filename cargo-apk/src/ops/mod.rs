@@ -1,8 +1,13 @@
 mod build;
+mod inspect;
 mod install;
 mod run;
 
 pub use self::build::build;
 pub use self::build::BuildResult;
+pub use self::inspect::inspect;
 pub use self::install::install;
 pub use self::run::run;
+
+#[cfg(test)]
+pub use self::build::tempfile::TempDir;
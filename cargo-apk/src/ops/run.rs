@@ -5,6 +5,7 @@ use cargo::util::process_builder::process;
 use cargo::util::CargoResult;
 use clap::ArgMatches;
 use failure::format_err;
+use std::collections::BTreeSet;
 
 pub fn run(workspace: &Workspace, config: &AndroidConfig, options: &ArgMatches) -> CargoResult<()> {
     let build_result = install::install(workspace, config, options)?;
@@ -19,13 +20,13 @@ pub fn run(workspace: &Workspace, config: &AndroidConfig, options: &ArgMatches)
     } else if let Some(example) = options.value_of("example") {
         (TargetKind::ExampleBin, example.to_owned())
     } else {
-        match build_result.target_to_apk_map.len() {
-            1 => build_result
-                .target_to_apk_map
-                .keys()
-                .next()
-                .unwrap()
-                .to_owned(),
+        let targets = build_result
+            .target_to_apk_map
+            .keys()
+            .chain(build_result.target_to_split_apks_map.keys())
+            .collect::<BTreeSet<_>>();
+        match targets.len() {
+            1 => targets.into_iter().next().unwrap().to_owned(),
             0 => return Err(format_err!("No APKs to execute.")),
             _ => {
                 return Err(format_err!(
@@ -0,0 +1,13 @@
+#!/bin/bash
+# Generates the keys used by the signing tests. The store and key password is `android`.
+
+set -e
+
+cd "$(dirname "$0")"
+
+openssl genpkey -quiet -algorithm RSA -pkeyopt rsa_keygen_bits:2048 -out test.pem
+openssl req -new -x509 -key test.pem -subj "/CN=cargo-apk test" -days 36500 -out test.x509.pem
+openssl pkcs8 -topk8 -nocrypt -in test.pem -outform DER -out test.pk8
+openssl pkcs12 -export -in test.x509.pem -inkey test.pem -name test -passout pass:android \
+    -out test.p12
+cat test.x509.pem >> test.pem